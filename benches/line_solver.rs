@@ -0,0 +1,60 @@
+// Times the line solver against the old pattern enumerating one on every
+// shipped level. Run with `cargo bench`.
+use logicpaint::editor::solver::{TheMultiVerseOfLines, collapse_by_enumeration};
+use logicpaint::levels::load_levels_from_dir;
+use logicpaint::playstate::PlayState;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 20;
+
+fn average_time<T>(mut solve: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        std::hint::black_box(solve());
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("levels");
+    let levels = load_levels_from_dir(&dir).expect("the shipped levels should load");
+
+    println!(
+        "{:<16} {:>7} {:>14} {:>14} {:>8}",
+        "level", "size", "line solver", "enumerating", "speedup"
+    );
+    let mut total_dp = Duration::ZERO;
+    let mut total_enumerated = Duration::ZERO;
+    for level in &levels {
+        let play_state: PlayState = level.into();
+        let dp = average_time(|| {
+            let mut multiverse = TheMultiVerseOfLines::new(&play_state);
+            multiverse.collapse();
+            // line_state, not state, so a stuck level doesn't time a search
+            // the enumerating solver never does
+            multiverse.line_state()
+        });
+        let enumerated = average_time(|| collapse_by_enumeration(&play_state));
+        total_dp += dp;
+        total_enumerated += enumerated;
+
+        let name = level.path.file_stem().unwrap_or_default().to_string_lossy();
+        println!(
+            "{:<16} {:>7} {:>14.2?} {:>14.2?} {:>7.1}x",
+            name,
+            format!("{}x{}", play_state.num_columns, play_state.num_rows),
+            dp,
+            enumerated,
+            enumerated.as_secs_f64() / dp.as_secs_f64()
+        );
+    }
+    println!(
+        "{:<16} {:>7} {:>14.2?} {:>14.2?} {:>7.1}x",
+        "total",
+        "",
+        total_dp,
+        total_enumerated,
+        total_enumerated.as_secs_f64() / total_dp.as_secs_f64()
+    );
+}
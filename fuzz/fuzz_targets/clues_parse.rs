@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use logicpaint::clues::{ClueFormat, Clues};
+
+// The first byte picks the format and the rest is the file
+fuzz_target!(|data: &[u8]| {
+    let Some((&pick, rest)) = data.split_first() else {
+        return;
+    };
+    let Ok(text) = std::str::from_utf8(rest) else {
+        return;
+    };
+    let format = ClueFormat::ALL[pick as usize % ClueFormat::ALL.len()];
+    // Anything that parses has to write back out as the same clues
+    if let Ok(clues) = Clues::parse(format, text)
+        && let Ok(written) = clues.write(format)
+    {
+        assert_eq!(Ok(clues), Clues::parse(format, &written));
+    }
+});
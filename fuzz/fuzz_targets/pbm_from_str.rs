@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use logicpaint::netpbm::Pbm;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    // Anything that parses has to print back out as the same picture
+    if let Ok(pbm) = text.parse::<Pbm>() {
+        let again: Pbm = pbm.to_string().parse().expect("printed pbm should parse");
+        assert_eq!(pbm.cells, again.cells);
+    }
+});
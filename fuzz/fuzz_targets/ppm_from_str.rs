@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use logicpaint::netpbm::Ppm;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    // Anything that parses has to print back out as the same picture
+    if let Ok(ppm) = text.parse::<Ppm>() {
+        let again: Ppm = ppm.to_string().parse().expect("printed ppm should parse");
+        assert_eq!(ppm.cells, again.cells);
+    }
+});
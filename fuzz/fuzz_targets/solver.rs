@@ -0,0 +1,40 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use logicpaint::editor::difficulty::trace_solve;
+use logicpaint::editor::solver::{SolvedState, TheMultiVerseOfLines};
+use logicpaint::netpbm::Pbm;
+use logicpaint::playstate::PlayState;
+
+// Takes .pbm pictures, so the shipped levels make a good starting corpus
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(pbm) = text.parse::<Pbm>() else {
+        return;
+    };
+    // Anything much bigger is just slow rather than interesting
+    if pbm.width == 0 || pbm.height == 0 || pbm.width.max(pbm.height) > 30 {
+        return;
+    }
+
+    let play_state: PlayState = (&pbm).into();
+    let mut multiverse = TheMultiVerseOfLines::new(&play_state);
+    multiverse.collapse();
+    // The picture the clues came from always fits them
+    assert_ne!(SolvedState::Unsolvable, multiverse.line_state());
+    for (idx, &filled) in pbm.cells.iter().enumerate() {
+        let colors = multiverse.cell_colors(idx / pbm.width, idx % pbm.width);
+        assert_ne!(0, colors & (1 << filled as u32));
+    }
+
+    let known: Vec<u32> = pbm.cells.iter().map(|&filled| 1 << filled as u32).collect();
+    let pinned = TheMultiVerseOfLines::with_known(&play_state, &known)
+        .expect("the original picture can't contradict its own clues");
+    assert_eq!(SolvedState::UniqueSolution, pinned.line_state());
+
+    if multiverse.line_state() == SolvedState::UniqueSolution {
+        trace_solve(&play_state);
+    }
+});
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Starts each fuzz target's corpus off with the shipped levels
+set -e
+cd "$(dirname "$0")"
+mkdir -p corpus/pbm_from_str corpus/ppm_from_str corpus/solver corpus/clues_parse
+cp ../levels/*.pbm corpus/pbm_from_str/
+cp ../levels/*.pbm corpus/solver/
+cp ../levels/*.ppm corpus/ppm_from_str/
+# A plus sign in each clue format, the first byte picks which one it's read as
+printf '\000rows\n1\n3\n1\ncolumns\n1\n3\n1\n' > corpus/clues_parse/plus.txt
+printf '\001width 3\nheight 3\nrows\n1\n3\n1\ncolumns\n1\n3\n1\n' > corpus/clues_parse/plus.non
+printf '\002#d\n a:X #FF0000 red\n: rows\n1a\n3a\n1a\n: columns\n1a\n3a\n1a\n' > corpus/clues_parse/plus.g
+printf '\003{"rows": [[1], [3], [1]], "cols": [[1], [3], [1]]}' > corpus/clues_parse/plus.json
@@ -1,10 +1,17 @@
 use logicpaint::base_dir;
-use logicpaint::editor::editor_grids::{EditorGrids, save_grid_as_level};
+use logicpaint::clues::{ClueFormat, Clues, import_level};
+use logicpaint::editor::edit_history::EditHistory;
+use logicpaint::editor::editor_grids::{EditorGrids, recovery_level, save_grid_as_level};
 use logicpaint::editor::editor_settings::LevelSettings;
 use logicpaint::editor::editor_ui_actions::UiActions;
 use logicpaint::editor::editor_ui_actions::{IOWorkerRequest, IOWorkerResponse};
-use logicpaint::editor::solver_display::SolverDisplay;
+use logicpaint::editor::editor_ui_actions::{SolverWorkerRequest, SolverWorkerResponse};
+use logicpaint::editor::mask_from_image::{color_image_from_mask, mask_from_image};
+use logicpaint::editor::solver_display::{SolverDisplay, repairs_for_display, solve_for_display};
+use logicpaint::editor::transforms;
+use logicpaint::editor::unsaved_changes::{Discarding, UnsavedChangesPrompt, UnsavedChoice};
 use logicpaint::levels::{Level, load_level};
+use logicpaint::playstate::PlayState;
 use logicpaint::pop_up::PopUp;
 
 use rfd::FileDialog;
@@ -14,11 +21,11 @@ use std::thread;
 
 use egor::{
     app::{App, WindowEvent},
-    input::KeyCode,
+    input::{KeyCode, MouseButton},
 };
 
 use egor::{
-    app::{egui::Align2, egui::Window},
+    app::{egui::Align2, egui::Id, egui::Window},
     render::Color,
 };
 
@@ -28,9 +35,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut save_pop_up: Option<PopUp> = None;
     let mut solver = SolverDisplay::default();
     let mut last_known_solve = solver.recompute(&level_settings, &grids);
+    let mut history = EditHistory::new(&level_settings, &grids);
+    let mut unsaved_prompt = UnsavedChangesPrompt::default();
+    // Whether the design might have changed since the history last looked
+    let mut unsettled = false;
 
     let base = base_dir().join("levels");
     let (io_sender, io_reciever) = spawn_io_worker(base);
+    let (solver_sender, solver_reciever) = spawn_solver_worker();
 
     App::new()
         .window_size(1280, 720)
@@ -39,20 +51,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             for event in &frame_context.events {
                 match event {
                     WindowEvent::CloseRequested => {
+                        // egor ends the event loop on its own when the window is closed, so
+                        // unlike escape there's no chance to ask about unsaved changes here.
+                        // Tuck them away somewhere instead of losing them.
+                        history.settle(&level_settings, &grids);
+                        if history.has_unsaved_changes() {
+                            match save_recovery(&level_settings, &grids) {
+                                Ok(path) => eprintln!("unsaved changes were kept in {:?}", path),
+                                Err(error) => {
+                                    eprintln!(
+                                        "while keeping unsaved changes, experienced: {}",
+                                        error
+                                    )
+                                }
+                            }
+                        }
                         // This feels very silly to have to clone to avoid a move when the program is
                         // going to kill itself. But that's what rust feels like doing.
-                        kill_self(io_sender.clone());
+                        kill_self(io_sender.clone(), solver_sender.clone());
                     }
                     _ => {}
                 }
             }
-            if frame_context.input.key_pressed(KeyCode::Escape) {
-                kill_self(io_sender.clone());
+
+            // Something that would throw the design away, and then whether it's fine to
+            let mut wants_to: Option<Discarding> = None;
+            let mut go_ahead: Option<Discarding> = None;
+            let unsaved_changes = history.has_unsaved_changes();
+            if frame_context.input.key_pressed(KeyCode::Escape) && !unsaved_prompt.is_open() {
+                wants_to = Some(Discarding::Quit);
             }
 
             if let Ok(io_response) = io_reciever.try_recv() {
                 match io_response {
-                    IOWorkerResponse::IoOpenChoice(to_open) => match load_level(to_open) {
+                    IOWorkerResponse::IoOpenChoice(to_open) => match open_level(to_open) {
                         Err(error) => {
                             save_pop_up = Some(PopUp {
                                 heading: "Error".to_owned(),
@@ -62,75 +94,265 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                         Ok(level) => {
                             load_level_in_editor(level, &mut level_settings, &mut grids);
+                            history.reset(&level_settings, &grids);
+                            // Nothing worked out about the old design fits this one
+                            solve_in_background(
+                                &mut solver,
+                                &solver_sender,
+                                &level_settings,
+                                &grids,
+                            );
                         }
                     },
                 }
             }
 
+            // Only the newest drawing's solve is worth showing on the grid
+            while let Ok(response) = solver_reciever.try_recv() {
+                match response {
+                    SolverWorkerResponse::Solved(outcome) => {
+                        if let Some(possibilities) = solver.receive(outcome) {
+                            last_known_solve = possibilities;
+                        }
+                    }
+                    SolverWorkerResponse::Repairs { job, repairs } => {
+                        solver.receive_repairs(job, repairs);
+                    }
+                }
+            }
+
             let gfx = &mut (frame_context.gfx);
             let egui_ctx = frame_context.egui_ctx;
 
+            // Typing in the filename box has its own undo
+            let input = &frame_context.input;
+            let ctrl = input.keys_held(&[KeyCode::ControlLeft, KeyCode::ControlRight]);
+            if ctrl && input.key_pressed(KeyCode::KeyZ) && !egui_ctx.wants_keyboard_input() {
+                let shift = input.keys_held(&[KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+                let changed = match shift {
+                    true => history.redo(&mut level_settings, &mut grids),
+                    false => history.undo(&mut level_settings, &mut grids),
+                };
+                if changed {
+                    solve_in_background(&mut solver, &solver_sender, &level_settings, &grids);
+                }
+            }
+
             gfx.clear(Color::new([0.5, 0.5, 0.5, 1.0]));
 
-            match grids.ui(frame_context, &mut level_settings, &last_known_solve) {
+            match grids.ui(
+                frame_context,
+                &mut level_settings,
+                &last_known_solve,
+                &solver.differing_cells,
+                solver.replay.as_ref(),
+            ) {
                 UiActions::LevelGridUpdated => {
-                    last_known_solve = solver.recompute(&level_settings, &grids);
+                    unsettled = true;
+                    solve_in_background(&mut solver, &solver_sender, &level_settings, &grids);
                 }
                 _ => {}
             };
-            Window::new("Settings")
+            let title = match unsaved_changes {
+                true => format!("Settings - {} (unsaved changes)", level_settings.filename),
+                false => format!("Settings - {}", level_settings.filename),
+            };
+            Window::new(title)
+                // Or it would count as a new window whenever the title changes
+                .id(Id::new("settings"))
                 .anchor(Align2::LEFT_TOP, egor::app::egui::Vec2::ZERO)
                 .default_size([90.0, 400.0])
                 .show(egui_ctx, |ui| {
-                    match level_settings.ui(ui) {
+                    // egor doesn't let us retitle the editor's own window, so this
+                    // window's title is where unsaved changes show up
+                    if unsaved_changes {
+                        ui.label("Closing the window loses unsaved changes, press Escape to quit.");
+                    }
+                    let action = level_settings.ui(ui);
+                    if !matches!(action, UiActions::Nothing) {
+                        unsettled = true;
+                    }
+                    match action {
                         UiActions::Nothing => {}
                         UiActions::LevelGridUpdated => {
-                            unreachable!();
+                            solve_in_background(
+                                &mut solver,
+                                &solver_sender,
+                                &level_settings,
+                                &grids,
+                            );
                         }
                         UiActions::RecomputePalette => {
                             level_settings.refresh_palette_with(grids.unique_colors());
                         }
-                        UiActions::OpenLevel => {
-                            match io_sender.send(IOWorkerRequest::OpenFileDialog) {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    save_pop_up = Some(PopUp {
-                                        heading: "Error".to_owned(),
-                                        msg: format!(
-                                            "An error has occurred, cannot open file: {}",
-                                            error
-                                        )
-                                        .to_owned(),
-                                        visible: true,
-                                    });
-                                }
+                        UiActions::Undo => {
+                            if history.undo(&mut level_settings, &mut grids) {
+                                solve_in_background(
+                                    &mut solver,
+                                    &solver_sender,
+                                    &level_settings,
+                                    &grids,
+                                );
                             }
                         }
-                        UiActions::SaveLevel => {
+                        UiActions::Redo => {
+                            if history.redo(&mut level_settings, &mut grids) {
+                                solve_in_background(
+                                    &mut solver,
+                                    &solver_sender,
+                                    &level_settings,
+                                    &grids,
+                                );
+                            }
+                        }
+                        UiActions::Transform(transform) => {
+                            if transforms::apply(transform, &mut level_settings, &mut grids) {
+                                solve_in_background(
+                                    &mut solver,
+                                    &solver_sender,
+                                    &level_settings,
+                                    &grids,
+                                );
+                            }
+                        }
+                        UiActions::Resize { width, height } => {
+                            grids.resize(&mut level_settings, width, height);
+                            solve_in_background(
+                                &mut solver,
+                                &solver_sender,
+                                &level_settings,
+                                &grids,
+                            );
+                        }
+                        UiActions::MaskFromImage(rule) => {
+                            if mask_from_image(&rule, &level_settings, &mut grids) {
+                                solve_in_background(
+                                    &mut solver,
+                                    &solver_sender,
+                                    &level_settings,
+                                    &grids,
+                                );
+                            }
+                        }
+                        UiActions::ColorImageFromMask => {
+                            let color = level_settings.current_color;
+                            if color_image_from_mask(color, &level_settings, &mut grids) {
+                                level_settings.refresh_palette_with(grids.unique_colors());
+                                solve_in_background(
+                                    &mut solver,
+                                    &solver_sender,
+                                    &level_settings,
+                                    &grids,
+                                );
+                            }
+                        }
+                        // Only the solver display asks for these
+                        UiActions::FindRepairs
+                        | UiActions::ToggleCells(_)
+                        | UiActions::StartReplay => {}
+                        UiActions::ExportClues(format) => {
                             let level = save_grid_as_level(&level_settings, &grids);
-                            match level.save() {
-                                Ok(_) => {
-                                    save_pop_up = Some(PopUp {
-                                        heading: "Saved".to_owned(),
-                                        msg: "Your level has been saved".to_owned(),
-                                        visible: true,
-                                    });
-                                }
-                                Err(error) => {
-                                    save_pop_up = Some(PopUp {
-                                        heading: "Error".to_owned(),
-                                        msg: format!("Error: {error}").to_owned(),
-                                        visible: true,
-                                    });
+                            save_pop_up = Some(match export_clues(&level, format) {
+                                Ok(path) => PopUp {
+                                    heading: "Exported".to_owned(),
+                                    msg: format!("Clues written to {}", path.display()),
+                                    visible: true,
+                                },
+                                Err(error) => PopUp {
+                                    heading: "Error".to_owned(),
+                                    msg: format!("Could not export clues: {error}"),
+                                    visible: true,
+                                },
+                            });
+                        }
+                        UiActions::OpenLevel => wants_to = Some(Discarding::OpenLevel),
+                        UiActions::NewLevel => wants_to = Some(Discarding::NewLevel),
+                        UiActions::SaveLevel => {
+                            save_pop_up = Some(match save_level(&level_settings, &grids) {
+                                Ok(saved) => {
+                                    history.mark_saved(&level_settings, &grids);
+                                    saved
                                 }
+                                Err(error) => error,
+                            });
+                        }
+                    }
+                    match solver.ui(ui) {
+                        UiActions::FindRepairs => {
+                            let request = solver.request_repairs(&level_settings, &grids);
+                            if let Err(error) = solver_sender.send(request) {
+                                eprintln!("could not send to the solver worker: {}", error);
                             }
                         }
+                        UiActions::StartReplay => {
+                            solver.start_replay(&level_settings, &grids);
+                        }
+                        UiActions::ToggleCells(cells) => {
+                            unsettled = true;
+                            grids.toggle_cells(&cells);
+                            solve_in_background(
+                                &mut solver,
+                                &solver_sender,
+                                &level_settings,
+                                &grids,
+                            );
+                        }
+                        _ => {}
                     }
-                    solver.ui(ui);
                     if let Some(popup) = save_pop_up.as_mut() {
                         popup.ui(ui);
                     }
+                    match unsaved_prompt.ui(ui) {
+                        Some((discarding, UnsavedChoice::SaveFirst)) => {
+                            match save_level(&level_settings, &grids) {
+                                Ok(_) => {
+                                    history.mark_saved(&level_settings, &grids);
+                                    go_ahead = Some(discarding);
+                                }
+                                Err(error) => save_pop_up = Some(error),
+                            }
+                        }
+                        Some((discarding, UnsavedChoice::Discard)) => go_ahead = Some(discarding),
+                        Some((_, UnsavedChoice::Cancel)) | None => {}
+                    }
                 });
+
+            if let Some(discarding) = wants_to {
+                match unsaved_changes {
+                    true => unsaved_prompt.ask(discarding),
+                    false => go_ahead = Some(discarding),
+                }
+            }
+            match go_ahead {
+                Some(Discarding::Quit) => kill_self(io_sender.clone(), solver_sender.clone()),
+                Some(Discarding::OpenLevel) => {
+                    if let Err(error) = io_sender.send(IOWorkerRequest::OpenFileDialog) {
+                        save_pop_up = Some(PopUp {
+                            heading: "Error".to_owned(),
+                            msg: format!("An error has occurred, cannot open file: {}", error),
+                            visible: true,
+                        });
+                    }
+                }
+                Some(Discarding::NewLevel) => {
+                    level_settings.new_level();
+                    grids = EditorGrids::new(level_settings.width, level_settings.height);
+                    history.reset(&level_settings, &grids);
+                    solve_in_background(&mut solver, &solver_sender, &level_settings, &grids);
+                }
+                None => {}
+            }
+
+            // A stroke only becomes one undo step once the mouse lets go, and
+            // the design only gets compared then or after an action
+            let input = &frame_context.input;
+            let mouse_held =
+                input.mouse_held(MouseButton::Left) || input.mouse_held(MouseButton::Right);
+            unsettled |= mouse_held;
+            if unsettled && !mouse_held {
+                history.settle(&level_settings, &grids);
+                unsettled = false;
+            }
         });
 
     Ok(())
@@ -148,6 +370,7 @@ fn spawn_io_worker(
                 IOWorkerRequest::OpenFileDialog => {
                     let selected_file = FileDialog::new()
                         .add_filter("level", &["level"])
+                        .add_filter("clues", &ClueFormat::ALL.map(|format| format.extension()))
                         .set_directory(&start_directory)
                         .pick_file();
                     if let Some(file) = selected_file {
@@ -166,16 +389,156 @@ fn spawn_io_worker(
     (main_thread_sender, worker_thread_reciever)
 }
 
-fn kill_self(io_sender: Sender<IOWorkerRequest>) -> ! {
+// Solves drawings one at a time as they come in, and looks for repairs when
+// asked. When the drawing changes again mid solve, the newer request is picked
+// up in place of finishing the old one, and anything else that piled up in the
+// meantime is skipped over.
+fn spawn_solver_worker() -> (Sender<SolverWorkerRequest>, Receiver<SolverWorkerResponse>) {
+    let (main_thread_sender, main_thread_reciever) = channel::<SolverWorkerRequest>();
+    let (worker_thread_sender, worker_thread_reciever) = channel::<SolverWorkerResponse>();
+    thread::spawn(move || {
+        let mut jobs = SolverJobs::default();
+        while jobs.wait_for(&main_thread_reciever) {
+            let response = if let Some((job, play_state, too_many_colors)) = jobs.solve.take() {
+                solve_for_display(job, &play_state, too_many_colors, || {
+                    jobs.catch_up(&main_thread_reciever)
+                })
+                .map(SolverWorkerResponse::Solved)
+            } else if let Some((job, level_settings, grids)) = jobs.repairs.take() {
+                repairs_for_display(&level_settings, &grids, || {
+                    jobs.catch_up(&main_thread_reciever)
+                })
+                .map(|repairs| SolverWorkerResponse::Repairs { job, repairs })
+            } else {
+                None
+            };
+            if let Some(response) = response
+                && let Err(error) = worker_thread_sender.send(response)
+            {
+                eprintln!("solver worker experienced error: {}", error);
+                break;
+            }
+        }
+    });
+    (main_thread_sender, worker_thread_reciever)
+}
+
+// The newest of each kind of request the solver worker has yet to get to
+#[derive(Default)]
+struct SolverJobs {
+    solve: Option<(u64, Box<PlayState>, bool)>,
+    repairs: Option<(u64, Box<LevelSettings>, Box<EditorGrids>)>,
+    shutdown: bool,
+}
+
+impl SolverJobs {
+    // Blocks until there's something to do, false once it's time to stop
+    fn wait_for(&mut self, reciever: &Receiver<SolverWorkerRequest>) -> bool {
+        if self.solve.is_none() && self.repairs.is_none() {
+            match reciever.recv() {
+                Ok(request) => self.hear(request),
+                Err(_) => self.shutdown = true,
+            }
+        }
+        self.catch_up(reciever);
+        !self.shutdown
+    }
+
+    // Takes in whatever else has been sent. Returns true when that makes the
+    // job being worked on not worth finishing, which is a newer drawing.
+    fn catch_up(&mut self, reciever: &Receiver<SolverWorkerRequest>) -> bool {
+        let mut newer_drawing = false;
+        for request in reciever.try_iter() {
+            newer_drawing |= matches!(request, SolverWorkerRequest::Solve { .. });
+            self.hear(request);
+        }
+        newer_drawing || self.shutdown
+    }
+
+    fn hear(&mut self, request: SolverWorkerRequest) {
+        match request {
+            SolverWorkerRequest::Solve {
+                job,
+                play_state,
+                too_many_colors,
+            } => {
+                // Repairs for the old drawing won't fit the new one
+                self.repairs = None;
+                self.solve = Some((job, play_state, too_many_colors));
+            }
+            SolverWorkerRequest::FindRepairs {
+                job,
+                level_settings,
+                grids,
+            } => self.repairs = Some((job, level_settings, grids)),
+            SolverWorkerRequest::Shutdown => self.shutdown = true,
+        }
+    }
+}
+
+fn solve_in_background(
+    solver: &mut SolverDisplay,
+    solver_sender: &Sender<SolverWorkerRequest>,
+    level_settings: &LevelSettings,
+    grids: &EditorGrids,
+) {
+    if let Err(error) = solver_sender.send(solver.request(level_settings, grids)) {
+        eprintln!("could not send to the solver worker: {}", error);
+    }
+}
+
+fn kill_self(io_sender: Sender<IOWorkerRequest>, solver_sender: Sender<SolverWorkerRequest>) -> ! {
     match io_sender.send(IOWorkerRequest::Shutdown) {
         Ok(_) => {}
         Err(error) => {
             eprintln!("while shutting down io worker, experienced: {}", error);
         }
     }
+    // It may be mid solve and not hear this, which is fine since we're exiting
+    let _ = solver_sender.send(SolverWorkerRequest::Shutdown);
     std::process::exit(0)
 }
 
+// Clue files get solved into a level, anything else is a level already
+fn open_level(path: PathBuf) -> Result<Level, Box<dyn std::error::Error>> {
+    match ClueFormat::from_path(&path) {
+        Some(_) => Ok(import_level(&path)?),
+        None => Ok(load_level(path)?),
+    }
+}
+
+fn export_clues(level: &Level, format: ClueFormat) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = level.path.with_extension(format.extension());
+    std::fs::write(&path, Clues::from(level).write(format)?)?;
+    Ok(path)
+}
+
+// Either way there's something to tell the user
+fn save_level(level_settings: &LevelSettings, grids: &EditorGrids) -> Result<PopUp, PopUp> {
+    let level = save_grid_as_level(level_settings, grids);
+    match level.save() {
+        Ok(_) => Ok(PopUp {
+            heading: "Saved".to_owned(),
+            msg: "Your level has been saved".to_owned(),
+            visible: true,
+        }),
+        Err(error) => Err(PopUp {
+            heading: "Error".to_owned(),
+            msg: format!("Error: {error}").to_owned(),
+            visible: true,
+        }),
+    }
+}
+
+fn save_recovery(level_settings: &LevelSettings, grids: &EditorGrids) -> std::io::Result<PathBuf> {
+    let level = recovery_level(level_settings, grids);
+    if let Some(dir) = level.path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    level.save()?;
+    Ok(level.path)
+}
+
 fn load_level_in_editor(level: Level, level_settings: &mut LevelSettings, grids: &mut EditorGrids) {
     grids.load_level(&level);
     level_settings.load_level(&level);
@@ -0,0 +1,92 @@
+use logicpaint::editor::difficulty::DifficultyBand;
+use logicpaint::generator::{PuzzleRequest, generate};
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "usage: logicpaint-generate [options]
+
+  --width N          columns, 10 by default
+  --height N         rows, 10 by default
+  --density F        how much of the grid starts filled, 0 to 1
+  --difficulty BAND  easy, medium, hard or expert
+  --seed N           the same seed always makes the same puzzle
+  --out PATH         save as a level instead of printing it
+
+Prints the seed used so a puzzle made without one can be made again.";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut request = PuzzleRequest {
+        seed: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64),
+        ..PuzzleRequest::default()
+    };
+    let mut out = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        let invalid = || format!("invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--width" => request.width = value.parse().map_err(|_| invalid())?,
+            "--height" => request.height = value.parse().map_err(|_| invalid())?,
+            "--density" => request.density = value.parse().map_err(|_| invalid())?,
+            "--seed" => request.seed = value.parse().map_err(|_| invalid())?,
+            "--difficulty" => {
+                let band = DifficultyBand::ALL
+                    .into_iter()
+                    .find(|band| band.to_string().eq_ignore_ascii_case(&value))
+                    .ok_or_else(|| format!("unknown difficulty {}", value))?;
+                request.difficulty = Some(band);
+            }
+            "--out" => out = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    let puzzle = generate(&request);
+    let rating = puzzle.difficulty;
+    println!(
+        "seed: {}, difficulty: {} ({})",
+        request.seed,
+        rating,
+        DifficultyBand::of(rating)
+    );
+    match out {
+        Some(path) => {
+            let level = puzzle.to_level(path.with_extension("level"));
+            level
+                .save()
+                .map_err(|error| format!("could not save {:?}: {}", level.path, error))?;
+            println!("saved {}", level.path.display());
+        }
+        None => {
+            for row in puzzle.info.rows() {
+                let row: String = row
+                    .into_iter()
+                    .map(|filled| if filled { '#' } else { '.' })
+                    .collect();
+                println!("{}", row);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use logicpaint::clues::load_clues;
+use logicpaint::editor::difficulty::{DifficultyBand, trace_solve};
+use logicpaint::editor::solver::{SolvedState, TheMultiVerseOfLines};
+use logicpaint::levels::load_level;
+use logicpaint::netpbm::Pbm;
+use logicpaint::playstate::PlayState;
+use logicpaint::validate::{ReportFormat, json_string, state_fails, validate_dirs};
+
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+const USAGE: &str = "usage: logicpaint-solve [--json] <file or directory>...
+       logicpaint-solve validate [--format table|json|junit] <directory>...
+
+Solves .level and .pbm files, or clue files in the .non, Olsak (.g), json or
+plain (a rows section then a columns section) formats. Directories are searched for .level files. Exits with 1 if
+any puzzle is unsolvable or has more than one solution, and 2 if a file
+couldn't be read.
+
+validate checks every level in the directories in parallel: that each has a
+single solution, that its image is the same size as its puzzle and that no two
+levels share a mask. Exits with 1 if any level fails. Both ways, a single
+solution that line logic can't reach without a guess is only a warning.";
+
+// Everything worked out about a single puzzle
+struct Report {
+    path: PathBuf,
+    state: SolvedState,
+    iterations: usize,
+    difficulty: Option<u32>,
+    millis: f64,
+    // One string per row, '?' for cells line logic couldn't settle. Empty
+    // when there's no solution to show.
+    grid: Vec<String>,
+    // Why line logic thinks there's no solution, when it can tell
+    contradiction: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg == "validate") {
+        return validate(args.skip(1));
+    }
+
+    let mut json = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        match level_files_in(&path) {
+            Ok(found) => files.extend(found),
+            Err(error) => {
+                eprintln!("could not read {:?}: {}", path, error);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let mut reports = Vec::new();
+    for file in files {
+        match load_play_state(&file) {
+            Ok(play_state) => reports.push(solve(file, &play_state)),
+            Err(error) => {
+                eprintln!("could not load {:?}: {}", file, error);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    if json {
+        let objects: Vec<String> = reports.iter().map(to_json).collect();
+        println!("[{}]", objects.join(",\n"));
+    } else {
+        for report in &reports {
+            print_report(report);
+        }
+    }
+
+    let bad = reports.iter().any(|report| state_fails(report.state));
+    match bad {
+        true => ExitCode::from(1),
+        false => ExitCode::SUCCESS,
+    }
+}
+
+fn validate(mut args: impl Iterator<Item = String>) -> ExitCode {
+    let mut format = ReportFormat::Table;
+    let mut dirs = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(|value| value.parse()) {
+                Some(Ok(parsed)) => format = parsed,
+                Some(Err(error)) => {
+                    eprintln!("{}\n\n{}", error, USAGE);
+                    return ExitCode::from(2);
+                }
+                None => {
+                    eprintln!("--format needs a value\n\n{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+    if dirs.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let report = validate_dirs(&dirs);
+    print!("{}", report.render(format));
+    match report.passed() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(1),
+    }
+}
+
+fn level_files_in(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let file = entry?.path();
+        if file.extension().is_some_and(|ext| ext == "level") {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn load_play_state(path: &Path) -> Result<PlayState, Box<dyn std::error::Error>> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("level") => Ok((&load_level(path.to_path_buf())?).into()),
+        Some("pbm") => {
+            let pbm: Pbm = read_to_string(path)?.parse()?;
+            Ok((&pbm).into())
+        }
+        _ => Ok((&load_clues(path)?).into()),
+    }
+}
+
+fn solve(path: PathBuf, play_state: &PlayState) -> Report {
+    let start = Instant::now();
+    let mut multiverse = TheMultiVerseOfLines::new(play_state);
+    let iterations = multiverse.collapse();
+    let state = multiverse.state();
+    let difficulty = match state {
+        SolvedState::UniqueSolution | SolvedState::UniqueRequiresGuessing => {
+            Some(trace_solve(play_state).rating())
+        }
+        _ => None,
+    };
+    let millis = start.elapsed().as_secs_f64() * 1000.;
+
+    let width = play_state.num_columns;
+    let monochrome = play_state.palette.is_empty();
+    let grid: Vec<String> = match state {
+        SolvedState::Unsolvable => Vec::new(),
+        // Line logic stops short of these, so show the solution search found
+        SolvedState::UniqueRequiresGuessing => multiverse.search(1).witnesses[0]
+            .chunks(width)
+            .map(|row| {
+                row.iter()
+                    .map(|&color| cell_char(1 << color, monochrome))
+                    .collect()
+            })
+            .collect(),
+        _ => (0..play_state.num_rows)
+            .map(|r| {
+                (0..width)
+                    .map(|c| cell_char(multiverse.cell_colors(r, c), monochrome))
+                    .collect()
+            })
+            .collect(),
+    };
+    let contradiction = match state {
+        SolvedState::Unsolvable => {
+            let unknown = vec![u32::MAX; play_state.num_rows * width];
+            TheMultiVerseOfLines::with_known(play_state, &unknown)
+                .err()
+                .map(|contradiction| contradiction.to_string())
+        }
+        _ => None,
+    };
+
+    Report {
+        path,
+        state,
+        iterations,
+        difficulty,
+        millis,
+        grid,
+        contradiction,
+    }
+}
+
+// Cells are bit sets of the colors they could be, bit 0 being empty. Color
+// puzzles show the palette index of each cell instead of #.
+fn cell_char(colors: u32, monochrome: bool) -> char {
+    match colors {
+        0b1 => '.',
+        0b10 if monochrome => '#',
+        colors if colors.count_ones() == 1 => {
+            char::from_digit(colors.trailing_zeros(), 10).unwrap_or('?')
+        }
+        _ => '?',
+    }
+}
+
+fn print_report(report: &Report) {
+    println!("{}: {:?}", report.path.display(), report.state);
+    for row in &report.grid {
+        println!("  {}", row);
+    }
+    if let Some(contradiction) = &report.contradiction {
+        println!("  {}", contradiction);
+    }
+    let difficulty = match report.difficulty {
+        Some(rating) => format!("{} ({})", rating, DifficultyBand::of(rating)),
+        None => "unrated".to_owned(),
+    };
+    println!(
+        "  iterations: {}, difficulty: {}, time: {:.3}ms",
+        report.iterations, difficulty, report.millis
+    );
+}
+
+fn to_json(report: &Report) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_owned());
+    let grid: Vec<String> = report.grid.iter().map(|row| json_string(row)).collect();
+    format!(
+        "{{\"path\":{},\"state\":\"{:?}\",\"iterations\":{},\"difficulty\":{},\"band\":{},\"millis\":{:.3},\"grid\":[{}],\"contradiction\":{}}}",
+        json_string(&report.path.display().to_string()),
+        report.state,
+        report.iterations,
+        optional(report.difficulty.map(|rating| rating.to_string())),
+        optional(
+            report
+                .difficulty
+                .map(|rating| json_string(&DifficultyBand::of(rating).to_string()))
+        ),
+        report.millis,
+        grid.join(","),
+        optional(report.contradiction.as_deref().map(json_string)),
+    )
+}
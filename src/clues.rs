@@ -0,0 +1,1147 @@
+use crate::editor::difficulty::trace_solve;
+use crate::editor::solver::{MAX_COLORS, SolvedState, TheMultiVerseOfLines};
+use crate::levels::{Level, LevelStats, PuzzleKind, palette_rgba};
+use crate::netpbm::{Pbm, Pgm, Ppm};
+use crate::playstate::{PlayState, color_runs};
+use std::path::{Path, PathBuf};
+
+// Each line's clues in order, as (count, palette index)
+pub type ClueLines = Vec<Vec<(usize, usize)>>;
+
+// Just the numbers of a puzzle, with no picture behind them. Each clue is a
+// (count, palette index) pair the same as the solver uses, where monochrome
+// puzzles only ever use 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clues {
+    pub rows: ClueLines,
+    pub columns: ClueLines,
+    // Colors by palette index - 1 out of 255, empty for monochrome puzzles
+    pub palette: Vec<[u16; 3]>,
+}
+
+impl Clues {
+    pub fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_monochrome(&self) -> bool {
+        self.palette.is_empty()
+    }
+
+    pub fn parse(format: ClueFormat, contents: &str) -> CluesResult<Clues> {
+        let clues = match format {
+            ClueFormat::Plain => parse_plain(contents)?,
+            ClueFormat::Non => parse_non(contents)?,
+            ClueFormat::Olsak => parse_olsak(contents)?,
+            ClueFormat::Json => parse_json(contents)?,
+        };
+        clues.validate()?;
+        Ok(clues)
+    }
+
+    pub fn write(&self, format: ClueFormat) -> Result<String, ExportCluesErr> {
+        if !self.is_monochrome() && format != ClueFormat::Olsak {
+            return Err(ExportCluesErr::ColorsUnsupported { format });
+        }
+        Ok(match format {
+            ClueFormat::Plain => self.to_plain(),
+            ClueFormat::Non => self.to_non(),
+            ClueFormat::Olsak => self.to_olsak(),
+            ClueFormat::Json => self.to_json(),
+        })
+    }
+
+    fn validate(&self) -> CluesResult<()> {
+        // Nothing to draw, and the editor can't do anything with it either
+        if self.width() == 0 || self.height() == 0 {
+            return Err(LoadCluesErr::Empty {
+                width: self.width(),
+                height: self.height(),
+            });
+        }
+        if self.palette.len() > MAX_COLORS {
+            return Err(LoadCluesErr::TooManyColors {
+                found: self.palette.len(),
+            });
+        }
+        // No clue can be longer than a line, and checking keeps the totals
+        // below from overflowing on silly numbers
+        let lines = [(&self.rows, self.width()), (&self.columns, self.height())];
+        for (clues, length) in lines {
+            if let Some(&(count, _)) = clues.iter().flatten().find(|&&(count, _)| count > length) {
+                return Err(LoadCluesErr::CountTooLarge {
+                    found: count,
+                    length,
+                });
+            }
+        }
+        // Every color has to be used as much going across as going down
+        for color in 1..=self.palette.len().max(1) {
+            let total = |lines: &[Vec<(usize, usize)>]| -> usize {
+                lines
+                    .iter()
+                    .flatten()
+                    .filter(|&&(_, c)| c == color)
+                    .map(|&(count, _)| count)
+                    .sum()
+            };
+            let (rows, columns) = (total(&self.rows), total(&self.columns));
+            if rows != columns {
+                return Err(LoadCluesErr::MismatchedTotals { rows, columns });
+            }
+        }
+        Ok(())
+    }
+
+    // Solves the clues to get the picture back, which only works if there's
+    // exactly one. The reward image is made up since clues don't come with one.
+    pub fn to_level(&self, path: PathBuf) -> Result<Level, ImportErr> {
+        let play_state: PlayState = self.into();
+        let mut multiverse = TheMultiVerseOfLines::new(&play_state);
+        multiverse.collapse();
+        match multiverse.state() {
+            SolvedState::UniqueSolution | SolvedState::UniqueRequiresGuessing => {}
+            SolvedState::Unsolvable => return Err(ImportErr::Unsolvable),
+            SolvedState::MultipleSolutions => return Err(ImportErr::Ambiguous),
+        }
+        let solution = multiverse.search(1).witnesses.remove(0);
+        let (width, height) = (self.width(), self.height());
+        let info = Pbm {
+            width,
+            height,
+            cells: solution.iter().map(|&color| color != 0).collect(),
+        };
+        let (kind, image) = match self.is_monochrome() {
+            true => (PuzzleKind::Monochrome, placeholder_image(&info)),
+            false => {
+                let mask = Pgm {
+                    width,
+                    height,
+                    max_value: self.palette.len() as u16,
+                    cells: solution.iter().map(|&color| color as u16).collect(),
+                };
+                let image = Ppm {
+                    width,
+                    height,
+                    max_value: 255,
+                    cells: solution
+                        .iter()
+                        .map(|&color| match color {
+                            0 => [255, 255, 255],
+                            color => self.palette[color - 1],
+                        })
+                        .collect(),
+                };
+                let palette = self.palette.clone();
+                (PuzzleKind::Color { mask, palette }, image)
+            }
+        };
+        Ok(Level {
+            difficulty: Some(trace_solve(&play_state).rating()),
+            info,
+            image,
+            kind,
+            completed: false,
+            stats: LevelStats::default(),
+            path: path.with_extension("level"),
+        })
+    }
+
+    fn to_plain(&self) -> String {
+        let mut out = String::new();
+        for (name, lines) in [("rows", &self.rows), ("columns", &self.columns)] {
+            out += name;
+            out += "\n";
+            for line in lines {
+                out += &counts(line, " ");
+                out += "\n";
+            }
+        }
+        out
+    }
+
+    fn to_non(&self) -> String {
+        let mut out = format!("width {}\nheight {}\n", self.width(), self.height());
+        for (name, lines) in [("rows", &self.rows), ("columns", &self.columns)] {
+            out += &format!("\n{}\n", name);
+            for line in lines {
+                out += &counts(line, ",");
+                out += "\n";
+            }
+        }
+        out
+    }
+
+    fn to_olsak(&self) -> String {
+        let mut out = String::new();
+        if !self.is_monochrome() {
+            out += "#d\n   0:   #FFFFFF   white\n";
+            for (idx, [r, g, b]) in self.palette.iter().enumerate() {
+                let letter = olsak_letter(idx + 1);
+                out += &format!(
+                    "   {}:{}   #{:02X}{:02X}{:02X}   color{}\n",
+                    letter,
+                    letter,
+                    r,
+                    g,
+                    b,
+                    idx + 1
+                );
+            }
+        }
+        for (name, lines) in [("rows", &self.rows), ("columns", &self.columns)] {
+            out += &format!(": {}\n", name);
+            for line in lines {
+                let clues: Vec<String> = line
+                    .iter()
+                    .map(|&(count, color)| match self.is_monochrome() {
+                        true => count.to_string(),
+                        false => format!("{}{}", count, olsak_letter(color)),
+                    })
+                    .collect();
+                match clues.is_empty() {
+                    true => out += "0\n",
+                    false => out += &format!("{}\n", clues.join(" ")),
+                }
+            }
+        }
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let lines = |lines: &[Vec<(usize, usize)>]| -> String {
+            let lines: Vec<String> = lines
+                .iter()
+                .map(|line| {
+                    let counts: Vec<String> =
+                        line.iter().map(|(count, _)| count.to_string()).collect();
+                    format!("[{}]", counts.join(","))
+                })
+                .collect();
+            lines.join(",")
+        };
+        format!(
+            "{{\"rows\":[{}],\"cols\":[{}]}}\n",
+            lines(&self.rows),
+            lines(&self.columns)
+        )
+    }
+}
+
+// Empty lines are written as a single 0, which every format reads back
+fn counts(line: &[(usize, usize)], separator: &str) -> String {
+    let counts: Vec<String> = line.iter().map(|(count, _)| count.to_string()).collect();
+    match counts.is_empty() {
+        true => "0".to_owned(),
+        false => counts.join(separator),
+    }
+}
+
+// Olsak files name colors by letter, a being the first after the background
+fn olsak_letter(color: usize) -> char {
+    (b'a' + (color - 1) as u8) as char
+}
+
+// Something to show when the puzzle's done, shading the picture from top to
+// bottom so it's not just a copy of the clues' solution
+fn placeholder_image(info: &Pbm) -> Ppm {
+    Ppm::shaded(info, [40, 60, 160], [20, 140, 120], [245, 240, 225])
+}
+
+impl From<&Pbm> for Clues {
+    fn from(pbm: &Pbm) -> Clues {
+        let runs = |line: Vec<bool>| -> Vec<(usize, usize)> {
+            let line: Vec<usize> = line.into_iter().map(usize::from).collect();
+            color_runs(&line)
+        };
+        Clues {
+            rows: pbm.rows().into_iter().map(runs).collect(),
+            columns: pbm.cols().into_iter().map(runs).collect(),
+            palette: Vec::new(),
+        }
+    }
+}
+
+impl From<&Level> for Clues {
+    fn from(level: &Level) -> Clues {
+        match &level.kind {
+            PuzzleKind::Monochrome => (&level.info).into(),
+            PuzzleKind::Color { mask, palette } => {
+                let runs = |line: Vec<u16>| -> Vec<(usize, usize)> {
+                    let line: Vec<usize> = line.into_iter().map(usize::from).collect();
+                    color_runs(&line)
+                };
+                Clues {
+                    rows: mask.rows().into_iter().map(runs).collect(),
+                    columns: mask.cols().into_iter().map(runs).collect(),
+                    palette: palette.clone(),
+                }
+            }
+        }
+    }
+}
+
+impl From<&Clues> for PlayState {
+    fn from(clues: &Clues) -> PlayState {
+        let palette = clues.palette.iter().map(|&c| palette_rgba(c)).collect();
+        PlayState::unpainted(&clues.rows, &clues.columns, palette)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClueFormat {
+    // A rows section then a columns section, one line of clue numbers each
+    // where 0 stands for an empty line:
+    //
+    //   rows
+    //   1
+    //   3
+    //   1
+    //   columns
+    //   ...
+    Plain,
+    // Steve Simpson's .non files, with width and height up front and clues
+    // separated by commas
+    Non,
+    // Olsak's solver input, with ": rows" and ": columns" sections. Color
+    // puzzles list their palette in a #d block and tag each clue with a letter.
+    Olsak,
+    // {"rows":[[1],[3],[1]],"cols":[[1],[3],[1]]}
+    Json,
+}
+
+impl ClueFormat {
+    pub const ALL: [ClueFormat; 4] = [
+        ClueFormat::Plain,
+        ClueFormat::Non,
+        ClueFormat::Olsak,
+        ClueFormat::Json,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ClueFormat::Plain => "clues",
+            ClueFormat::Non => "non",
+            ClueFormat::Olsak => "g",
+            ClueFormat::Json => "json",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<ClueFormat> {
+        let extension = path.extension()?.to_str()?;
+        ClueFormat::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
+
+impl std::fmt::Display for ClueFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, ".{}", self.extension())
+    }
+}
+
+// Reads a clue file of whichever format its extension says, falling back to
+// the plain one
+pub fn load_clues(path: &Path) -> Result<Clues, ImportErr> {
+    let contents = std::fs::read_to_string(path).map_err(ImportErr::Io)?;
+    let format = ClueFormat::from_path(path).unwrap_or(ClueFormat::Plain);
+    Clues::parse(format, &contents).map_err(ImportErr::Parse)
+}
+
+pub fn import_level(path: &Path) -> Result<Level, ImportErr> {
+    load_clues(path)?.to_level(path.to_path_buf())
+}
+
+pub type CluesResult<T> = Result<T, LoadCluesErr>;
+
+#[derive(Debug, PartialEq)]
+pub enum LoadCluesErr {
+    MissingSection {
+        name: &'static str,
+    },
+    UnexpectedLine {
+        line: usize,
+        found: String,
+    },
+    InvalidNumber {
+        line: usize,
+        found: String,
+    },
+    UnknownColor {
+        line: usize,
+        found: char,
+    },
+    WrongLineCount {
+        name: &'static str,
+        expected: usize,
+        got: usize,
+    },
+    InvalidJson {
+        reason: String,
+    },
+    Empty {
+        width: usize,
+        height: usize,
+    },
+    TooManyColors {
+        found: usize,
+    },
+    CountTooLarge {
+        found: usize,
+        length: usize,
+    },
+    // The cells the rows ask for don't add up to the columns'
+    MismatchedTotals {
+        rows: usize,
+        columns: usize,
+    },
+}
+
+impl std::error::Error for LoadCluesErr {}
+
+impl std::fmt::Display for LoadCluesErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        use LoadCluesErr::*;
+        match self {
+            MissingSection { name } => write!(f, "missing the {} section", name),
+            UnexpectedLine { line, found } => {
+                write!(f, "line {}: unexpected {}", line, found)
+            }
+            InvalidNumber { line, found } => {
+                write!(f, "line {}: invalid clue number {}", line, found)
+            }
+            UnknownColor { line, found } => {
+                write!(f, "line {}: color {} isn't in the palette", line, found)
+            }
+            WrongLineCount {
+                name,
+                expected,
+                got,
+            } => write!(f, "expected {} {} but found {}", expected, name, got),
+            InvalidJson { reason } => write!(f, "invalid json: {}", reason),
+            Empty { width, height } => write!(
+                f,
+                "clues are for {}x{} but puzzles need at least one row and column",
+                width, height
+            ),
+            TooManyColors { found } => write!(
+                f,
+                "palette has {} colors but puzzles can have at most {}",
+                found, MAX_COLORS
+            ),
+            CountTooLarge { found, length } => write!(
+                f,
+                "clue {} is longer than its line of {} cells",
+                found, length
+            ),
+            MismatchedTotals { rows, columns } => {
+                write!(f, "rows fill {} cells but columns fill {}", rows, columns)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExportCluesErr {
+    ColorsUnsupported { format: ClueFormat },
+}
+
+impl std::error::Error for ExportCluesErr {}
+
+impl std::fmt::Display for ExportCluesErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ExportCluesErr::ColorsUnsupported { format } => write!(
+                f,
+                "{} files can't hold color clues, use {} instead",
+                format,
+                ClueFormat::Olsak
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportErr {
+    Io(std::io::Error),
+    Parse(LoadCluesErr),
+    Unsolvable,
+    Ambiguous,
+}
+
+impl std::fmt::Display for ImportErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ImportErr::Io(error) => write!(f, "could not read clues: {}", error),
+            ImportErr::Parse(error) => write!(f, "could not parse clues: {}", error),
+            ImportErr::Unsolvable => write!(f, "no picture fits these clues"),
+            ImportErr::Ambiguous => write!(f, "more than one picture fits these clues"),
+        }
+    }
+}
+
+impl std::error::Error for ImportErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportErr::Io(error) => Some(error),
+            ImportErr::Parse(error) => Some(error),
+            ImportErr::Unsolvable | ImportErr::Ambiguous => None,
+        }
+    }
+}
+
+// Clue numbers split by spaces or commas, with 0 on its own for an empty line
+fn parse_counts(line: &str, number: usize) -> CluesResult<Vec<(usize, usize)>> {
+    line.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|count| !count.is_empty())
+        .map(|count| {
+            count.parse::<usize>().map(|count| (count, 1)).map_err(|_| {
+                LoadCluesErr::InvalidNumber {
+                    line: number,
+                    found: count.to_owned(),
+                }
+            })
+        })
+        .filter(|clue| !matches!(clue, Ok((0, _))))
+        .collect()
+}
+
+fn parse_plain(contents: &str) -> CluesResult<Clues> {
+    let mut rows = None;
+    let mut columns = None;
+    let mut section: Option<&mut ClueLines> = None;
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line {
+            "rows" => section = Some(rows.insert(Vec::new())),
+            "columns" => section = Some(columns.insert(Vec::new())),
+            _ => {
+                let Some(lines) = section.as_mut() else {
+                    return Err(LoadCluesErr::UnexpectedLine {
+                        line: idx + 1,
+                        found: line.to_owned(),
+                    });
+                };
+                lines.push(parse_counts(line, idx + 1)?);
+            }
+        }
+    }
+
+    Ok(Clues {
+        rows: rows.ok_or(LoadCluesErr::MissingSection { name: "rows" })?,
+        columns: columns.ok_or(LoadCluesErr::MissingSection { name: "columns" })?,
+        palette: Vec::new(),
+    })
+}
+
+// Anything we don't use, like the title or a goal, is skipped over. Since the
+// size is given up front, blank lines in a section count as empty lines.
+fn parse_non(contents: &str) -> CluesResult<Clues> {
+    let mut width = None;
+    let mut height = None;
+    let mut rows = Vec::new();
+    let mut columns = Vec::new();
+    // Which section we're in and how many lines it still has
+    let mut section: Option<(&mut ClueLines, usize)> = None;
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let is_keyword = line.starts_with(|c: char| c.is_ascii_alphabetic());
+        if let Some((lines, remaining)) = section.as_mut()
+            && *remaining > 0
+            && !is_keyword
+        {
+            lines.push(parse_counts(line, idx + 1)?);
+            *remaining -= 1;
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let size = |word: Option<&str>| -> CluesResult<usize> {
+            let word = word.unwrap_or_default();
+            word.parse().map_err(|_| LoadCluesErr::InvalidNumber {
+                line: idx + 1,
+                found: word.to_owned(),
+            })
+        };
+        match words.next() {
+            Some("width") => width = Some(size(words.next())?),
+            Some("height") => height = Some(size(words.next())?),
+            Some("rows") => {
+                let height = height.ok_or(LoadCluesErr::MissingSection { name: "height" })?;
+                section = Some((&mut rows, height));
+            }
+            Some("columns") => {
+                let width = width.ok_or(LoadCluesErr::MissingSection { name: "width" })?;
+                section = Some((&mut columns, width));
+            }
+            _ => section = None,
+        }
+    }
+
+    let check = |name: &'static str, lines: &[Vec<(usize, usize)>], expected: Option<usize>| {
+        let expected = expected.ok_or(LoadCluesErr::MissingSection { name })?;
+        match lines.len() == expected {
+            true => Ok(()),
+            false => Err(LoadCluesErr::WrongLineCount {
+                name,
+                expected,
+                got: lines.len(),
+            }),
+        }
+    };
+    check("rows", &rows, height)?;
+    check("columns", &columns, width)?;
+    Ok(Clues {
+        rows,
+        columns,
+        palette: Vec::new(),
+    })
+}
+
+fn parse_olsak(contents: &str) -> CluesResult<Clues> {
+    // Palette letters in the order they're defined, with the background left out
+    let mut letters: Vec<char> = Vec::new();
+    let mut palette: Vec<[u16; 3]> = Vec::new();
+    let mut in_palette = false;
+    let mut rows = None;
+    let mut columns = None;
+    let mut section: Option<&mut ClueLines> = None;
+
+    for (idx, line) in contents.lines().enumerate() {
+        let number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(heading) = line.strip_prefix(':') {
+            in_palette = false;
+            section = match heading.trim() {
+                heading if heading.starts_with("rows") => Some(rows.insert(Vec::new())),
+                heading if heading.starts_with("columns") => Some(columns.insert(Vec::new())),
+                _ => None,
+            };
+            continue;
+        }
+        if line.starts_with('#') {
+            in_palette = line == "#d";
+            continue;
+        }
+        if in_palette {
+            let unexpected = || LoadCluesErr::UnexpectedLine {
+                line: number,
+                found: line.to_owned(),
+            };
+            let letter = line.chars().next().ok_or_else(unexpected)?;
+            if letter == '0' {
+                continue;
+            }
+            // Checked to be plain ascii before slicing it up by bytes
+            let hex = line
+                .split_whitespace()
+                .find_map(|word| word.strip_prefix('#'))
+                .filter(|hex| hex.len() == 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .ok_or_else(unexpected)?;
+            let channel = |at: usize| u16::from_str_radix(&hex[at..at + 2], 16);
+            let color = [channel(0), channel(2), channel(4)];
+            let [Ok(r), Ok(g), Ok(b)] = color else {
+                return Err(unexpected());
+            };
+            letters.push(letter);
+            palette.push([r, g, b]);
+            continue;
+        }
+        let Some(lines) = section.as_mut() else {
+            return Err(LoadCluesErr::UnexpectedLine {
+                line: number,
+                found: line.to_owned(),
+            });
+        };
+        let mut clues = Vec::new();
+        for word in line.split_whitespace() {
+            let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+            let count: usize = digits.parse().map_err(|_| LoadCluesErr::InvalidNumber {
+                line: number,
+                found: word.to_owned(),
+            })?;
+            let color = match word[digits.len()..].chars().next() {
+                None => 1,
+                Some(letter) => {
+                    let idx = letters.iter().position(|&l| l == letter).ok_or(
+                        LoadCluesErr::UnknownColor {
+                            line: number,
+                            found: letter,
+                        },
+                    )?;
+                    idx + 1
+                }
+            };
+            if count > 0 {
+                clues.push((count, color));
+            }
+        }
+        lines.push(clues);
+    }
+
+    // A palette of just black is how monochrome puzzles sometimes say so
+    if palette == [[0, 0, 0]] {
+        palette.clear();
+    }
+    Ok(Clues {
+        rows: rows.ok_or(LoadCluesErr::MissingSection { name: "rows" })?,
+        columns: columns.ok_or(LoadCluesErr::MissingSection { name: "columns" })?,
+        palette,
+    })
+}
+
+fn parse_json(contents: &str) -> CluesResult<Clues> {
+    let invalid = |reason: &str| LoadCluesErr::InvalidJson {
+        reason: reason.to_owned(),
+    };
+    let mut parser = JsonParser {
+        chars: contents.chars().peekable(),
+        depth: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.chars.next().is_some() {
+        return Err(invalid("trailing characters after the clues"));
+    }
+    let Json::Object(fields) = value else {
+        return Err(invalid("expected an object with rows and cols"));
+    };
+    let lines = |names: &[&'static str]| -> CluesResult<ClueLines> {
+        let Some((_, value)) = fields.iter().find(|(key, _)| names.contains(&key.as_str())) else {
+            return Err(LoadCluesErr::MissingSection { name: names[0] });
+        };
+        let Json::Array(lines) = value else {
+            return Err(invalid("clues should be an array of arrays"));
+        };
+        lines
+            .iter()
+            .map(|line| {
+                let Json::Array(counts) = line else {
+                    return Err(invalid("clues should be an array of arrays"));
+                };
+                let counts = counts.iter().map(|count| match count {
+                    Json::Number(n) if n.fract() == 0. && *n >= 0. => Ok(*n as usize),
+                    _ => Err(invalid("clues should be whole numbers")),
+                });
+                let counts: Vec<usize> = counts.collect::<CluesResult<_>>()?;
+                Ok(counts
+                    .into_iter()
+                    .filter(|&n| n > 0)
+                    .map(|n| (n, 1))
+                    .collect())
+            })
+            .collect()
+    };
+    Ok(Clues {
+        rows: lines(&["rows"])?,
+        columns: lines(&["cols", "columns"])?,
+        palette: Vec::new(),
+    })
+}
+
+// Just enough json to read clue files, which is why strings don't bother
+// with escapes beyond the simple ones
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+// Clue files only ever go two arrays deep, this is just so a file full of
+// brackets can't recurse until the stack runs out
+const MAX_JSON_DEPTH: usize = 32;
+
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    // How many arrays and objects the parser is inside of
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> CluesResult<()> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            found => Err(LoadCluesErr::InvalidJson {
+                reason: format!("expected {} but found {:?}", expected, found),
+            }),
+        }
+    }
+
+    fn value(&mut self) -> CluesResult<Json> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some(c @ ('{' | '[')) => {
+                if self.depth == MAX_JSON_DEPTH {
+                    return Err(LoadCluesErr::InvalidJson {
+                        reason: format!("nested more than {} deep", MAX_JSON_DEPTH),
+                    });
+                }
+                self.depth += 1;
+                let value = match c {
+                    '{' => self.object(),
+                    _ => self.array(),
+                };
+                self.depth -= 1;
+                value
+            }
+            Some('"') => self.string().map(Json::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphabetic()) {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "null" => Ok(Json::Null),
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    _ => Err(LoadCluesErr::InvalidJson {
+                        reason: format!("unexpected {}", word),
+                    }),
+                }
+            }
+            found => Err(LoadCluesErr::InvalidJson {
+                reason: format!("unexpected {:?}", found),
+            }),
+        }
+    }
+
+    fn number(&mut self) -> CluesResult<Json> {
+        let mut number = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            number.push(c);
+        }
+        number
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| LoadCluesErr::InvalidJson {
+                reason: format!("invalid number {}", number),
+            })
+    }
+
+    fn string(&mut self) -> CluesResult<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some(c) => string.push(c),
+                    None => break,
+                },
+                Some(c) => string.push(c),
+                None => break,
+            }
+        }
+        Err(LoadCluesErr::InvalidJson {
+            reason: "unterminated string".to_owned(),
+        })
+    }
+
+    fn array(&mut self) -> CluesResult<Json> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(values)),
+                found => {
+                    return Err(LoadCluesErr::InvalidJson {
+                        reason: format!("expected , or ] but found {:?}", found),
+                    });
+                }
+            }
+        }
+    }
+
+    fn object(&mut self) -> CluesResult<Json> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(fields)),
+                found => {
+                    return Err(LoadCluesErr::InvalidJson {
+                        reason: format!("expected , or }} but found {:?}", found),
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod clue_tests {
+    use super::*;
+
+    const PLUS: &str = "# a plus sign
+rows
+1
+3
+1
+columns
+1
+3
+1
+";
+
+    fn plus() -> Clues {
+        Clues::parse(ClueFormat::Plain, PLUS).unwrap()
+    }
+
+    #[test]
+    fn clues_parse_and_print_the_same() {
+        let clues = plus();
+        assert_eq!(vec![vec![(1, 1)], vec![(3, 1)], vec![(1, 1)]], clues.rows);
+        assert_eq!(3, clues.width());
+        assert_eq!(
+            PLUS.trim_start_matches("# a plus sign\n"),
+            clues.write(ClueFormat::Plain).unwrap()
+        );
+    }
+
+    #[test]
+    fn every_format_reads_back_what_it_wrote() {
+        let pbm = Pbm {
+            width: 3,
+            height: 2,
+            cells: vec![true, false, true, false, false, false],
+        };
+        let clues: Clues = (&pbm).into();
+        assert_eq!(vec![vec![(1, 1), (1, 1)], vec![]], clues.rows);
+        for format in ClueFormat::ALL {
+            let written = clues.write(format).unwrap();
+            assert_eq!(
+                Ok(clues.clone()),
+                Clues::parse(format, &written),
+                "{}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn non_files_skip_what_they_dont_need() {
+        let non = "catalogue \"test\"
+title \"Plus\"
+width 3
+height 3
+
+rows
+1
+3
+1
+
+columns
+1
+3
+1
+goal 010111010
+";
+        assert_eq!(Ok(plus()), Clues::parse(ClueFormat::Non, non));
+        assert_eq!(
+            Err(LoadCluesErr::WrongLineCount {
+                name: "rows",
+                expected: 3,
+                got: 2
+            }),
+            Clues::parse(
+                ClueFormat::Non,
+                "width 3\nheight 3\nrows\n1\n3\ncolumns\n1\n"
+            )
+        );
+    }
+
+    #[test]
+    fn olsak_colors_come_from_the_palette() {
+        let olsak = "#d
+   0:   #FFFFFF   white
+   a:X  #FF0000   red
+   b:%  #0000FF   blue
+: rows
+1a 1b
+0
+: columns
+1a
+1b
+";
+        let clues = Clues::parse(ClueFormat::Olsak, olsak).unwrap();
+        assert_eq!(vec![[255, 0, 0], [0, 0, 255]], clues.palette);
+        assert_eq!(vec![vec![(1, 1), (1, 2)], vec![]], clues.rows);
+        assert_eq!(
+            Ok(clues.clone()),
+            Clues::parse(ClueFormat::Olsak, &clues.to_olsak())
+        );
+        assert_eq!(
+            Err(ExportCluesErr::ColorsUnsupported {
+                format: ClueFormat::Json
+            }),
+            clues.write(ClueFormat::Json)
+        );
+        assert_eq!(
+            Err(LoadCluesErr::UnknownColor {
+                line: 4,
+                found: 'c'
+            }),
+            Clues::parse(ClueFormat::Olsak, "#d\n a:X #FF0000 red\n: rows\n1c\n")
+        );
+    }
+
+    #[test]
+    fn json_clues_allow_other_fields() {
+        let json = r#"{"title": "plus", "rows": [[1], [3], [1]], "cols": [[1], [3], [1]], "solved": null}"#;
+        assert_eq!(Ok(plus()), Clues::parse(ClueFormat::Json, json));
+        assert!(matches!(
+            Clues::parse(ClueFormat::Json, r#"{"rows": [[1]], "cols": [[1]]"#),
+            Err(LoadCluesErr::InvalidJson { .. })
+        ));
+    }
+
+    #[test]
+    fn imported_clues_are_solved_into_a_level() {
+        let level = plus().to_level(PathBuf::from("plus.non")).unwrap();
+        assert_eq!(
+            vec![false, true, false, true, true, true, false, true, false],
+            level.info.cells
+        );
+        assert_eq!(PathBuf::from("plus.level"), level.path);
+        assert_eq!(plus(), Clues::from(&level));
+
+        let ambiguous = Clues::parse(ClueFormat::Plain, "rows\n1\n1\ncolumns\n1\n1\n").unwrap();
+        assert!(matches!(
+            ambiguous.to_level(PathBuf::from("x.clues")),
+            Err(ImportErr::Ambiguous)
+        ));
+    }
+
+    #[test]
+    fn shipped_levels_export_and_import_unchanged() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("levels");
+        let levels = crate::levels::load_levels_from_dir(&dir).unwrap();
+        for level in &levels {
+            let clues = Clues::from(level);
+            let format = match clues.is_monochrome() {
+                true => ClueFormat::Non,
+                false => ClueFormat::Olsak,
+            };
+            let written = clues.write(format).unwrap();
+            let read = Clues::parse(format, &written).unwrap();
+            assert_eq!(clues, read, "{}", level.path.display());
+            let imported = read.to_level(level.path.clone()).unwrap();
+            assert_eq!(level.info.cells, imported.info.cells);
+            assert_eq!(level.kind, imported.kind, "{}", level.path.display());
+        }
+    }
+
+    #[test]
+    fn broken_clue_files_say_where() {
+        let parse = |contents| Clues::parse(ClueFormat::Plain, contents);
+        assert_eq!(
+            Err(LoadCluesErr::UnexpectedLine {
+                line: 1,
+                found: "1 2".to_owned()
+            }),
+            parse("1 2\nrows\n")
+        );
+        assert_eq!(
+            Err(LoadCluesErr::InvalidNumber {
+                line: 2,
+                found: "x".to_owned()
+            }),
+            parse("rows\n1 x\n")
+        );
+        assert_eq!(
+            Err(LoadCluesErr::MissingSection { name: "columns" }),
+            parse("rows\n1\n")
+        );
+        assert_eq!(
+            Err(LoadCluesErr::MismatchedTotals {
+                rows: 2,
+                columns: 1
+            }),
+            parse("rows\n2\ncolumns\n1\n0\n")
+        );
+    }
+
+    #[test]
+    fn hostile_clue_files_are_refused_without_panicking() {
+        let huge = "rows\n18446744073709551615 1\ncolumns\n18446744073709551615 1\n";
+        assert_eq!(
+            Err(LoadCluesErr::CountTooLarge {
+                found: usize::MAX,
+                length: 1
+            }),
+            Clues::parse(ClueFormat::Plain, huge)
+        );
+        let olsak = "#d\n a:X #aébé\n: rows\n1a\n: columns\n1a\n";
+        assert_eq!(
+            Err(LoadCluesErr::UnexpectedLine {
+                line: 2,
+                found: "a:X #aébé".to_owned()
+            }),
+            Clues::parse(ClueFormat::Olsak, olsak)
+        );
+        let empty = "width 0\nheight 0\nrows\ncolumns\n";
+        assert_eq!(
+            Err(LoadCluesErr::Empty {
+                width: 0,
+                height: 0
+            }),
+            Clues::parse(ClueFormat::Non, empty)
+        );
+        assert_eq!(
+            Err(LoadCluesErr::Empty {
+                width: 0,
+                height: 0
+            }),
+            Clues::parse(ClueFormat::Plain, "rows\ncolumns\n")
+        );
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(matches!(
+            Clues::parse(ClueFormat::Json, &nested),
+            Err(LoadCluesErr::InvalidJson { .. })
+        ));
+    }
+}
@@ -0,0 +1,473 @@
+use crate::ui::{Action, PlayerInput};
+use std::collections::VecDeque;
+
+use egor::{
+    input::{Input, KeyCode, MouseButton},
+    math::{Rect, Vec2},
+};
+
+const UP_KEYS: [KeyCode; 2] = [KeyCode::ArrowUp, KeyCode::KeyW];
+const DOWN_KEYS: [KeyCode; 2] = [KeyCode::ArrowDown, KeyCode::KeyS];
+const LEFT_KEYS: [KeyCode; 2] = [KeyCode::ArrowLeft, KeyCode::KeyA];
+const RIGHT_KEYS: [KeyCode; 2] = [KeyCode::ArrowRight, KeyCode::KeyD];
+const FILL_KEYS: [KeyCode; 3] = [KeyCode::Space, KeyCode::Enter, KeyCode::KeyZ];
+const MARK_KEYS: [KeyCode; 2] = [KeyCode::KeyX, KeyCode::Backspace];
+const COLOR_KEYS: [KeyCode; 8] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+];
+
+// How long an arrow key has to be held before the cursor starts sliding,
+// and then how often it moves again after that. In seconds.
+const REPEAT_DELAY: f32 = 0.3;
+const REPEAT_INTERVAL: f32 = 0.08;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// The things a cursor can land on for the current screen, laid out in rows.
+// Rows don't need to be the same length, the cursor will clamp itself into
+// whatever row it moves into.
+#[derive(Debug, Default)]
+pub struct FocusGrid {
+    pub rows: Vec<Vec<Rect>>,
+}
+
+impl FocusGrid {
+    pub fn push_row(&mut self, row: Vec<Rect>) {
+        if !row.is_empty() {
+            self.rows.push(row);
+        }
+    }
+
+    pub fn rect_at(&self, cursor: &GridCursor) -> Option<Rect> {
+        self.rows
+            .get(cursor.row)
+            .and_then(|row| row.get(cursor.column))
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridCursor {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl Default for GridCursor {
+    // Every screen puts its buttons in the first focus row, so start
+    // one row down on whatever the screen is actually about.
+    fn default() -> Self {
+        GridCursor { row: 1, column: 0 }
+    }
+}
+
+impl GridCursor {
+    pub fn step(&mut self, direction: Direction, focus: &FocusGrid) {
+        match direction {
+            Direction::Up => self.row = self.row.saturating_sub(1),
+            Direction::Down => self.row += 1,
+            Direction::Left => self.column = self.column.saturating_sub(1),
+            Direction::Right => self.column += 1,
+        }
+        self.clamp_to(focus);
+    }
+
+    pub fn clamp_to(&mut self, focus: &FocusGrid) {
+        if focus.rows.is_empty() {
+            return;
+        }
+        self.row = self.row.min(focus.rows.len() - 1);
+        self.column = self.column.min(focus.rows[self.row].len() - 1);
+    }
+}
+
+// Everything an input source might want to look at to decide what the player
+// is doing this frame.
+pub struct InputFrame<'a> {
+    pub input: &'a Input,
+    // mouse position in world units
+    pub mouse_position: Vec2,
+    // seconds since the last frame
+    pub delta: f32,
+    pub focus: &'a FocusGrid,
+}
+
+// The palette index picked with the number keys this frame, counting from 1
+pub fn color_key_pressed(input: &Input) -> Option<usize> {
+    COLOR_KEYS
+        .iter()
+        .position(|&key| input.key_pressed(key))
+        .map(|idx| idx + 1)
+}
+
+pub trait InputSource {
+    fn next_input(&mut self, frame: &InputFrame) -> PlayerInput;
+}
+
+pub struct MouseInput;
+
+impl InputSource for MouseInput {
+    fn next_input(&mut self, frame: &InputFrame) -> PlayerInput {
+        let input = frame.input;
+        let left_mouse_pressed =
+            input.mouse_pressed(MouseButton::Left) || input.mouse_held(MouseButton::Left);
+        let right_mouse_pressed =
+            input.mouse_pressed(MouseButton::Right) || input.mouse_held(MouseButton::Right);
+
+        PlayerInput {
+            position: frame.mouse_position,
+            action: {
+                match (left_mouse_pressed, right_mouse_pressed) {
+                    (false, false) => None,
+                    (true, false) => Some(Action::FillCell),
+                    (_, true) => Some(Action::MarkCell),
+                }
+            },
+            pressed: input.mouse_pressed(MouseButton::Left),
+        }
+    }
+}
+
+// Turns direction keys going down and staying down into cursor moves. A
+// freshly pressed key always moves, a held one moves again after the repeat
+// delay and then every repeat interval after that.
+#[derive(Debug, Default)]
+struct KeyRepeat {
+    held_direction: Option<Direction>,
+    held_for: f32,
+    next_repeat_at: f32,
+}
+
+impl KeyRepeat {
+    // Returns the direction to move in this frame, if any
+    fn next_direction(
+        &mut self,
+        pressed: Option<Direction>,
+        held: impl Fn(Direction) -> bool,
+        delta: f32,
+    ) -> Option<Direction> {
+        if let Some(direction) = pressed {
+            self.held_direction = Some(direction);
+            self.held_for = 0.0;
+            self.next_repeat_at = REPEAT_DELAY;
+            return Some(direction);
+        }
+
+        let direction = self.held_direction?;
+        if !held(direction) {
+            self.held_direction = None;
+            return None;
+        }
+
+        self.held_for += delta;
+        if self.held_for >= self.next_repeat_at {
+            self.next_repeat_at += REPEAT_INTERVAL;
+            Some(direction)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct KeyboardInput {
+    pub cursor: GridCursor,
+    repeat: KeyRepeat,
+}
+
+impl KeyboardInput {
+    fn direction_pressed(input: &Input) -> Option<Direction> {
+        if input.keys_pressed(&UP_KEYS) {
+            Some(Direction::Up)
+        } else if input.keys_pressed(&DOWN_KEYS) {
+            Some(Direction::Down)
+        } else if input.keys_pressed(&LEFT_KEYS) {
+            Some(Direction::Left)
+        } else if input.keys_pressed(&RIGHT_KEYS) {
+            Some(Direction::Right)
+        } else {
+            None
+        }
+    }
+
+    fn direction_held(input: &Input, direction: Direction) -> bool {
+        match direction {
+            Direction::Up => input.keys_held(&UP_KEYS),
+            Direction::Down => input.keys_held(&DOWN_KEYS),
+            Direction::Left => input.keys_held(&LEFT_KEYS),
+            Direction::Right => input.keys_held(&RIGHT_KEYS),
+        }
+    }
+
+    pub fn any_key_pressed(input: &Input) -> bool {
+        Self::direction_pressed(input).is_some()
+            || input.keys_pressed(&FILL_KEYS)
+            || input.keys_pressed(&MARK_KEYS)
+    }
+}
+
+impl InputSource for KeyboardInput {
+    fn next_input(&mut self, frame: &InputFrame) -> PlayerInput {
+        let input = frame.input;
+        self.cursor.clamp_to(frame.focus);
+        let pressed = Self::direction_pressed(input);
+        let held = |direction| Self::direction_held(input, direction);
+        if let Some(direction) = self.repeat.next_direction(pressed, held, frame.delta) {
+            self.cursor.step(direction, frame.focus);
+        }
+
+        let fill_held = input.keys_held(&FILL_KEYS);
+        let mark_held = input.keys_held(&MARK_KEYS);
+        cursor_input(
+            &self.cursor,
+            frame.focus,
+            match (fill_held, mark_held) {
+                (false, false) => None,
+                (true, false) => Some(Action::FillCell),
+                (_, true) => Some(Action::MarkCell),
+            },
+            input.keys_pressed(&FILL_KEYS),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScriptedStep {
+    // a direction key going down this step
+    pub direction: Option<Direction>,
+    // a direction key still down from an earlier step, repeats like the
+    // keyboard does
+    pub held: Option<Direction>,
+    pub action: Option<Action>,
+    pub pressed: bool,
+}
+
+// Plays back a list of steps, one per frame, ignoring the real input
+// entirely. Handy for tests and for anything that wants to drive the
+// game without a person sitting in front of it.
+#[derive(Debug, Default)]
+pub struct ScriptedInput {
+    pub cursor: GridCursor,
+    pub steps: VecDeque<ScriptedStep>,
+    repeat: KeyRepeat,
+}
+
+impl ScriptedInput {
+    pub fn is_finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn next_input(&mut self, frame: &InputFrame) -> PlayerInput {
+        self.cursor.clamp_to(frame.focus);
+        let step = self.steps.pop_front().unwrap_or_default();
+        let held = |direction| step.held == Some(direction);
+        if let Some(direction) = self
+            .repeat
+            .next_direction(step.direction, held, frame.delta)
+        {
+            self.cursor.step(direction, frame.focus);
+        }
+        cursor_input(&self.cursor, frame.focus, step.action, step.pressed)
+    }
+}
+
+fn cursor_input(
+    cursor: &GridCursor,
+    focus: &FocusGrid,
+    action: Option<Action>,
+    pressed: bool,
+) -> PlayerInput {
+    // With nothing to focus on, park the cursor somewhere it can't touch anything.
+    let position = focus
+        .rect_at(cursor)
+        .map(|rect| rect.center())
+        .unwrap_or(Vec2::splat(f32::NEG_INFINITY));
+    PlayerInput {
+        position,
+        action,
+        pressed,
+    }
+}
+
+// Swaps between mouse and keyboard depending on which one was touched last,
+// so the player never has to go find a setting to pick their controls.
+#[derive(Debug, Default)]
+pub struct Controls {
+    pub keyboard: KeyboardInput,
+    pub using_keyboard: bool,
+}
+
+impl Controls {
+    pub fn reset_cursor(&mut self) {
+        self.keyboard.cursor = GridCursor::default();
+    }
+}
+
+impl InputSource for Controls {
+    fn next_input(&mut self, frame: &InputFrame) -> PlayerInput {
+        let input = frame.input;
+        let (dx, dy) = input.mouse_delta();
+        let mouse_touched = dx != 0.0
+            || dy != 0.0
+            || input.mouse_pressed(MouseButton::Left)
+            || input.mouse_pressed(MouseButton::Right);
+        if KeyboardInput::any_key_pressed(input) {
+            self.using_keyboard = true;
+        } else if mouse_touched {
+            self.using_keyboard = false;
+        }
+
+        if self.using_keyboard {
+            self.keyboard.next_input(frame)
+        } else {
+            MouseInput.next_input(frame)
+        }
+    }
+}
+
+#[cfg(test)]
+mod controls_tests {
+    use super::*;
+    use egor::math::vec2;
+
+    fn three_by_two_focus() -> FocusGrid {
+        let mut focus = FocusGrid::default();
+        focus.push_row(vec![Rect::new(vec2(100., 0.), vec2(10., 10.))]);
+        for r in 0..2 {
+            let row = (0..3)
+                .map(|c| Rect::new(vec2(c as f32 * 10., 20. + r as f32 * 10.), vec2(10., 10.)))
+                .collect();
+            focus.push_row(row);
+        }
+        focus
+    }
+
+    fn scripted_frame<'a>(input: &'a Input, focus: &'a FocusGrid) -> InputFrame<'a> {
+        InputFrame {
+            input,
+            mouse_position: Vec2::ZERO,
+            delta: 1. / 60.,
+            focus,
+        }
+    }
+
+    #[test]
+    fn cursor_clamps_to_ragged_rows() {
+        let focus = three_by_two_focus();
+        let mut cursor = GridCursor::default();
+        cursor.step(Direction::Right, &focus);
+        cursor.step(Direction::Right, &focus);
+        cursor.step(Direction::Right, &focus);
+        assert_eq!(GridCursor { row: 1, column: 2 }, cursor);
+
+        cursor.step(Direction::Up, &focus);
+        assert_eq!(GridCursor { row: 0, column: 0 }, cursor);
+
+        cursor.step(Direction::Down, &focus);
+        cursor.step(Direction::Down, &focus);
+        cursor.step(Direction::Down, &focus);
+        assert_eq!(GridCursor { row: 2, column: 0 }, cursor);
+    }
+
+    #[test]
+    fn scripted_input_moves_the_cursor_over_focus_targets() {
+        let focus = three_by_two_focus();
+        let input = Input::default();
+        let mut script = ScriptedInput::default();
+        script.steps.push_back(ScriptedStep {
+            direction: None,
+            held: None,
+            action: Some(Action::FillCell),
+            pressed: true,
+        });
+        script.steps.push_back(ScriptedStep {
+            direction: Some(Direction::Right),
+            held: None,
+            action: Some(Action::FillCell),
+            pressed: false,
+        });
+
+        let first = script.next_input(&scripted_frame(&input, &focus));
+        assert_eq!(vec2(5., 25.), first.position);
+        assert!(first.pressed);
+        assert!(matches!(first.action, Some(Action::FillCell)));
+
+        let second = script.next_input(&scripted_frame(&input, &focus));
+        assert_eq!(vec2(15., 25.), second.position);
+        assert!(!second.pressed);
+        assert!(script.is_finished());
+
+        let idle = script.next_input(&scripted_frame(&input, &focus));
+        assert!(idle.action.is_none());
+    }
+
+    #[test]
+    fn cursor_input_without_focus_is_parked_out_of_reach() {
+        let focus = FocusGrid::default();
+        let input = Input::default();
+        let mut script = ScriptedInput::default();
+        let parked = script.next_input(&scripted_frame(&input, &focus));
+        assert!(!Rect::new(Vec2::ZERO, vec2(1000., 1000.)).contains(parked.position));
+    }
+
+    #[test]
+    fn held_direction_repeats_after_the_delay() {
+        let mut focus = FocusGrid::default();
+        focus.push_row(vec![Rect::new(Vec2::ZERO, vec2(10., 10.))]);
+        focus.push_row(
+            (0..50)
+                .map(|c| Rect::new(vec2(c as f32 * 10., 20.), vec2(10., 10.)))
+                .collect(),
+        );
+        let input = Input::default();
+        let mut script = ScriptedInput::default();
+        script.steps.push_back(ScriptedStep {
+            direction: Some(Direction::Right),
+            ..ScriptedStep::default()
+        });
+        for _ in 1..40 {
+            script.steps.push_back(ScriptedStep {
+                held: Some(Direction::Right),
+                ..ScriptedStep::default()
+            });
+        }
+        // let go, then press again
+        script.steps.push_back(ScriptedStep::default());
+        script.steps.push_back(ScriptedStep {
+            direction: Some(Direction::Right),
+            ..ScriptedStep::default()
+        });
+
+        // a frame time that adds up without rounding
+        let frame = InputFrame {
+            delta: 1. / 64.,
+            ..scripted_frame(&input, &focus)
+        };
+        let mut moved_on = Vec::new();
+        let mut column = script.cursor.column;
+        for idx in 0..42 {
+            script.next_input(&frame);
+            if script.cursor.column != column {
+                column = script.cursor.column;
+                moved_on.push(idx);
+            }
+        }
+        // 0.3s is 19.2 frames in, then every 0.08s is 5.12 frames
+        assert_eq!(vec![0, 20, 25, 30, 35, 41], moved_on);
+        assert_eq!(6, script.cursor.column);
+    }
+}
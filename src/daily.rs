@@ -0,0 +1,365 @@
+use crate::editor::difficulty::DifficultyBand;
+use crate::generator::{GeneratedPuzzle, PuzzleRequest, SeededRng, generate};
+
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Mixed into every daily seed. Bump it whenever the generator changes in a
+// way that changes the puzzles it makes, so old dates don't quietly turn into
+// different puzzles than the ones people already solved.
+pub const DAILY_VERSION: u64 = 1;
+
+// A day on the calendar, counted in UTC. There's no time zone database to ask
+// about local midnight when offline, so everyone's day rolls over together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Date {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Date::from_days((seconds / 86_400) as i64)
+    }
+
+    // Days since 1970-01-01, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    pub fn from_days(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date {
+            year: year as i32,
+            month,
+            day,
+        }
+    }
+
+    // The other way around, see days_from_civil on the same page
+    pub fn days(&self) -> i64 {
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let shifted_month = (self.month as i64 + 9) % 12;
+        let day_of_year = (153 * shifted_month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    pub fn previous(&self) -> Date {
+        Date::from_days(self.days() - 1)
+    }
+
+    pub fn next(&self) -> Date {
+        Date::from_days(self.days() + 1)
+    }
+
+    // 0 for Monday through 6 for Sunday. 1970-01-01 was a Thursday.
+    pub fn weekday(&self) -> u32 {
+        (self.days() + 3).rem_euclid(7) as u32
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = DailyFileErr;
+    fn from_str(s: &str) -> Result<Date, DailyFileErr> {
+        let invalid = || DailyFileErr::InvalidDate {
+            found: s.to_owned(),
+        };
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || parts.next().and_then(|part| part.parse::<i64>().ok());
+        let (Some(year), Some(month), Some(day)) = (next(), next(), next()) else {
+            return Err(invalid());
+        };
+        let date = Date {
+            year: i32::try_from(year).map_err(|_| invalid())?,
+            month: u32::try_from(month).map_err(|_| invalid())?,
+            day: u32::try_from(day).map_err(|_| invalid())?,
+        };
+        // Going through days and back rolls over anything like the 31st of
+        // April, so it won't match what was written
+        match Date::from_days(date.days()) == date && date.day > 0 {
+            true => Ok(date),
+            false => Err(invalid()),
+        }
+    }
+}
+
+// Easier early in the week, building up to the weekend
+pub fn daily_request(date: Date) -> PuzzleRequest {
+    let difficulty = match date.weekday() {
+        0 | 1 => DifficultyBand::Easy,
+        2 | 3 | 6 => DifficultyBand::Medium,
+        _ => DifficultyBand::Hard,
+    };
+    let mut rng = SeededRng::new((DAILY_VERSION << 48) ^ date.days() as u64);
+    PuzzleRequest {
+        difficulty: Some(difficulty),
+        seed: rng.next_u64(),
+        ..PuzzleRequest::default()
+    }
+}
+
+pub fn daily_puzzle(date: Date) -> GeneratedPuzzle {
+    generate(&daily_request(date))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayResult {
+    pub hints_used: usize,
+    pub mistakes: usize,
+}
+
+impl DayResult {
+    fn is_better_than(&self, other: &DayResult) -> bool {
+        (self.mistakes, self.hints_used) < (other.mistakes, other.hints_used)
+    }
+}
+
+#[derive(Debug)]
+pub enum DailyFileErr {
+    InvalidDate { found: String },
+    InvalidResult { found: String },
+}
+
+impl std::error::Error for DailyFileErr {}
+
+impl std::fmt::Display for DailyFileErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        use DailyFileErr::*;
+        let s = match self {
+            InvalidDate { found } => format!("invalid date {}, expected year-month-day", found),
+            InvalidResult { found } => format!("invalid daily result: {}", found),
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// How every daily puzzle went, kept next to the levels' own progress. One
+// line per solved day with the best result for it:
+//   2026-10-17 hints_used=0 mistakes=2
+//   2026-10-18 hints_used=1 mistakes=0
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DailyProgress {
+    pub results: BTreeMap<Date, DayResult>,
+}
+
+impl DailyProgress {
+    // No file yet just means no dailies have been played
+    pub fn load(path: &Path) -> Result<DailyProgress, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(DailyProgress::default());
+        }
+        Ok(read_to_string(path)?.parse()?)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        write(path, self.to_string())
+    }
+
+    // Returns whether anything changed, replays only count when they go better
+    pub fn record(&mut self, date: Date, result: DayResult) -> bool {
+        match self.results.get(&date) {
+            Some(best) if !result.is_better_than(best) => false,
+            _ => {
+                self.results.insert(date, result);
+                true
+            }
+        }
+    }
+
+    pub fn result(&self, date: Date) -> Option<&DayResult> {
+        self.results.get(&date)
+    }
+
+    // Days in a row solved up to today. Today not being done yet doesn't break
+    // the streak, there's still time.
+    pub fn streak(&self, today: Date) -> usize {
+        let mut day = match self.results.contains_key(&today) {
+            true => today,
+            false => today.previous(),
+        };
+        let mut streak = 0;
+        while self.results.contains_key(&day) {
+            streak += 1;
+            day = day.previous();
+        }
+        streak
+    }
+}
+
+impl FromStr for DailyProgress {
+    type Err = DailyFileErr;
+    fn from_str(contents: &str) -> Result<DailyProgress, DailyFileErr> {
+        let mut progress = DailyProgress::default();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let mut words = line.split_whitespace();
+            let date: Date = words.next().unwrap_or_default().parse()?;
+            let mut result = DayResult {
+                hints_used: 0,
+                mistakes: 0,
+            };
+            for word in words {
+                let invalid = || DailyFileErr::InvalidResult {
+                    found: word.to_owned(),
+                };
+                let (key, value) = word.split_once('=').ok_or_else(invalid)?;
+                let value = value.parse().map_err(|_| invalid())?;
+                match key {
+                    "hints_used" => result.hints_used = value,
+                    "mistakes" => result.mistakes = value,
+                    _ => {}
+                }
+            }
+            progress.record(date, result);
+        }
+        Ok(progress)
+    }
+}
+
+impl std::fmt::Display for DailyProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        for (date, result) in &self.results {
+            writeln!(
+                f,
+                "{} hints_used={} mistakes={}",
+                date, result.hints_used, result.mistakes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod daily_tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn dates_count_days_from_1970() {
+        assert_eq!(0, date("1970-01-01").days());
+        assert_eq!(date("2000-03-01"), date("2000-02-29").next());
+        assert_eq!(date("2025-12-31"), date("2026-01-01").previous());
+        assert_eq!(20_744, date("2026-10-18").days());
+        // A Sunday
+        assert_eq!(6, date("2026-10-18").weekday());
+        for days in -1000..1000 {
+            assert_eq!(days, Date::from_days(days).days());
+        }
+        assert!("2026-02-29".parse::<Date>().is_err());
+        assert!("2026-13-01".parse::<Date>().is_err());
+        assert!("yesterday".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn the_same_date_and_version_make_the_same_puzzle() {
+        let first = daily_puzzle(date("2026-10-18"));
+        let again = daily_puzzle(date("2026-10-18"));
+        assert_eq!(first.info.cells, again.info.cells);
+        assert_eq!(first.image.cells, again.image.cells);
+        assert_ne!(
+            first.info.cells,
+            daily_puzzle(date("2026-10-19")).info.cells
+        );
+
+        // If this changes then so has every daily ever played, bump
+        // DAILY_VERSION and update it
+        assert_eq!(1, DAILY_VERSION);
+        let rows: Vec<String> = first
+            .info
+            .rows()
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|filled| if filled { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        let expected = [
+            "#.#......#",
+            "...#.###.#",
+            "##.#######",
+            "####.#..#.",
+            "#.#.#####.",
+            "#.####.#..",
+            "#.#..##.#.",
+            "##.#.#.#.#",
+            "#####..###",
+            "##.#.#.#..",
+        ];
+        assert_eq!(expected.to_vec(), rows);
+    }
+
+    #[test]
+    fn streaks_count_back_from_today() {
+        let mut progress = DailyProgress::default();
+        let result = DayResult {
+            hints_used: 1,
+            mistakes: 0,
+        };
+        let today = date("2026-10-18");
+        assert_eq!(0, progress.streak(today));
+        progress.record(date("2026-10-15"), result);
+        progress.record(date("2026-10-16"), result);
+        progress.record(date("2026-10-17"), result);
+        assert_eq!(3, progress.streak(today));
+        progress.record(today, result);
+        assert_eq!(4, progress.streak(today));
+        // A gap ends it
+        assert_eq!(0, progress.streak(date("2026-10-20")));
+    }
+
+    #[test]
+    fn only_better_results_replace_a_day() {
+        let mut progress = DailyProgress::default();
+        let today = date("2026-10-18");
+        let sloppy = DayResult {
+            hints_used: 0,
+            mistakes: 3,
+        };
+        let careful = DayResult {
+            hints_used: 2,
+            mistakes: 0,
+        };
+        assert!(progress.record(today, sloppy));
+        assert!(progress.record(today, careful));
+        assert!(!progress.record(today, sloppy));
+        assert_eq!(Some(&careful), progress.result(today));
+
+        let contents = progress.to_string();
+        assert_eq!("2026-10-18 hints_used=2 mistakes=0\n", contents);
+        assert_eq!(progress, contents.parse().unwrap());
+        assert!(
+            "2026-10-18 hints_used=lots"
+                .parse::<DailyProgress>()
+                .is_err()
+        );
+    }
+}
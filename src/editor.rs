@@ -1,5 +1,12 @@
+pub mod difficulty;
+pub mod drawing_tools;
+pub mod edit_history;
 pub mod editor_grids;
 pub mod editor_settings;
 pub mod editor_ui_actions;
+pub mod mask_from_image;
 pub mod solver;
 pub mod solver_display;
+pub mod solver_replay;
+pub mod transforms;
+pub mod unsaved_changes;
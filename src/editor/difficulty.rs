@@ -0,0 +1,489 @@
+use crate::editor::solver::{SolvedState, TheMultiVerseOfLines};
+use crate::hints::HintLine;
+use crate::playstate::PlayState;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Technique {
+    // The clues overlap themselves however they're laid out along the line
+    Overlap,
+    // Cells other lines settled push this line's clues into place
+    EdgeForcing,
+    // Trying a cell one way leaves some line with no way to fit its clues
+    Contradiction,
+    // Nothing left to work out so a cell had to be guessed, depth guesses in
+    Search { depth: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub line: HintLine,
+    pub technique: Technique,
+    // The cells it settled as (row, column, color), color 0 being empty
+    pub cells: Vec<(usize, usize, usize)>,
+    // How many steps this one relies on, one after the other, including itself
+    pub chain: usize,
+}
+
+// Every deduction it took to solve a puzzle, in the order they were made
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SolveTrace {
+    pub steps: Vec<Step>,
+    // How many times the lines were gone over
+    pub passes: usize,
+}
+
+impl SolveTrace {
+    pub fn max_chain(&self) -> usize {
+        self.steps.iter().map(|step| step.chain).max().unwrap_or(0)
+    }
+
+    pub fn contradictions(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.technique == Technique::Contradiction)
+            .count()
+    }
+
+    pub fn guesses(&self) -> usize {
+        self.steps
+            .iter()
+            .filter_map(|step| match step.technique {
+                Technique::Search { depth } => Some(depth),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn needs_lookahead(&self) -> bool {
+        self.contradictions() > 0 || self.guesses() > 0
+    }
+
+    // More passes and longer chains are more to keep track of, and having to
+    // look ahead or guess at all is a big jump on top of that.
+    pub fn rating(&self) -> u32 {
+        (self.passes + self.max_chain() + 5 * self.contradictions() + 20 * self.guesses()) as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DifficultyBand {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl DifficultyBand {
+    pub const ALL: [DifficultyBand; 4] = [
+        DifficultyBand::Easy,
+        DifficultyBand::Medium,
+        DifficultyBand::Hard,
+        DifficultyBand::Expert,
+    ];
+
+    pub fn of(rating: u32) -> DifficultyBand {
+        match rating {
+            0..10 => DifficultyBand::Easy,
+            10..20 => DifficultyBand::Medium,
+            20..40 => DifficultyBand::Hard,
+            _ => DifficultyBand::Expert,
+        }
+    }
+
+    // How far a rating is from falling in this band, 0 when it already does
+    pub fn distance(&self, rating: u32) -> u32 {
+        let (lowest, highest): (u32, u32) = match self {
+            DifficultyBand::Easy => (0, 9),
+            DifficultyBand::Medium => (10, 19),
+            DifficultyBand::Hard => (20, 39),
+            DifficultyBand::Expert => (40, u32::MAX),
+        };
+        lowest.saturating_sub(rating) + rating.saturating_sub(highest)
+    }
+}
+
+impl std::fmt::Display for DifficultyBand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let s = match self {
+            DifficultyBand::Easy => "Easy",
+            DifficultyBand::Medium => "Medium",
+            DifficultyBand::Hard => "Hard",
+            DifficultyBand::Expert => "Expert",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// Where the stepper is up to. The sweeps go over the lines in the same order
+// as collapse does, so stepping all the way through ends up in the same place.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    // Reading off what the clues say about each line on its own, rows first
+    Overlap {
+        next: usize,
+    },
+    // Narrowing each column (or row) with what its crossing lines know
+    Sweep {
+        columns: bool,
+        next: usize,
+        changed: bool,
+    },
+    // Line logic has nothing more to give
+    Stuck,
+    Done,
+}
+
+// Solves the puzzle one deduction at a time, the way a person would: line by
+// line while that works, then by looking for a cell that can't be something
+// without breaking a line, and only guessing when neither helps. Guesses are
+// always right since the point is to see how the puzzle gets solved, not to
+// find its solution.
+#[derive(Debug, Clone)]
+pub struct SolverStepper {
+    multiverse: TheMultiVerseOfLines,
+    phase: Phase,
+    num_columns: usize,
+    // The chain of the step that settled each cell, row by row
+    settled_by: Vec<Option<usize>>,
+    passes: usize,
+    guesses: usize,
+    // Where guesses come from, found the first time one is needed
+    solution: Option<Vec<usize>>,
+}
+
+impl SolverStepper {
+    pub fn new(play_state: &PlayState) -> Self {
+        Self {
+            multiverse: TheMultiVerseOfLines::new(play_state),
+            phase: Phase::Overlap { next: 0 },
+            num_columns: play_state.num_columns,
+            settled_by: vec![None; play_state.num_rows * play_state.num_columns],
+            passes: 0,
+            guesses: 0,
+            solution: None,
+        }
+    }
+
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.phase == Phase::Done
+    }
+
+    // Works until the next deduction that settles at least one cell, or
+    // returns None once there's nothing left to work out.
+    pub fn next_step(&mut self) -> Option<Step> {
+        loop {
+            let num_rows = self.multiverse.rows.len();
+            let num_columns = self.multiverse.columns.len();
+            match self.phase {
+                Phase::Overlap { next } if next == num_rows + num_columns => {
+                    self.start_collapse();
+                }
+                Phase::Overlap { next } => {
+                    self.phase = Phase::Overlap { next: next + 1 };
+                    let line = if next < num_rows {
+                        HintLine::Row(next)
+                    } else {
+                        HintLine::Column(next - num_rows)
+                    };
+                    if let Some(step) = self.record(line, Technique::Overlap) {
+                        return Some(step);
+                    }
+                }
+                Phase::Sweep {
+                    columns,
+                    next,
+                    changed,
+                } if next == if columns { num_columns } else { num_rows } => {
+                    self.phase = match (columns, changed) {
+                        (_, true) => {
+                            self.passes += 1;
+                            Phase::Sweep {
+                                columns: true,
+                                next: 0,
+                                changed: false,
+                            }
+                        }
+                        (true, false) => Phase::Sweep {
+                            columns: false,
+                            next: 0,
+                            changed: false,
+                        },
+                        (false, false) => match self.multiverse.line_state() {
+                            SolvedState::MultipleSolutions => Phase::Stuck,
+                            _ => Phase::Done,
+                        },
+                    };
+                }
+                Phase::Sweep {
+                    columns,
+                    next,
+                    changed,
+                } => {
+                    let line = if columns {
+                        HintLine::Column(next)
+                    } else {
+                        HintLine::Row(next)
+                    };
+                    let learned = self.multiverse.collapse_line(line);
+                    self.phase = Phase::Sweep {
+                        columns,
+                        next: next + 1,
+                        changed: changed || learned,
+                    };
+                    if learned && let Some(step) = self.record(line, Technique::EdgeForcing) {
+                        return Some(step);
+                    }
+                }
+                Phase::Stuck => {
+                    if let Some((r, c, colors)) = find_contradiction(&self.multiverse) {
+                        self.multiverse.assume(r, c, colors);
+                        self.start_collapse();
+                        if let Some(step) = self.record(HintLine::Row(r), Technique::Contradiction)
+                        {
+                            return Some(step);
+                        }
+                        continue;
+                    }
+
+                    let multiverse = &self.multiverse;
+                    let solution = self
+                        .solution
+                        .get_or_insert_with(|| {
+                            multiverse.search(1).witnesses.pop().unwrap_or_default()
+                        })
+                        .clone();
+                    let Some((r, c, _)) = self.multiverse.most_constrained_cell() else {
+                        self.phase = Phase::Done;
+                        continue;
+                    };
+                    let Some(&color) = solution.get(r * self.num_columns + c) else {
+                        self.phase = Phase::Done;
+                        continue;
+                    };
+                    self.guesses += 1;
+                    self.multiverse.assume(r, c, 1 << color);
+                    self.start_collapse();
+                    let technique = Technique::Search {
+                        depth: self.guesses,
+                    };
+                    if let Some(step) = self.record(HintLine::Row(r), technique) {
+                        return Some(step);
+                    }
+                }
+                Phase::Done => return None,
+            }
+        }
+    }
+
+    // Same as the first iteration of a call to collapse
+    fn start_collapse(&mut self) {
+        self.passes += 1;
+        self.phase = Phase::Sweep {
+            columns: true,
+            next: 0,
+            changed: false,
+        };
+    }
+
+    fn record(&mut self, line: HintLine, technique: Technique) -> Option<Step> {
+        let cells = match line {
+            HintLine::Row(r) => &self.multiverse.rows[r].cells,
+            HintLine::Column(c) => &self.multiverse.columns[c].cells,
+        };
+        let offsets: Vec<(usize, usize, usize)> = (0..cells.len())
+            .map(|idx| {
+                let (row, column) = line.cell(idx);
+                (row, column, row * self.num_columns + column)
+            })
+            .collect();
+        let chain = match technique {
+            Technique::Overlap => 1,
+            _ => {
+                let longest = offsets
+                    .iter()
+                    .filter_map(|&(_, _, offset)| self.settled_by[offset])
+                    .max();
+                longest.unwrap_or(0) + 1
+            }
+        };
+
+        let mut settled = Vec::new();
+        for (&(row, column, offset), &colors) in offsets.iter().zip(cells) {
+            if colors.count_ones() == 1 && self.settled_by[offset].is_none() {
+                self.settled_by[offset] = Some(chain);
+                settled.push((row, column, colors.trailing_zeros() as usize));
+            }
+        }
+        (!settled.is_empty()).then_some(Step {
+            line,
+            technique,
+            cells: settled,
+            chain,
+        })
+    }
+}
+
+pub fn trace_solve(play_state: &PlayState) -> SolveTrace {
+    let mut stepper = SolverStepper::new(play_state);
+    let steps = std::iter::from_fn(|| stepper.next_step()).collect();
+    SolveTrace {
+        steps,
+        passes: stepper.passes(),
+    }
+}
+
+// A cell along with the colors it's left with once the one that breaks a line
+// is ruled out.
+fn find_contradiction(multiverse: &TheMultiVerseOfLines) -> Option<(usize, usize, u32)> {
+    for r in 0..multiverse.rows.len() {
+        for c in 0..multiverse.columns.len() {
+            let colors = multiverse.cell_colors(r, c);
+            if colors.count_ones() < 2 {
+                continue;
+            }
+            for color in 0..u32::BITS {
+                if (colors >> color) & 1 == 0 {
+                    continue;
+                }
+                let mut probe = multiverse.clone();
+                probe.assume(r, c, 1 << color);
+                probe.collapse();
+                if probe.line_state() == SolvedState::Unsolvable {
+                    return Some((r, c, colors & !(1 << color)));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod difficulty_tests {
+    use super::*;
+    use crate::netpbm::Pbm;
+
+    fn play_state(picture: &[u8], width: usize) -> PlayState {
+        let pbm = Pbm {
+            width,
+            height: picture.len() / width,
+            cells: picture.iter().map(|&cell| cell == 1).collect(),
+        };
+        (&pbm).into()
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn line_solvable_puzzles_need_no_lookahead() {
+        let tps = play_state(&[
+            1, 0, 0, 0, 0,
+            1, 1, 0, 0, 0,
+            1, 1, 1, 0, 0,
+            1, 1, 1, 1, 0,
+            1, 1, 1, 1, 1,
+        ], 5);
+        let trace = trace_solve(&tps);
+        assert!(!trace.needs_lookahead());
+        assert_eq!(Technique::Overlap, trace.steps[0].technique);
+        // Every cell gets worked out exactly once
+        let settled: usize = trace.steps.iter().map(|step| step.cells.len()).sum();
+        assert_eq!(25, settled);
+        assert_eq!(DifficultyBand::Easy, DifficultyBand::of(trace.rating()));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn chains_grow_as_lines_rely_on_each_other() {
+        // Only the full bottom row and right column are given by overlap,
+        // everything else has to be worked out from those.
+        let tps = play_state(&[
+            0, 0, 0, 0, 1,
+            0, 0, 0, 1, 1,
+            0, 0, 1, 0, 1,
+            0, 1, 0, 0, 1,
+            1, 1, 1, 1, 1,
+        ], 5);
+        let trace = trace_solve(&tps);
+        assert!(trace.max_chain() > 1);
+        let forced: Vec<&Step> = trace
+            .steps
+            .iter()
+            .filter(|step| step.technique == Technique::EdgeForcing)
+            .collect();
+        assert!(!forced.is_empty());
+        assert!(forced.iter().all(|step| step.chain > 1));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn puzzles_that_stall_need_lookahead() {
+        let easy = trace_solve(&play_state(&[
+            1, 1, 1, 1, 1,
+            0, 0, 0, 0, 0,
+            1, 1, 1, 1, 1,
+            0, 0, 0, 0, 0,
+            1, 1, 1, 1, 1,
+        ], 5));
+        let hard = trace_solve(&play_state(&[
+            0, 0, 1, 1, 0,
+            0, 1, 1, 0, 1,
+            1, 1, 0, 0, 0,
+            0, 1, 0, 0, 1,
+            0, 0, 0, 0, 1,
+        ], 5));
+        assert!(hard.needs_lookahead());
+        assert!(hard.rating() > easy.rating());
+    }
+
+    #[test]
+    fn stepping_through_rates_shipped_levels_as_saved() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("levels");
+        let levels = crate::levels::load_levels_from_dir(&dir).unwrap();
+        for level in &levels {
+            let tps: PlayState = level.into();
+            let mut stepper = SolverStepper::new(&tps);
+            let mut steps = 0;
+            while stepper.next_step().is_some() {
+                steps += 1;
+            }
+            assert!(stepper.is_done());
+            let trace = trace_solve(&tps);
+            assert_eq!(trace.steps.len(), steps, "{}", level.path.display());
+            assert_eq!(
+                level.difficulty,
+                Some(trace.rating()),
+                "{}",
+                level.path.display()
+            );
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn line_steps_match_a_full_collapse() {
+        let tps = play_state(&[
+            0, 0, 0, 0, 1,
+            0, 0, 0, 1, 1,
+            0, 0, 1, 0, 1,
+            0, 1, 0, 0, 1,
+            1, 1, 1, 1, 1,
+        ], 5);
+        let mut multiverse = TheMultiVerseOfLines::new(&tps);
+        let iterations = multiverse.collapse();
+        let trace = trace_solve(&tps);
+        // The overlap pass doesn't count as a pass of its own
+        assert_eq!(iterations, trace.passes);
+        let mut settled = vec![None; 25];
+        for step in &trace.steps {
+            for &(r, c, color) in &step.cells {
+                assert_eq!(None, settled[r * 5 + c].replace(color));
+            }
+        }
+        let expected: Vec<Option<usize>> = tps.picture().iter().map(|&color| Some(color)).collect();
+        assert_eq!(expected, settled);
+    }
+}
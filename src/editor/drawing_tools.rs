@@ -0,0 +1,390 @@
+// What the left mouse button does on the editor grids. Right click always
+// takes things away again with the same tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tool {
+    #[default]
+    Pencil,
+    Line,
+    Rectangle,
+    FilledRectangle,
+    Ellipse,
+    Fill,
+    // Picks the current color up off the PPM grid
+    Eyedropper,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 7] = [
+        Tool::Pencil,
+        Tool::Line,
+        Tool::Rectangle,
+        Tool::FilledRectangle,
+        Tool::Ellipse,
+        Tool::Fill,
+        Tool::Eyedropper,
+    ];
+
+    // Shapes are dragged out from one corner and only drawn once let go
+    pub fn is_shape(&self) -> bool {
+        matches!(
+            self,
+            Tool::Line | Tool::Rectangle | Tool::FilledRectangle | Tool::Ellipse
+        )
+    }
+
+    // Every cell the shape covers when dragged from one cell to another, or
+    // nothing for tools that aren't shapes
+    pub fn shape(&self, from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+        match self {
+            Tool::Line => line(from, to),
+            Tool::Rectangle => rectangle(from, to, false),
+            Tool::FilledRectangle => rectangle(from, to, true),
+            Tool::Ellipse => ellipse(from, to),
+            Tool::Pencil | Tool::Fill | Tool::Eyedropper => Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for Tool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            Tool::Pencil => "Pencil",
+            Tool::Line => "Line",
+            Tool::Rectangle => "Rectangle",
+            Tool::FilledRectangle => "Filled rectangle",
+            Tool::Ellipse => "Ellipse",
+            Tool::Fill => "Fill",
+            Tool::Eyedropper => "Eyedropper",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Drawing mirrored as it goes, for symmetric pictures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symmetry {
+    #[default]
+    Off,
+    // Mirrored left to right
+    Horizontal,
+    // Mirrored top to bottom
+    Vertical,
+    Both,
+    // Turned half way around the middle
+    Rotational,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 5] = [
+        Symmetry::Off,
+        Symmetry::Horizontal,
+        Symmetry::Vertical,
+        Symmetry::Both,
+        Symmetry::Rotational,
+    ];
+
+    // The cells along with wherever they get mirrored to in a level of the
+    // given size, without repeats for cells that land on themselves
+    pub fn mirror(
+        &self,
+        cells: &[(usize, usize)],
+        rows: usize,
+        columns: usize,
+    ) -> Vec<(usize, usize)> {
+        let mut mirrored = Vec::with_capacity(cells.len() * 4);
+        for &(r, c) in cells.iter().filter(|&&(r, c)| r < rows && c < columns) {
+            let (flipped_r, flipped_c) = (rows - 1 - r, columns - 1 - c);
+            mirrored.push((r, c));
+            match self {
+                Symmetry::Off => {}
+                Symmetry::Horizontal => mirrored.push((r, flipped_c)),
+                Symmetry::Vertical => mirrored.push((flipped_r, c)),
+                Symmetry::Both => {
+                    mirrored.extend([(r, flipped_c), (flipped_r, c), (flipped_r, flipped_c)])
+                }
+                Symmetry::Rotational => mirrored.push((flipped_r, flipped_c)),
+            }
+        }
+        mirrored.sort_unstable();
+        mirrored.dedup();
+        mirrored
+    }
+}
+
+impl std::fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            Symmetry::Off => "Off",
+            Symmetry::Horizontal => "Horizontal",
+            Symmetry::Vertical => "Vertical",
+            Symmetry::Both => "Both",
+            Symmetry::Rotational => "Rotational",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Anything the tools can draw on. The PBM grid holds bools and the PPM grid
+// holds colors, but drawing a line is the same on either.
+pub trait Canvas {
+    type Cell: Copy + PartialEq;
+    fn rows(&self) -> usize;
+    fn columns(&self) -> usize;
+    fn get(&self, r: usize, c: usize) -> Self::Cell;
+    fn set(&mut self, r: usize, c: usize, value: Self::Cell);
+}
+
+// One of the editor's grids along with the level's size, which is where
+// fills and shapes stop.
+pub struct LevelArea<'a, T> {
+    pub grid: &'a mut [Vec<T>],
+    pub rows: usize,
+    pub columns: usize,
+}
+
+impl<T: Copy + PartialEq> Canvas for LevelArea<'_, T> {
+    type Cell = T;
+
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn columns(&self) -> usize {
+        self.columns
+    }
+
+    fn get(&self, r: usize, c: usize) -> T {
+        self.grid[r][c]
+    }
+
+    fn set(&mut self, r: usize, c: usize, value: T) {
+        self.grid[r][c] = value;
+    }
+}
+
+// Returns whether anything changed
+pub fn paint<C: Canvas>(canvas: &mut C, cells: &[(usize, usize)], value: C::Cell) -> bool {
+    let mut changed = false;
+    for &(r, c) in cells {
+        if r < canvas.rows() && c < canvas.columns() && canvas.get(r, c) != value {
+            canvas.set(r, c, value);
+            changed = true;
+        }
+    }
+    changed
+}
+
+// Fills everything joined to the start cell by an edge that shares its value,
+// diagonals don't count. Returns whether anything changed.
+pub fn flood_fill<C: Canvas>(canvas: &mut C, start: (usize, usize), value: C::Cell) -> bool {
+    let (r, c) = start;
+    if r >= canvas.rows() || c >= canvas.columns() {
+        return false;
+    }
+    let replacing = canvas.get(r, c);
+    if replacing == value {
+        return false;
+    }
+    let mut stack = vec![start];
+    canvas.set(r, c, value);
+    while let Some((r, c)) = stack.pop() {
+        let neighbours = [
+            (r.wrapping_sub(1), c),
+            (r + 1, c),
+            (r, c.wrapping_sub(1)),
+            (r, c + 1),
+        ];
+        for (r, c) in neighbours {
+            if r < canvas.rows() && c < canvas.columns() && canvas.get(r, c) == replacing {
+                canvas.set(r, c, value);
+                stack.push((r, c));
+            }
+        }
+    }
+    true
+}
+
+// Bresenham's, so every step moves one cell along the longer axis
+fn line(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    let (mut r, mut c) = (from.0 as i64, from.1 as i64);
+    let (r1, c1) = (to.0 as i64, to.1 as i64);
+    let dr = -(r1 - r).abs();
+    let dc = (c1 - c).abs();
+    let step_r = if r < r1 { 1 } else { -1 };
+    let step_c = if c < c1 { 1 } else { -1 };
+    let mut error = dc + dr;
+    let mut cells = vec![(r as usize, c as usize)];
+    while (r, c) != (r1, c1) {
+        let doubled = 2 * error;
+        if doubled >= dr {
+            error += dr;
+            c += step_c;
+        }
+        if doubled <= dc {
+            error += dc;
+            r += step_r;
+        }
+        cells.push((r as usize, c as usize));
+    }
+    cells
+}
+
+fn corners(from: (usize, usize), to: (usize, usize)) -> (usize, usize, usize, usize) {
+    (
+        from.0.min(to.0),
+        from.1.min(to.1),
+        from.0.max(to.0),
+        from.1.max(to.1),
+    )
+}
+
+fn rectangle(from: (usize, usize), to: (usize, usize), filled: bool) -> Vec<(usize, usize)> {
+    let (top, left, bottom, right) = corners(from, to);
+    let mut cells = Vec::new();
+    for r in top..=bottom {
+        for c in left..=right {
+            let on_edge = r == top || r == bottom || c == left || c == right;
+            if filled || on_edge {
+                cells.push((r, c));
+            }
+        }
+    }
+    cells
+}
+
+// The outline of the biggest ellipse that fits in the dragged out box. Cells
+// count as inside when their middle is, and the outline is every inside cell
+// with an outside cell next to it.
+fn ellipse(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    let (top, left, bottom, right) = corners(from, to);
+    let center_r = (top + bottom) as f32 / 2.;
+    let center_c = (left + right) as f32 / 2.;
+    let radius_r = (bottom - top) as f32 / 2. + 0.5;
+    let radius_c = (right - left) as f32 / 2. + 0.5;
+    let inside = |r: i64, c: i64| {
+        let dr = (r as f32 - center_r) / radius_r;
+        let dc = (c as f32 - center_c) / radius_c;
+        dr * dr + dc * dc <= 1.
+    };
+    let mut cells = Vec::new();
+    for r in top..=bottom {
+        for c in left..=right {
+            let (r_, c_) = (r as i64, c as i64);
+            let on_edge = !inside(r_ - 1, c_)
+                || !inside(r_ + 1, c_)
+                || !inside(r_, c_ - 1)
+                || !inside(r_, c_ + 1);
+            if inside(r_, c_) && on_edge {
+                cells.push((r, c));
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod drawing_tools_tests {
+    use super::*;
+
+    fn area<T>(grid: &mut [Vec<T>], size: usize) -> LevelArea<'_, T> {
+        LevelArea {
+            grid,
+            rows: size,
+            columns: size,
+        }
+    }
+
+    #[test]
+    fn lines_join_their_ends_one_cell_at_a_time() {
+        assert_eq!(
+            vec![(0, 0), (1, 1), (2, 2)],
+            Tool::Line.shape((0, 0), (2, 2))
+        );
+        let cells = Tool::Line.shape((4, 0), (1, 7));
+        assert_eq!(Some(&(4, 0)), cells.first());
+        assert_eq!(Some(&(1, 7)), cells.last());
+        assert_eq!(8, cells.len());
+        for pair in cells.windows(2) {
+            assert!(pair[0].0.abs_diff(pair[1].0) <= 1);
+            assert_eq!(1, pair[0].1.abs_diff(pair[1].1));
+        }
+    }
+
+    #[test]
+    fn rectangles_and_ellipses_stay_in_their_box() {
+        // Dragged from the bottom right, a 3x4 box
+        assert_eq!(10, Tool::Rectangle.shape((3, 4), (1, 1)).len());
+        assert_eq!(12, Tool::FilledRectangle.shape((3, 4), (1, 1)).len());
+        let ellipse = Tool::Ellipse.shape((0, 0), (6, 8));
+        for &(r, c) in &ellipse {
+            assert!(r <= 6 && c <= 8);
+            // Mirrored both ways
+            assert!(ellipse.contains(&(6 - r, c)));
+            assert!(ellipse.contains(&(r, 8 - c)));
+        }
+        // Touches each side of the box, but not the corners
+        assert!(ellipse.contains(&(0, 4)) && ellipse.contains(&(3, 0)));
+        assert!(!ellipse.contains(&(0, 0)));
+        // Hollow in the middle
+        assert!(!ellipse.contains(&(3, 4)));
+        assert_eq!(vec![(2, 2)], Tool::Ellipse.shape((2, 2), (2, 2)));
+    }
+
+    #[test]
+    fn symmetry_mirrors_around_the_level_not_the_grid() {
+        assert_eq!(vec![(1, 2)], Symmetry::Off.mirror(&[(1, 2)], 5, 7));
+        assert_eq!(
+            vec![(1, 2), (1, 4)],
+            Symmetry::Horizontal.mirror(&[(1, 2)], 5, 7)
+        );
+        assert_eq!(
+            vec![(1, 2), (3, 2)],
+            Symmetry::Vertical.mirror(&[(1, 2)], 5, 7)
+        );
+        assert_eq!(
+            vec![(1, 2), (1, 4), (3, 2), (3, 4)],
+            Symmetry::Both.mirror(&[(1, 2)], 5, 7)
+        );
+        assert_eq!(
+            vec![(1, 2), (3, 4)],
+            Symmetry::Rotational.mirror(&[(1, 2)], 5, 7)
+        );
+        // The middle only lands on itself
+        assert_eq!(vec![(2, 3)], Symmetry::Both.mirror(&[(2, 3)], 5, 7));
+        // Nothing outside the level
+        assert!(Symmetry::Both.mirror(&[(5, 0)], 5, 7).is_empty());
+    }
+
+    #[test]
+    fn fills_go_through_edges_but_not_corners() {
+        // A diagonal wall splits the top left from the rest
+        let mut grid = vec![
+            vec![false, false, true, false],
+            vec![false, true, false, false],
+            vec![true, false, false, false],
+            vec![false, false, false, false],
+        ];
+        assert!(flood_fill(&mut area(&mut grid, 4), (0, 0), true));
+        assert!(grid[0][1] && grid[1][0]);
+        assert!(!grid[3][3] && !grid[0][3]);
+        assert!(!flood_fill(&mut area(&mut grid, 4), (0, 0), true));
+    }
+
+    #[test]
+    fn tools_draw_on_colors_the_same_as_cells() {
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let black = [0.0, 0.0, 0.0, 1.0];
+        let mut grid = vec![vec![black; 5]; 5];
+        // Only the level's 3x3 corner gets filled
+        assert!(flood_fill(&mut area(&mut grid, 3), (1, 1), red));
+        assert_eq!(red, grid[2][2]);
+        assert_eq!(black, grid[3][3]);
+        assert_eq!(black, grid[0][4]);
+
+        let cells = Tool::Line.shape((0, 0), (4, 4));
+        assert!(paint(&mut area(&mut grid, 3), &cells, black));
+        assert_eq!(black, grid[1][1]);
+        assert_eq!(red, grid[0][2]);
+        assert!(!paint(&mut area(&mut grid, 3), &cells, black));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::editor::editor_grids::EditorGrids;
+use crate::editor::editor_settings::LevelSettings;
+
+// How many changes back undo can go. Each one holds both grids in full.
+const MAX_HISTORY: usize = 100;
+
+// Everything undo puts back
+#[derive(Clone, PartialEq)]
+struct Snapshot {
+    pbm_grid: Vec<Vec<bool>>,
+    ppm_grid: Vec<Vec<[f32; 4]>>,
+    width: usize,
+    height: usize,
+    color_puzzle: bool,
+    palette: Vec<[f32; 4]>,
+}
+
+impl Snapshot {
+    fn of(level_settings: &LevelSettings, grids: &EditorGrids) -> Self {
+        Self {
+            pbm_grid: grids.pbm_grid.clone(),
+            ppm_grid: grids.ppm_grid.clone(),
+            width: level_settings.width,
+            height: level_settings.height,
+            color_puzzle: level_settings.color_puzzle,
+            palette: level_settings.palette.clone(),
+        }
+    }
+
+    fn restore(&self, level_settings: &mut LevelSettings, grids: &mut EditorGrids) {
+        grids.pbm_grid.clone_from(&self.pbm_grid);
+        grids.ppm_grid.clone_from(&self.ppm_grid);
+        level_settings.width = self.width;
+        level_settings.height = self.height;
+        level_settings.color_puzzle = self.color_puzzle;
+        level_settings.palette.clone_from(&self.palette);
+    }
+}
+
+// Undo and redo for the editor. Rather than every bit of the editor reporting
+// what it changed, the design is compared against how it looked after the last
+// change once a stroke ends or an action is done. So a whole mouse stroke, or
+// a whole slide of the width slider, comes out as a single step.
+pub struct EditHistory {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    // How things looked once the last recorded change was done
+    settled: Snapshot,
+    // How things looked when last saved or opened
+    saved: Snapshot,
+    // Whether settled is different to saved, kept up to date so nobody has
+    // to compare the grids every frame to find out
+    unsaved: bool,
+}
+
+impl EditHistory {
+    pub fn new(level_settings: &LevelSettings, grids: &EditorGrids) -> Self {
+        let now = Snapshot::of(level_settings, grids);
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            settled: now.clone(),
+            saved: now,
+            unsaved: false,
+        }
+    }
+
+    // Starts over on a different design, so undo can't bring back the old one
+    pub fn reset(&mut self, level_settings: &LevelSettings, grids: &EditorGrids) {
+        *self = Self::new(level_settings, grids);
+    }
+
+    pub fn mark_saved(&mut self, level_settings: &LevelSettings, grids: &EditorGrids) {
+        self.saved = Snapshot::of(level_settings, grids);
+        self.unsaved = false;
+    }
+
+    // As of the last settle. Undoing back to how it was saved counts as no
+    // changes.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.unsaved
+    }
+
+    // Call once a stroke ends or an action is done. Returns true when there was
+    // a change to record.
+    pub fn settle(&mut self, level_settings: &LevelSettings, grids: &EditorGrids) -> bool {
+        let now = Snapshot::of(level_settings, grids);
+        if now == self.settled {
+            return false;
+        }
+        self.undo.push(std::mem::replace(&mut self.settled, now));
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.unsaved = self.settled != self.saved;
+        true
+    }
+
+    // Returns false when there's nothing to undo
+    pub fn undo(&mut self, level_settings: &mut LevelSettings, grids: &mut EditorGrids) -> bool {
+        self.settle(level_settings, grids);
+        let Some(previous) = self.undo.pop() else {
+            return false;
+        };
+        previous.restore(level_settings, grids);
+        self.redo
+            .push(std::mem::replace(&mut self.settled, previous));
+        self.unsaved = self.settled != self.saved;
+        true
+    }
+
+    // Returns false when there's nothing to redo
+    pub fn redo(&mut self, level_settings: &mut LevelSettings, grids: &mut EditorGrids) -> bool {
+        // Changing anything since the last undo throws the redos away
+        if self.settle(level_settings, grids) {
+            return false;
+        }
+        let Some(next) = self.redo.pop() else {
+            return false;
+        };
+        next.restore(level_settings, grids);
+        self.undo.push(std::mem::replace(&mut self.settled, next));
+        self.unsaved = self.settled != self.saved;
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod edit_history_tests {
+    use super::*;
+
+    #[test]
+    fn a_stroke_is_undone_in_one_go() {
+        let mut level_settings = LevelSettings::default();
+        let mut grids = EditorGrids::default();
+        let mut history = EditHistory::new(&level_settings, &grids);
+        assert!(!history.settle(&level_settings, &grids));
+
+        // One stroke over three cells, only settled once the mouse comes up
+        for c in 0..3 {
+            grids.pbm_grid[0][c] = true;
+        }
+        assert!(history.settle(&level_settings, &grids));
+        level_settings.width = 12;
+        grids.ppm_grid[1][1] = [1.0, 0.0, 0.0, 1.0];
+        assert!(history.settle(&level_settings, &grids));
+
+        assert!(history.undo(&mut level_settings, &mut grids));
+        assert_eq!(10, level_settings.width);
+        assert_eq!([0.0, 0.0, 0.0, 1.0], grids.ppm_grid[1][1]);
+        assert!(grids.pbm_grid[0][2]);
+        assert!(history.undo(&mut level_settings, &mut grids));
+        assert!(!grids.pbm_grid[0][0]);
+        assert!(!history.undo(&mut level_settings, &mut grids));
+
+        assert!(history.redo(&mut level_settings, &mut grids));
+        assert!(history.redo(&mut level_settings, &mut grids));
+        assert_eq!(12, level_settings.width);
+        assert!(grids.pbm_grid[0][1]);
+        assert!(!history.redo(&mut level_settings, &mut grids));
+    }
+
+    #[test]
+    fn undoing_back_to_the_save_leaves_nothing_unsaved() {
+        let mut level_settings = LevelSettings::default();
+        let mut grids = EditorGrids::default();
+        let mut history = EditHistory::new(&level_settings, &grids);
+        assert!(!history.has_unsaved_changes());
+
+        // Mid stroke doesn't count until it's settled
+        grids.pbm_grid[2][2] = true;
+        assert!(!history.has_unsaved_changes());
+        history.settle(&level_settings, &grids);
+        assert!(history.has_unsaved_changes());
+        history.mark_saved(&level_settings, &grids);
+        assert!(!history.has_unsaved_changes());
+
+        history.undo(&mut level_settings, &mut grids);
+        assert!(history.has_unsaved_changes());
+        history.redo(&mut level_settings, &mut grids);
+        assert!(!history.has_unsaved_changes());
+    }
+
+    #[test]
+    fn resetting_forgets_the_old_design() {
+        let mut level_settings = LevelSettings::default();
+        let mut grids = EditorGrids::default();
+        let mut history = EditHistory::new(&level_settings, &grids);
+        grids.pbm_grid[1][1] = true;
+        history.settle(&level_settings, &grids);
+        history.undo(&mut level_settings, &mut grids);
+        grids.pbm_grid[3][3] = true;
+        history.settle(&level_settings, &grids);
+        assert!(history.has_unsaved_changes());
+
+        let mut grids = EditorGrids::new(6, 6);
+        level_settings.width = 6;
+        level_settings.height = 6;
+        history.reset(&level_settings, &grids);
+        assert!(!history.has_unsaved_changes());
+        assert!(!history.can_undo() && !history.can_redo());
+        assert!(!history.undo(&mut level_settings, &mut grids));
+        assert_eq!(6, grids.pbm_grid.len());
+        assert!(!history.settle(&level_settings, &grids));
+    }
+
+    #[test]
+    fn changing_things_after_an_undo_drops_the_redos() {
+        let mut level_settings = LevelSettings::default();
+        let mut grids = EditorGrids::default();
+        let mut history = EditHistory::new(&level_settings, &grids);
+        grids.pbm_grid[0][0] = true;
+        history.settle(&level_settings, &grids);
+        history.undo(&mut level_settings, &mut grids);
+        assert!(history.can_redo());
+
+        // Not settled yet, redo still notices
+        grids.pbm_grid[4][4] = true;
+        assert!(!history.redo(&mut level_settings, &mut grids));
+        assert!(!history.can_redo());
+        assert!(grids.pbm_grid[4][4]);
+        assert!(!grids.pbm_grid[0][0]);
+        assert!(history.can_undo());
+    }
+}
@@ -1,62 +1,257 @@
 use crate::base_dir;
-use crate::editor::editor_settings::LevelSettings;
+use crate::editor::difficulty::trace_solve;
+use crate::editor::drawing_tools::{Canvas, LevelArea, Symmetry, Tool, flood_fill, paint};
+use crate::editor::editor_settings::{LevelSettings, MAX_SIZE};
 use crate::editor::editor_ui_actions::UiActions;
-use crate::editor::solver::TheMultiVerseOfLines;
-use crate::levels::Level;
+use crate::editor::solver::{MAX_COLORS, TheMultiVerseOfLines};
+use crate::editor::solver_replay::{Replay, ReplayCell};
+use crate::levels::{Level, LevelStats, PuzzleKind};
 use crate::netpbm::Pbm;
+use crate::netpbm::Pgm;
 use crate::netpbm::Ppm;
 use crate::ui::GridLayout;
 use crate::ui::draw_centered_text;
 use egor::app::FrameContext;
-use egor::input::MouseButton;
+use egor::input::{Input, MouseButton};
 use egor::math::Rect;
 use egor::math::{Vec2, vec2};
 use egor::render::Color;
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+// What right click paints the PPM grid with
+pub const BLANK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+#[derive(Clone)]
 pub struct EditorGrids {
     pub pbm_grid: Vec<Vec<bool>>,
     pub ppm_grid: Vec<Vec<[f32; 4]>>,
     pub size: Vec2,
     pub top_left: Vec2,
+    // A shape being dragged out, only drawn for real once the mouse lets go
+    drag: Option<ShapeDrag>,
+    // Whatever shrinking the level cut off, when asked to keep it
+    hidden: Option<HiddenCells>,
 }
 
-impl Default for EditorGrids {
-    fn default() -> EditorGrids {
-        let mut pbm_grid = Vec::with_capacity(20);
-        let mut ppm_grid = Vec::with_capacity(20);
-
-        for _ in 0..20 {
-            let mut pbm_row = Vec::with_capacity(20);
-            let mut ppm_row = Vec::with_capacity(20);
-            for _ in 0..20 {
-                pbm_row.push(false);
-                ppm_row.push([0.0, 0.0, 0.0, 1.0]);
+// Which part of the design stays put when the level is resized. Shrinking
+// crops away from it and growing adds blank cells away from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    pub const ALL: [Anchor; 9] = [
+        Anchor::TopLeft,
+        Anchor::Top,
+        Anchor::TopRight,
+        Anchor::Left,
+        Anchor::Center,
+        Anchor::Right,
+        Anchor::BottomLeft,
+        Anchor::Bottom,
+        Anchor::BottomRight,
+    ];
+
+    // How far down and across the level the anchor is, in halves
+    fn halves(&self) -> (usize, usize) {
+        match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (0, 1),
+            Anchor::TopRight => (0, 2),
+            Anchor::Left => (1, 0),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (1, 2),
+            Anchor::BottomLeft => (2, 0),
+            Anchor::Bottom => (2, 1),
+            Anchor::BottomRight => (2, 2),
+        }
+    }
+}
+
+impl std::fmt::Display for Anchor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            Anchor::TopLeft => "Top left",
+            Anchor::Top => "Top",
+            Anchor::TopRight => "Top right",
+            Anchor::Left => "Left",
+            Anchor::Center => "Center",
+            Anchor::Right => "Right",
+            Anchor::BottomLeft => "Bottom left",
+            Anchor::Bottom => "Bottom",
+            Anchor::BottomRight => "Bottom right",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// The whole drawing the level was cut out of, so growing it again brings back
+// what shrinking took away
+#[derive(Clone)]
+struct HiddenCells {
+    pbm_grid: Vec<Vec<bool>>,
+    ppm_grid: Vec<Vec<[f32; 4]>>,
+    // Where the level's top left sits in the drawing
+    origin: (usize, usize),
+    // The level's size when it was cut out. Undo puts back an older size
+    // without telling anyone, and then this can't be lined up again.
+    rows: usize,
+    columns: usize,
+}
+
+#[derive(Clone, Copy)]
+struct ShapeDrag {
+    tool: Tool,
+    on_ppm: bool,
+    from: (usize, usize),
+    to: (usize, usize),
+    erase: bool,
+}
+
+// One go of a tool on a grid
+enum Drawing {
+    Cells(Vec<(usize, usize)>),
+    FillFrom(Vec<(usize, usize)>),
+}
+
+impl Drawing {
+    fn mirrored(self, symmetry: Symmetry, rows: usize, columns: usize) -> Drawing {
+        match self {
+            Drawing::Cells(cells) => Drawing::Cells(symmetry.mirror(&cells, rows, columns)),
+            Drawing::FillFrom(starts) => Drawing::FillFrom(symmetry.mirror(&starts, rows, columns)),
+        }
+    }
+
+    fn apply<C: Canvas>(&self, canvas: &mut C, value: C::Cell) -> bool {
+        match self {
+            Drawing::Cells(cells) => paint(canvas, cells, value),
+            Drawing::FillFrom(starts) => {
+                // Later fills find their start already done when they're joined up
+                let mut changed = false;
+                for &start in starts {
+                    changed |= flood_fill(canvas, start, value);
+                }
+                changed
             }
-            pbm_grid.push(pbm_row);
-            ppm_grid.push(ppm_row);
         }
+    }
+}
 
+impl Default for EditorGrids {
+    fn default() -> EditorGrids {
+        let level_settings = LevelSettings::default();
+        EditorGrids::new(level_settings.width, level_settings.height)
+    }
+}
+
+impl EditorGrids {
+    // The grids are always exactly the size of the level
+    pub fn new(width: usize, height: usize) -> EditorGrids {
         EditorGrids {
-            pbm_grid,
-            ppm_grid,
+            pbm_grid: vec![vec![false; width]; height],
+            ppm_grid: vec![vec![BLANK; width]; height],
             size: vec2(400., 400.),
             top_left: vec2(400., 120.), // [ 90 + 500 + 100 + 500 + 90  ]
+            drag: None,
+            hidden: None,
         }
     }
-}
 
-impl EditorGrids {
     pub fn load_level(&mut self, level: &Level) {
-        for (r, row) in level.info.rows().into_iter().enumerate() {
-            for (c, b) in row.into_iter().enumerate() {
-                self.pbm_grid[r][c] = b;
-            }
+        self.pbm_grid = level.info.rows();
+        self.ppm_grid = level
+            .image
+            .rows()
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|triplet| level.image.to_rgba(triplet))
+                    .collect()
+            })
+            .collect();
+        self.forget_hidden();
+    }
+
+    // Resizes the level around the anchor in the settings. Anything cut off is
+    // thrown away unless the settings say to keep hidden cells, in which case
+    // growing back over them brings them back.
+    pub fn resize(&mut self, level_settings: &mut LevelSettings, width: usize, height: usize) {
+        let width = width.clamp(1, MAX_SIZE);
+        let height = height.clamp(1, MAX_SIZE);
+        let (rows, columns) = (level_settings.height, level_settings.width);
+        let keep = level_settings.keep_hidden_cells;
+
+        // Lay the level back down over what it was cut out of
+        let mut drawing = match self.hidden.take() {
+            Some(hidden) if keep && (hidden.rows, hidden.columns) == (rows, columns) => hidden,
+            _ => HiddenCells {
+                pbm_grid: self.pbm_grid.clone(),
+                ppm_grid: self.ppm_grid.clone(),
+                origin: (0, 0),
+                rows,
+                columns,
+            },
+        };
+        let (top, left) = drawing.origin;
+        paste(&mut drawing.pbm_grid, &self.pbm_grid, top, left);
+        paste(&mut drawing.ppm_grid, &self.ppm_grid, top, left);
+
+        // The anchor's cell stays the same cell either side of the resize.
+        // Going one step at a time around the center then grows or shrinks
+        // each side in turn, same as doing it all at once would.
+        let (half_down, half_across) = level_settings.resize_anchor.halves();
+        let top = top as i64 + (rows * half_down / 2) as i64 - (height * half_down / 2) as i64;
+        let left =
+            left as i64 + (columns * half_across / 2) as i64 - (width * half_across / 2) as i64;
+        let padding = (
+            (-top).max(0) as usize,
+            (-left).max(0) as usize,
+            (top + height as i64 - drawing.pbm_grid.len() as i64).max(0) as usize,
+            (left + width as i64 - drawing.pbm_grid[0].len() as i64).max(0) as usize,
+        );
+        pad(&mut drawing.pbm_grid, padding, false);
+        pad(&mut drawing.ppm_grid, padding, BLANK);
+        let origin = (
+            (top + padding.0 as i64) as usize,
+            (left + padding.1 as i64) as usize,
+        );
+
+        self.pbm_grid = cut(&drawing.pbm_grid, origin, height, width);
+        self.ppm_grid = cut(&drawing.ppm_grid, origin, height, width);
+        level_settings.width = width;
+        level_settings.height = height;
+        if keep {
+            self.hidden = Some(HiddenCells {
+                origin,
+                rows: height,
+                columns: width,
+                ..drawing
+            });
         }
-        for (r, row) in level.image.rows().into_iter().enumerate() {
-            for (c, triplet) in row.into_iter().enumerate() {
-                self.ppm_grid[r][c] = level.image.to_rgba(triplet);
+    }
+
+    // For changes that move the level's cells around, after which the hidden
+    // ones wouldn't line up with them any more
+    pub fn forget_hidden(&mut self) {
+        self.hidden = None;
+    }
+
+    pub fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        // Repairs found before a resize can point past the level now
+        for &(r, c) in cells {
+            if let Some(filled) = self.pbm_grid.get_mut(r).and_then(|row| row.get_mut(c)) {
+                *filled = !*filled;
             }
         }
     }
@@ -90,28 +285,16 @@ impl EditorGrids {
         frame_context: &mut FrameContext,
         level_settings: &mut LevelSettings,
         last_known_solve: &TheMultiVerseOfLines,
+        differing_cells: &[(usize, usize)],
+        replay: Option<&Replay>,
     ) -> UiActions {
-        let mut action = UiActions::Nothing;
-
         let gfx = &mut (frame_context.gfx);
         let input = &mut (frame_context.input);
-        let left_mouse_pressed =
-            input.mouse_pressed(MouseButton::Left) || input.mouse_held(MouseButton::Left);
-        let right_mouse_pressed =
-            input.mouse_pressed(MouseButton::Right) || input.mouse_held(MouseButton::Right);
         let (mx, my) = input.mouse_position();
         let world_xy = gfx.camera().screen_to_world(Vec2::new(mx, my));
 
         let num_boxes_x = level_settings.width;
         let num_boxes_y = level_settings.height; // TODO: maybe just always have a square
-        let gutter = 2.;
-        let cell_size_x =
-            (self.size.x - (gutter + gutter * num_boxes_x as f32)) / num_boxes_x as f32;
-        let cell_size_y =
-            (self.size.y - (gutter + gutter * num_boxes_y as f32)) / num_boxes_y as f32;
-        let cell_size = vec2(cell_size_x, cell_size_y);
-
-        let pbm_anchor = self.top_left;
 
         let layout = GridLayout {
             area: Rect {
@@ -120,7 +303,8 @@ impl EditorGrids {
             },
             rows: level_settings.height,
             columns: level_settings.width,
-            cell_gap: 2.,
+            // Big levels would be all gap otherwise
+            cell_gap: (self.size.x / (num_boxes_x.max(num_boxes_y) * 8) as f32).min(2.),
         };
 
         // Draw the background, highlighting bad cells as needed
@@ -134,8 +318,8 @@ impl EditorGrids {
             if c >= last_known_solve.columns.len() {
                 continue;
             }
-            let potential_row_patterns = last_known_solve.rows[r].len();
-            let potential_column_patterns = last_known_solve.columns[c].len();
+            let potential_row_patterns = last_known_solve.rows[r].layouts;
+            let potential_column_patterns = last_known_solve.columns[c].layouts;
             let color = match (potential_row_patterns, potential_column_patterns) {
                 (1, 1) => Color::GREEN,
                 (0, 0) => Color::RED,
@@ -154,7 +338,7 @@ impl EditorGrids {
             if r >= last_known_solve.rows.len() || c > 0 {
                 continue;
             }
-            let potential_patterns = last_known_solve.rows[r].len();
+            let potential_patterns = last_known_solve.rows[r].layouts;
             if potential_patterns == 1 {
                 continue;
             }
@@ -175,7 +359,7 @@ impl EditorGrids {
             if c >= last_known_solve.columns.len() || r > 0 {
                 continue;
             }
-            let potential_patterns = last_known_solve.columns[c].len();
+            let potential_patterns = last_known_solve.columns[c].layouts;
             if potential_patterns == 1 {
                 continue;
             }
@@ -188,62 +372,301 @@ impl EditorGrids {
             );
         }
 
+        let ppm_layout = layout.shifted_by(vec2(400. + 50., 0.));
+        let hovered = |layout: &GridLayout| {
+            layout
+                .iter_cells()
+                .find(|(_, _, rect)| rect.contains(world_xy))
+                .map(|(r, c, _)| (r, c))
+        };
+        let hovered = match (hovered(&layout), hovered(&ppm_layout)) {
+            (Some(cell), _) => Some((false, cell)),
+            (None, Some(cell)) => Some((true, cell)),
+            (None, None) => None,
+        };
+        let action = self.use_tool(level_settings, input, hovered);
+        let preview = self.drag.map(|drag| {
+            let cells = drag.tool.shape(drag.from, drag.to);
+            let cells =
+                level_settings
+                    .symmetry
+                    .mirror(&cells, level_settings.height, level_settings.width);
+            (drag.on_ppm, cells)
+        });
+        let previewing = |on_ppm: bool, r: usize, c: usize| {
+            preview
+                .as_ref()
+                .is_some_and(|(on, cells)| *on == on_ppm && cells.contains(&(r, c)))
+        };
+
         for (r, c, rect) in layout.iter_cells() {
-            if rect.contains(world_xy) && left_mouse_pressed {
-                action = UiActions::LevelGridUpdated;
-                self.pbm_grid[r][c] = true;
-            }
-            if rect.contains(world_xy) && right_mouse_pressed {
-                action = UiActions::LevelGridUpdated;
-                self.pbm_grid[r][c] = false;
-            }
-            let color = if self.pbm_grid[r][c] {
-                Color::WHITE
-            } else {
-                Color::BLACK
+            // Color puzzles show the color each filled cell will be clued in
+            let color = match (self.pbm_grid[r][c], level_settings.color_puzzle) {
+                (true, true) => Color::new(self.ppm_grid[r][c]),
+                (true, false) => Color::WHITE,
+                (false, _) => Color::BLACK,
             };
 
             gfx.rect().at(rect.position).size(rect.size).color(color);
+            if previewing(false, r, c) {
+                draw_preview(gfx, rect);
+            }
+
+            // Mark where another solution disagrees with the drawing
+            if differing_cells.binary_search(&(r, c)).is_ok() {
+                gfx.rect()
+                    .at(rect.position + rect.size * 0.3)
+                    .size(rect.size * 0.4)
+                    .color(Color::new([1.0, 0.0, 1.0, 1.0]));
+            }
+
+            // While stepping through, grey out what isn't worked out yet and
+            // flash what the current step just settled
+            match replay.map(|replay| replay.cell(r, c)) {
+                Some(ReplayCell::Unknown) => {
+                    gfx.rect()
+                        .at(rect.position)
+                        .size(rect.size)
+                        .color(Color::new([0.5, 0.5, 0.5, 0.85]));
+                }
+                Some(ReplayCell::JustSettled(fade)) => {
+                    gfx.rect()
+                        .at(rect.position + rect.size * 0.1)
+                        .size(rect.size * 0.8)
+                        .color(Color::new([1.0, 0.8, 0.0, 0.3 + 0.7 * fade]));
+                }
+                Some(ReplayCell::Known) | None => {}
+            }
         }
+        draw_symmetry_guides(gfx, layout.area, level_settings.symmetry);
 
-        let layout = layout.shifted_by(vec2(400. + 50., 0.));
+        let layout = ppm_layout;
         gfx.rect()
             .at(layout.area.position)
             .size(layout.area.size)
             .color(Color::WHITE);
 
         for (r, c, rect) in layout.iter_cells() {
-            if rect.contains(world_xy) && left_mouse_pressed {
-                self.ppm_grid[r][c] = level_settings.current_color;
-            }
-            if rect.contains(world_xy) && right_mouse_pressed {
-                self.ppm_grid[r][c] = [0.0, 0.0, 0.0, 1.0];
-            }
             let rgb = self.ppm_grid[r][c];
 
             gfx.rect()
                 .at(rect.position)
                 .size(rect.size)
                 .color(Color::new(rgb));
+            if previewing(true, r, c) {
+                draw_preview(gfx, rect);
+            }
         }
+        draw_symmetry_guides(gfx, layout.area, level_settings.symmetry);
         action
     }
+
+    // Left click draws with the tool and right click draws emptiness with it.
+    // Hovered is the cell under the mouse and whether it's on the PPM grid.
+    fn use_tool(
+        &mut self,
+        level_settings: &mut LevelSettings,
+        input: &Input,
+        hovered: Option<(bool, (usize, usize))>,
+    ) -> UiActions {
+        let left = input.mouse_pressed(MouseButton::Left) || input.mouse_held(MouseButton::Left);
+        let right = input.mouse_pressed(MouseButton::Right) || input.mouse_held(MouseButton::Right);
+        let just_pressed =
+            input.mouse_pressed(MouseButton::Left) || input.mouse_pressed(MouseButton::Right);
+
+        if let Some(drag) = self.drag.as_mut() {
+            if left || right {
+                // Shapes stay on the grid they were started on
+                if let Some((on_ppm, cell)) = hovered
+                    && on_ppm == drag.on_ppm
+                {
+                    drag.to = cell;
+                }
+                return UiActions::Nothing;
+            }
+            let ShapeDrag {
+                tool,
+                on_ppm,
+                from,
+                to,
+                erase,
+            } = *drag;
+            self.drag = None;
+            let cells = tool.shape(from, to);
+            return self.draw(level_settings, on_ppm, Drawing::Cells(cells), erase);
+        }
+
+        let Some((on_ppm, cell)) = hovered else {
+            return UiActions::Nothing;
+        };
+        if !left && !right {
+            return UiActions::Nothing;
+        }
+        let erase = !left;
+        match level_settings.tool {
+            Tool::Pencil => self.draw(level_settings, on_ppm, Drawing::Cells(vec![cell]), erase),
+            Tool::Fill if just_pressed => {
+                self.draw(level_settings, on_ppm, Drawing::FillFrom(vec![cell]), erase)
+            }
+            Tool::Eyedropper if on_ppm && !erase => {
+                let (r, c) = cell;
+                level_settings.current_color = self.ppm_grid[r][c];
+                UiActions::Nothing
+            }
+            tool if tool.is_shape() && just_pressed => {
+                self.drag = Some(ShapeDrag {
+                    tool,
+                    on_ppm,
+                    from: cell,
+                    to: cell,
+                    erase,
+                });
+                UiActions::Nothing
+            }
+            _ => UiActions::Nothing,
+        }
+    }
+
+    fn draw(
+        &mut self,
+        level_settings: &LevelSettings,
+        on_ppm: bool,
+        drawing: Drawing,
+        erase: bool,
+    ) -> UiActions {
+        let rows = level_settings.height;
+        let columns = level_settings.width;
+        let drawing = drawing.mirrored(level_settings.symmetry, rows, columns);
+        let changed = if on_ppm {
+            let color = if erase {
+                BLANK
+            } else {
+                level_settings.current_color
+            };
+            let mut canvas = LevelArea {
+                grid: &mut self.ppm_grid,
+                rows,
+                columns,
+            };
+            // The image is where a color puzzle's clues get their colors from
+            drawing.apply(&mut canvas, color) && level_settings.color_puzzle
+        } else {
+            let mut canvas = LevelArea {
+                grid: &mut self.pbm_grid,
+                rows,
+                columns,
+            };
+            drawing.apply(&mut canvas, !erase)
+        };
+        match changed {
+            true => UiActions::LevelGridUpdated,
+            false => UiActions::Nothing,
+        }
+    }
+}
+
+fn paste<T: Copy>(onto: &mut [Vec<T>], grid: &[Vec<T>], top: usize, left: usize) {
+    for (r, row) in grid.iter().enumerate() {
+        onto[top + r][left..left + row.len()].copy_from_slice(row);
+    }
+}
+
+// Adds blank cells to the top, left, bottom and right
+fn pad<T: Copy>(grid: &mut Vec<Vec<T>>, padding: (usize, usize, usize, usize), blank: T) {
+    let (top, left, bottom, right) = padding;
+    for row in grid.iter_mut() {
+        row.splice(0..0, std::iter::repeat_n(blank, left));
+        row.extend(std::iter::repeat_n(blank, right));
+    }
+    let columns = grid[0].len();
+    grid.splice(0..0, std::iter::repeat_n(vec![blank; columns], top));
+    grid.extend(std::iter::repeat_n(vec![blank; columns], bottom));
+}
+
+fn cut<T: Copy>(
+    grid: &[Vec<T>],
+    origin: (usize, usize),
+    rows: usize,
+    columns: usize,
+) -> Vec<Vec<T>> {
+    let (top, left) = origin;
+    grid[top..top + rows]
+        .iter()
+        .map(|row| row[left..left + columns].to_vec())
+        .collect()
+}
+
+// Where a shape will go once the mouse lets go
+fn draw_preview(gfx: &mut egor::render::Graphics, rect: Rect) {
+    gfx.rect()
+        .at(rect.position + rect.size * 0.25)
+        .size(rect.size * 0.5)
+        .color(Color::new([0.0, 0.6, 1.0, 0.8]));
+}
+
+// Lines through the middle that drawing gets mirrored across
+fn draw_symmetry_guides(gfx: &mut egor::render::Graphics, area: Rect, symmetry: Symmetry) {
+    let middle = area.position + area.size * 0.5;
+    let color = Color::new([0.0, 0.6, 1.0, 0.6]);
+    let (across, down) = match symmetry {
+        Symmetry::Off => (false, false),
+        Symmetry::Horizontal => (false, true),
+        Symmetry::Vertical => (true, false),
+        Symmetry::Both => (true, true),
+        Symmetry::Rotational => {
+            gfx.rect()
+                .at(middle - Vec2::splat(4.))
+                .size(Vec2::splat(8.))
+                .color(color);
+            (false, false)
+        }
+    };
+    if down {
+        gfx.rect()
+            .at(vec2(middle.x - 1., area.position.y))
+            .size(vec2(2., area.size.y))
+            .color(color);
+    }
+    if across {
+        gfx.rect()
+            .at(vec2(area.position.x, middle.y - 1.))
+            .size(vec2(area.size.x, 2.))
+            .color(color);
+    }
 }
 
 pub fn save_grid_as_level(level_settings: &LevelSettings, grids: &EditorGrids) -> Level {
     let ppm = (level_settings, grids).into();
-    let pbm = (level_settings, grids).into();
+    let pbm: Pbm = (level_settings, grids).into();
+    let kind: PuzzleKind = (level_settings, grids).into();
+    // Saving refuses palettes that are too big anyway, so don't bother rating them
+    let difficulty = match kind.palette() {
+        Some(palette) if palette.len() > MAX_COLORS => None,
+        _ => Some(trace_solve(&kind.play_state(&pbm)).rating()),
+    };
     let base = base_dir();
     let path: PathBuf = ["levels", &level_settings.filename].iter().collect();
     let path = base.join(path);
     Level {
         info: pbm,
         image: ppm,
+        kind,
         completed: false,
+        stats: LevelStats::default(),
+        difficulty,
         path: path.with_extension("level"),
     }
 }
 
+// Where the editor leaves a design it had to close on without asking about
+// saving. It's kept out of the levels folder so the game doesn't list it.
+pub fn recovery_level(level_settings: &LevelSettings, grids: &EditorGrids) -> Level {
+    let mut level = save_grid_as_level(level_settings, grids);
+    let path: PathBuf = ["recovered", &level_settings.filename].iter().collect();
+    level.path = base_dir().join(path).with_extension("level");
+    level
+}
+
 fn percent_to_u16(t: f32, max_value: f32) -> u16 {
     (t.clamp(0.0, 1.0) * max_value).round() as u16
 }
@@ -296,3 +719,142 @@ impl From<(&LevelSettings, &EditorGrids)> for Pbm {
         }
     }
 }
+
+// Color puzzles take their clue colors straight from the reward image, with
+// one palette entry for each distinct color found under a filled cell.
+impl From<(&LevelSettings, &EditorGrids)> for PuzzleKind {
+    fn from(tuple: (&LevelSettings, &EditorGrids)) -> PuzzleKind {
+        let (level_settings, _) = tuple;
+        if !level_settings.color_puzzle {
+            return PuzzleKind::Monochrome;
+        }
+        let pbm: Pbm = tuple.into();
+        let ppm: Ppm = tuple.into();
+
+        let mut palette: Vec<[u16; 3]> = vec![];
+        let cells = pbm
+            .cells
+            .iter()
+            .zip(&ppm.cells)
+            .map(|(&filled, &color)| {
+                if !filled {
+                    return 0;
+                }
+                let index = match palette.iter().position(|&known| known == color) {
+                    Some(index) => index,
+                    None => {
+                        palette.push(color);
+                        palette.len() - 1
+                    }
+                };
+                index as u16 + 1
+            })
+            .collect();
+
+        let mask = Pgm {
+            width: pbm.width,
+            height: pbm.height,
+            max_value: palette.len() as u16,
+            cells,
+        };
+        PuzzleKind::Color { mask, palette }
+    }
+}
+
+#[cfg(test)]
+mod editor_grids_tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    fn filled_cells(grids: &EditorGrids) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for (r, row) in grids.pbm_grid.iter().enumerate() {
+            for (c, &filled) in row.iter().enumerate() {
+                if filled {
+                    cells.push((r, c));
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn recovered_designs_stay_out_of_the_levels_folder() {
+        let level_settings = LevelSettings {
+            filename: "castle".to_owned(),
+            ..LevelSettings::default()
+        };
+        let grids = EditorGrids::default();
+        let saved = save_grid_as_level(&level_settings, &grids);
+        let recovered = recovery_level(&level_settings, &grids);
+        assert_eq!(base_dir().join("recovered/castle.level"), recovered.path);
+        assert_ne!(saved.path.parent(), recovered.path.parent());
+        assert_eq!(saved.info.cells, recovered.info.cells);
+    }
+
+    #[test]
+    fn resizing_crops_and_pads_away_from_the_anchor() {
+        let mut level_settings = LevelSettings::default();
+        let mut grids = EditorGrids::default();
+        grids.pbm_grid[0][0] = true;
+        grids.pbm_grid[9][9] = true;
+        grids.ppm_grid[9][9] = RED;
+
+        level_settings.resize_anchor = Anchor::BottomRight;
+        grids.resize(&mut level_settings, 6, 8);
+        assert_eq!((6, 8), (level_settings.width, level_settings.height));
+        assert_eq!(8, grids.pbm_grid.len());
+        assert_eq!(6, grids.ppm_grid[0].len());
+        assert_eq!(vec![(7, 5)], filled_cells(&grids));
+        assert_eq!(RED, grids.ppm_grid[7][5]);
+
+        // Growing around the middle pads both sides, without the cut off cells
+        level_settings.resize_anchor = Anchor::Center;
+        grids.resize(&mut level_settings, 10, 12);
+        assert_eq!(vec![(9, 7)], filled_cells(&grids));
+        assert_eq!(BLANK, grids.ppm_grid[0][0]);
+
+        // A step at a time around the middle ends up the same as all at once
+        let mut stepped = grids.clone();
+        let mut stepped_settings = LevelSettings {
+            width: 10,
+            height: 12,
+            resize_anchor: Anchor::Center,
+            ..LevelSettings::default()
+        };
+        for size in 11..=16 {
+            stepped.resize(&mut stepped_settings, size, size);
+        }
+        grids.resize(&mut level_settings, 16, 16);
+        assert_eq!(grids.pbm_grid, stepped.pbm_grid);
+        assert_eq!(vec![(11, 10)], filled_cells(&grids));
+    }
+
+    #[test]
+    fn hidden_cells_come_back_when_kept() {
+        let mut level_settings = LevelSettings {
+            keep_hidden_cells: true,
+            resize_anchor: Anchor::Center,
+            ..LevelSettings::default()
+        };
+        let mut grids = EditorGrids::default();
+        grids.pbm_grid[0][0] = true;
+        grids.pbm_grid[9][9] = true;
+        grids.ppm_grid[0][0] = RED;
+
+        grids.resize(&mut level_settings, 5, 5);
+        assert!(filled_cells(&grids).is_empty());
+        // Drawing while small ends up in the right place afterwards too
+        grids.pbm_grid[2][2] = true;
+        grids.resize(&mut level_settings, 12, 12);
+        assert_eq!(vec![(1, 1), (6, 6), (10, 10)], filled_cells(&grids));
+        assert_eq!(RED, grids.ppm_grid[1][1]);
+
+        // Shrinking without keeping them clears them for good
+        level_settings.keep_hidden_cells = false;
+        grids.resize(&mut level_settings, 6, 6);
+        grids.resize(&mut level_settings, 12, 12);
+        assert_eq!(vec![(6, 6)], filled_cells(&grids));
+    }
+}
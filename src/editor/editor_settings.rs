@@ -1,9 +1,20 @@
 use egor::app::egui::Rgba;
-use egor::app::{egui::Slider, egui::TextEdit, egui::Ui};
+use egor::app::{egui::Button, egui::ComboBox, egui::Slider, egui::TextEdit, egui::Ui};
 
+use crate::clues::ClueFormat;
+use crate::editor::drawing_tools::{Symmetry, Tool};
+use crate::editor::editor_grids::Anchor;
 use crate::editor::editor_ui_actions::UiActions;
-use crate::levels::Level;
+use crate::editor::mask_from_image::MaskRule;
+use crate::editor::transforms::Transform;
+use crate::levels::{Level, PuzzleKind};
 
+// The smallest a level can be made in the editor
+pub const MIN_SIZE: usize = 5;
+// Past this the cells get too small to click on
+pub const MAX_SIZE: usize = 200;
+
+#[derive(Clone)]
 pub struct LevelSettings {
     pub width: usize,
     pub height: usize,
@@ -11,6 +22,14 @@ pub struct LevelSettings {
     pub current_color: [f32; 4],
     pub palette: Vec<[f32; 4]>,
     pub max_colors: usize,
+    pub color_puzzle: bool,
+    pub tool: Tool,
+    pub symmetry: Symmetry,
+    pub resize_anchor: Anchor,
+    pub keep_hidden_cells: bool,
+    // For tracing the PPM grid into the PBM one
+    pub mask_threshold: f32,
+    pub mask_colors: Vec<[f32; 4]>,
 }
 
 impl Default for LevelSettings {
@@ -22,6 +41,13 @@ impl Default for LevelSettings {
             current_color: [0., 0., 0., 1.0],
             palette: vec![[0., 0., 0., 1.0]],
             max_colors: 12,
+            color_puzzle: false,
+            tool: Tool::default(),
+            symmetry: Symmetry::default(),
+            resize_anchor: Anchor::default(),
+            keep_hidden_cells: false,
+            mask_threshold: 0.5,
+            mask_colors: Vec::new(),
         }
     }
 }
@@ -35,6 +61,18 @@ impl LevelSettings {
             .unwrap_or(String::from("unknown"));
         self.width = level.info.width;
         self.height = level.info.height;
+        self.color_puzzle = matches!(level.kind, PuzzleKind::Color { .. });
+    }
+
+    // Back to a blank level, leaving how the editor is set up alone
+    pub fn new_level(&mut self) {
+        let blank = LevelSettings::default();
+        self.width = blank.width;
+        self.height = blank.height;
+        self.filename = blank.filename;
+        self.palette = blank.palette;
+        self.color_puzzle = blank.color_puzzle;
+        self.mask_colors.clear();
     }
 
     pub fn ui(&mut self, ui: &mut Ui) -> UiActions {
@@ -43,12 +81,108 @@ impl LevelSettings {
         ui.label("Left click to apply changes to the grids, right click to remove");
         ui.label("The PBM grid defines the cells to fill for the puzzle");
         ui.label("The PPM grid defines the pixel art reward. ");
+        ui.label("Ctrl+Z undoes a whole stroke at a time, Ctrl+Shift+Z redoes it");
+        ui.horizontal(|ui| {
+            if ui.button("Undo").clicked() {
+                result = UiActions::Undo;
+            }
+            if ui.button("Redo").clicked() {
+                result = UiActions::Redo;
+            }
+        });
         ui.separator();
-        ui.add(Slider::new(&mut self.width, 5..=20).text("Level Width"));
-        ui.add(Slider::new(&mut self.height, 5..=20).text("Level Height"));
+        // The grids resize to match, so the sliders only ask for it
+        let (mut width, mut height) = (self.width, self.height);
+        let too_big = format!("Past {} cells they get too small to draw on", MAX_SIZE);
+        let width_changed = ui
+            .add(Slider::new(&mut width, MIN_SIZE..=MAX_SIZE).text("Level Width"))
+            .on_hover_text(&too_big)
+            .changed();
+        let height_changed = ui
+            .add(Slider::new(&mut height, MIN_SIZE..=MAX_SIZE).text("Level Height"))
+            .on_hover_text(&too_big)
+            .changed();
+        if width_changed || height_changed {
+            result = UiActions::Resize { width, height };
+        }
+        ui.horizontal(|ui| {
+            ComboBox::from_label("stays put when resizing")
+                .selected_text(self.resize_anchor.to_string())
+                .show_ui(ui, |ui| {
+                    for anchor in Anchor::ALL {
+                        ui.selectable_value(&mut self.resize_anchor, anchor, anchor.to_string());
+                    }
+                });
+        });
+        ui.checkbox(
+            &mut self.keep_hidden_cells,
+            "Keep cells cut off by shrinking",
+        )
+        .on_hover_text("Growing the level again brings them back, otherwise they're cleared");
+        if ui
+            .checkbox(&mut self.color_puzzle, "Color puzzle")
+            .on_hover_text("Clues take their colors from the PPM grid")
+            .changed()
+        {
+            result = UiActions::LevelGridUpdated;
+        }
 
         ui.separator();
 
+        ui.label("Tool: ");
+        ui.horizontal_wrapped(|ui| {
+            for tool in Tool::ALL {
+                ui.selectable_value(&mut self.tool, tool, tool.to_string());
+            }
+        });
+        ui.label("Shapes are drawn once you let go, right click draws them empty");
+
+        ui.label("Symmetry: ");
+        ui.horizontal_wrapped(|ui| {
+            for symmetry in Symmetry::ALL {
+                ui.selectable_value(&mut self.symmetry, symmetry, symmetry.to_string());
+            }
+        });
+
+        ui.label("Transform both grids: ");
+        ui.horizontal_wrapped(|ui| {
+            for transform in Transform::ALL {
+                if ui.button(transform.to_string()).clicked() {
+                    result = UiActions::Transform(transform);
+                }
+            }
+        });
+
+        ui.label("Trace the PBM from the PPM: ");
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("Everything but the background").clicked() {
+                result = UiActions::MaskFromImage(MaskRule::NotBackground);
+            }
+            if ui
+                .add_enabled(
+                    !self.mask_colors.is_empty(),
+                    Button::new("Ticked palette colors"),
+                )
+                .clicked()
+            {
+                result = UiActions::MaskFromImage(MaskRule::Colors(self.mask_colors.clone()));
+            }
+        });
+        ui.horizontal(|ui| {
+            // Retraces as the slider moves, so the solver keeps up while tuning it
+            let slider = Slider::new(&mut self.mask_threshold, 0.0..=1.0).text("Brightness");
+            let moved = ui.add(slider).changed();
+            if ui.button("Brighter cells").clicked() || moved {
+                result = UiActions::MaskFromImage(MaskRule::BrighterThan(self.mask_threshold));
+            }
+        });
+        if ui
+            .button("Paint the PBM onto the PPM in the current color")
+            .clicked()
+        {
+            result = UiActions::ColorImageFromMask;
+        }
+
         ui.label("Color: ");
         let previous_color = self.current_color.clone();
         ui.color_edit_button_rgba_unmultiplied(&mut self.current_color);
@@ -69,6 +203,17 @@ impl LevelSettings {
             if ui.button("Open").clicked() {
                 result = UiActions::OpenLevel;
             }
+            if ui.button("New").clicked() {
+                result = UiActions::NewLevel;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Export clues as");
+            for format in ClueFormat::ALL {
+                if ui.button(format.to_string()).clicked() {
+                    result = UiActions::ExportClues(format);
+                }
+            }
         });
 
         ui.separator();
@@ -83,13 +228,22 @@ impl LevelSettings {
                         self.current_color = color.clone();
                         result = UiActions::RecomputePalette;
                     }
-                })
+                });
+                let mut in_mask = self.mask_colors.contains(color);
+                if ui.checkbox(&mut in_mask, "In mask").changed() {
+                    match in_mask {
+                        true => self.mask_colors.push(*color),
+                        false => self.mask_colors.retain(|known| known != color),
+                    }
+                }
             });
         }
         result
     }
 
     pub fn refresh_palette_with(&mut self, unique_colors: Vec<[f32; 4]>) {
+        self.mask_colors
+            .retain(|color| unique_colors.contains(color));
         self.palette = unique_colors;
     }
 }
@@ -1,3 +1,10 @@
+use crate::clues::ClueFormat;
+use crate::editor::editor_grids::EditorGrids;
+use crate::editor::editor_settings::LevelSettings;
+use crate::editor::mask_from_image::MaskRule;
+use crate::editor::solver_display::SolveOutcome;
+use crate::editor::transforms::Transform;
+use crate::playstate::PlayState;
 use std::path::PathBuf;
 
 pub enum UiActions {
@@ -5,7 +12,18 @@ pub enum UiActions {
     SaveLevel,
     RecomputePalette,
     OpenLevel,
+    NewLevel,
     LevelGridUpdated,
+    FindRepairs,
+    ToggleCells(Vec<(usize, usize)>),
+    StartReplay,
+    ExportClues(ClueFormat),
+    Undo,
+    Redo,
+    Transform(Transform),
+    Resize { width: usize, height: usize },
+    MaskFromImage(MaskRule),
+    ColorImageFromMask,
 }
 
 pub enum IOWorkerRequest {
@@ -16,3 +34,28 @@ pub enum IOWorkerRequest {
 pub enum IOWorkerResponse {
     IoOpenChoice(PathBuf),
 }
+
+pub enum SolverWorkerRequest {
+    // Jobs count up, so a newer one makes any older one not worth finishing
+    Solve {
+        job: u64,
+        play_state: Box<PlayState>,
+        too_many_colors: bool,
+    },
+    // For the drawing the job of the same number was for, so a newer solve
+    // makes it not worth finishing either
+    FindRepairs {
+        job: u64,
+        level_settings: Box<LevelSettings>,
+        grids: Box<EditorGrids>,
+    },
+    Shutdown,
+}
+
+pub enum SolverWorkerResponse {
+    Solved(SolveOutcome),
+    Repairs {
+        job: u64,
+        repairs: Vec<Vec<(usize, usize)>>,
+    },
+}
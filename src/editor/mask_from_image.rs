@@ -0,0 +1,165 @@
+use crate::editor::editor_grids::EditorGrids;
+use crate::editor::editor_settings::LevelSettings;
+
+// Which cells of the PPM image get filled in when tracing it into the PBM
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaskRule {
+    // Anything that isn't the background, taken to be the most common color
+    // around the edges of the picture
+    NotBackground,
+    // Brighter cells are filled, same as they show up white on the PBM grid
+    BrighterThan(f32),
+    Colors(Vec<[f32; 4]>),
+}
+
+// How bright a color looks, 0 to 1, going by the Rec. 709 weights
+pub fn luminance(color: [f32; 4]) -> f32 {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
+
+// Redraws the whole PBM grid from the PPM one. Returns whether anything changed.
+pub fn mask_from_image(
+    rule: &MaskRule,
+    level_settings: &LevelSettings,
+    grids: &mut EditorGrids,
+) -> bool {
+    let rows = level_settings.height;
+    let columns = level_settings.width;
+    let background = edge_color(&grids.ppm_grid, rows, columns);
+    let mut changed = false;
+    for r in 0..rows {
+        for c in 0..columns {
+            let color = grids.ppm_grid[r][c];
+            let filled = match rule {
+                MaskRule::NotBackground => Some(color) != background,
+                MaskRule::BrighterThan(threshold) => luminance(color) > *threshold,
+                MaskRule::Colors(colors) => colors.contains(&color),
+            };
+            changed |= grids.pbm_grid[r][c] != filled;
+            grids.pbm_grid[r][c] = filled;
+        }
+    }
+    changed
+}
+
+// The other way around, paints every filled cell of the PBM onto the PPM in
+// one color and leaves the rest of the picture be. Returns whether anything
+// changed.
+pub fn color_image_from_mask(
+    color: [f32; 4],
+    level_settings: &LevelSettings,
+    grids: &mut EditorGrids,
+) -> bool {
+    let mut changed = false;
+    for r in 0..level_settings.height {
+        for c in 0..level_settings.width {
+            if grids.pbm_grid[r][c] && grids.ppm_grid[r][c] != color {
+                grids.ppm_grid[r][c] = color;
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+// The most common color along the level's border, first one found on a tie
+fn edge_color(ppm_grid: &[Vec<[f32; 4]>], rows: usize, columns: usize) -> Option<[f32; 4]> {
+    let mut counts: Vec<([f32; 4], usize)> = Vec::new();
+    for (r, row) in ppm_grid.iter().enumerate().take(rows) {
+        for (c, &color) in row.iter().enumerate().take(columns) {
+            if r != 0 && c != 0 && r != rows - 1 && c != columns - 1 {
+                continue;
+            }
+            match counts.iter_mut().find(|(known, _)| *known == color) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((color, 1)),
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|&(_, count)| count)
+        .map(|(color, _)| color)
+}
+
+#[cfg(test)]
+mod mask_from_image_tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const NAVY: [f32; 4] = [0.0, 0.0, 0.5, 1.0];
+
+    // A white picture frame, a red square in it and a navy dot in the middle
+    fn painted() -> (LevelSettings, EditorGrids) {
+        let level_settings = LevelSettings {
+            width: 7,
+            height: 7,
+            ..LevelSettings::default()
+        };
+        let mut grids = EditorGrids::new(7, 7);
+        for r in 0..7 {
+            for c in 0..7 {
+                grids.ppm_grid[r][c] = match (r, c) {
+                    (3, 3) => NAVY,
+                    (2..=4, 2..=4) => RED,
+                    _ => WHITE,
+                };
+            }
+        }
+        (level_settings, grids)
+    }
+
+    fn filled(grids: &EditorGrids) -> usize {
+        grids
+            .pbm_grid
+            .iter()
+            .flatten()
+            .filter(|&&filled| filled)
+            .count()
+    }
+
+    #[test]
+    fn the_background_is_whatever_is_around_the_edges() {
+        let (level_settings, mut grids) = painted();
+        assert!(mask_from_image(
+            &MaskRule::NotBackground,
+            &level_settings,
+            &mut grids
+        ));
+        assert_eq!(9, filled(&grids));
+        assert!(grids.pbm_grid[2][2] && grids.pbm_grid[3][3]);
+        assert!(!mask_from_image(
+            &MaskRule::NotBackground,
+            &level_settings,
+            &mut grids
+        ));
+    }
+
+    #[test]
+    fn thresholds_and_colors_pick_out_parts_of_the_picture() {
+        let (level_settings, mut grids) = painted();
+        mask_from_image(&MaskRule::BrighterThan(0.5), &level_settings, &mut grids);
+        assert_eq!(40, filled(&grids));
+        assert!(!grids.pbm_grid[2][2] && grids.pbm_grid[0][0]);
+        mask_from_image(&MaskRule::BrighterThan(0.1), &level_settings, &mut grids);
+        assert_eq!(48, filled(&grids));
+
+        let rule = MaskRule::Colors(vec![NAVY, RED]);
+        mask_from_image(&rule, &level_settings, &mut grids);
+        assert_eq!(9, filled(&grids));
+    }
+
+    #[test]
+    fn the_mask_colors_the_image_back_in() {
+        let (level_settings, mut grids) = painted();
+        grids.pbm_grid[0][0] = true;
+        grids.pbm_grid[3][3] = true;
+        assert!(color_image_from_mask(RED, &level_settings, &mut grids));
+        assert_eq!(RED, grids.ppm_grid[0][0]);
+        assert_eq!(RED, grids.ppm_grid[3][3]);
+        assert_eq!(WHITE, grids.ppm_grid[0][1]);
+        assert!(!color_image_from_mask(RED, &level_settings, &mut grids));
+    }
+}
@@ -1,41 +1,85 @@
 use crate::editor::editor_grids::EditorGrids;
 use crate::editor::editor_settings::LevelSettings;
+use crate::hints::{HintLine, known};
+use crate::levels::PuzzleKind;
 use crate::netpbm::Pbm;
-use crate::playstate::PlayState;
+use crate::playstate::{Group, PlayState};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, Not, Shr};
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum SolvedState {
     Unsolvable,
     UniqueSolution,
+    // Only one picture fits, but line by line logic alone can't find it.
+    UniqueRequiresGuessing,
     MultipleSolutions,
 }
 
 pub fn editor_to_initial_state(level_settings: &LevelSettings, grids: &EditorGrids) -> PlayState {
     let pbm: Pbm = (level_settings, grids).into();
-    (&pbm).into()
+    let kind: PuzzleKind = (level_settings, grids).into();
+    kind.play_state(&pbm)
 }
 
-type LinePattern = u32;
-const MAX_BITS: usize = 32; // this should match to LinePattern. Always!
+// The cells of a line as bits, left aligned so the first cell is the highest
+// bit. Any unsigned integer will do, the line just has to fit in it.
+pub trait LineBits:
+    Copy
+    + Ord
+    + std::fmt::Debug
+    + std::fmt::Binary
+    + BitAnd<Output = Self>
+    + BitAndAssign
+    + BitOr<Output = Self>
+    + BitOrAssign
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shr<usize, Output = Self>
+{
+    const BITS: usize;
+    const ZERO: Self;
+    const ONE: Self;
+    const MAX: Self;
+}
+
+macro_rules! line_bits_for {
+    ($($bits:ty),*) => {
+        $(
+            impl LineBits for $bits {
+                const BITS: usize = <$bits>::BITS as usize;
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+                const MAX: Self = <$bits>::MAX;
+            }
+        )*
+    };
+}
+
+line_bits_for!(u32, u64, u128);
 
-pub fn bitblock_of(size: usize, at: usize) -> LinePattern {
-    assert!(at < MAX_BITS);
-    let one_bit_on_the_left = u32::MAX ^ (u32::MAX >> 1);
-    let mut base_pattern = 0;
+// What the enumerating solver stores its line patterns as, so it only takes
+// lines up to 128 cells. The line solver goes a cell at a time instead and
+// takes lines of any length.
+pub type LinePattern = u128;
+
+pub fn bitblock_of<B: LineBits>(size: usize, at: usize) -> B {
+    assert!(at < B::BITS);
+    let one_bit_on_the_left = B::MAX ^ (B::MAX >> 1);
+    let mut base_pattern = B::ZERO;
     for _ in 0..size {
         base_pattern = (base_pattern >> 1) | one_bit_on_the_left;
     }
-    assert!(size + at - 1 < MAX_BITS);
+    assert!(size + at - 1 < B::BITS);
     base_pattern = base_pattern >> at;
     base_pattern
 }
 
 // Remaining space is how much inclaimed space lays before our army of bits eagerly ready
 // to conquer and claim the electric homes of their permutious multitude.
-pub fn generate_line_pattern(remaining_space: usize, groups: &[usize]) -> Vec<LinePattern> {
+pub fn generate_line_pattern<B: LineBits>(remaining_space: usize, groups: &[usize]) -> Vec<B> {
     // If no groups remain, the line is empty so it can be filled as desired by the caller
     if groups.is_empty() {
-        return vec![0];
+        return vec![B::ZERO];
     }
 
     // If there is no space, no places exist for the bits to make their home.
@@ -53,7 +97,7 @@ pub fn generate_line_pattern(remaining_space: usize, groups: &[usize]) -> Vec<Li
 
     let size_of_first_group = *group;
     if size_of_first_group == 0 {
-        return vec![0];
+        return vec![B::ZERO];
     }
 
     if size_of_first_group == remaining_space {
@@ -70,7 +114,7 @@ pub fn generate_line_pattern(remaining_space: usize, groups: &[usize]) -> Vec<Li
     };
 
     // Construct left-aligned block for the first group
-    let base_pattern = bitblock_of(size_of_first_group, 0);
+    let base_pattern: B = bitblock_of(size_of_first_group, 0);
     let mut patterns = Vec::new();
 
     // How far we can shift the first group
@@ -91,7 +135,7 @@ pub fn generate_line_pattern(remaining_space: usize, groups: &[usize]) -> Vec<Li
             .saturating_sub(size_of_first_group)
             .saturating_sub(if others.is_empty() { 0 } else { 1 });
 
-        let other_patterns = generate_line_pattern(remaining_after_group, others);
+        let other_patterns: Vec<B> = generate_line_pattern(remaining_after_group, others);
         let shift = inset + size_of_first_group + 1;
         for other in other_patterns {
             patterns.push(pattern | (other >> shift));
@@ -101,68 +145,567 @@ pub fn generate_line_pattern(remaining_space: usize, groups: &[usize]) -> Vec<Li
     patterns
 }
 
-#[derive(Debug)]
+pub fn is_filled_at<B: LineBits>(pattern: B, idx: usize) -> bool {
+    (pattern >> (B::BITS - 1 - idx)) & B::ONE == B::ONE
+}
+
+// Color puzzles keep one LinePattern per palette color, so the planes are
+// indexed by color - 1 since 0 is an empty cell. Monochrome lines are just
+// a single plane with everything painted in color 1.
+pub const MAX_COLORS: usize = 8;
+pub type ColorPlanes = [LinePattern; MAX_COLORS];
+
+// Which color is painted at the idx'th cell, or 0 if it's empty
+pub fn color_at(planes: &ColorPlanes, idx: usize) -> usize {
+    planes
+        .iter()
+        .position(|&plane| is_filled_at(plane, idx))
+        .map_or(0, |plane| plane + 1)
+}
+
+// A run of the same color needs a gap before the next one to tell them apart,
+// but two different colors can sit right up against each other.
+fn gap_between(color: usize, next: Option<&(usize, usize)>) -> usize {
+    match next {
+        Some(&(_, next_color)) if next_color == color => 1,
+        _ => 0,
+    }
+}
+
+fn space_needed(groups: &[(usize, usize)]) -> usize {
+    groups
+        .iter()
+        .enumerate()
+        .map(|(i, &(size, color))| size + gap_between(color, groups.get(i + 1)))
+        .sum()
+}
+
+// Same idea as generate_line_pattern, but each group is a (size, color) pair.
+pub fn generate_colored_line_pattern(
+    line_length: usize,
+    groups: &[(usize, usize)],
+) -> Vec<ColorPlanes> {
+    let groups: Vec<(usize, usize)> = groups
+        .iter()
+        .copied()
+        .filter(|&(size, _)| size > 0)
+        .collect();
+    let mut patterns = Vec::new();
+    place_colored_groups(line_length, &groups, 0, [0; MAX_COLORS], &mut patterns);
+    patterns
+}
+
+fn place_colored_groups(
+    line_length: usize,
+    groups: &[(usize, usize)],
+    start: usize,
+    placed: ColorPlanes,
+    patterns: &mut Vec<ColorPlanes>,
+) {
+    let Some((&(size, color), others)) = groups.split_first() else {
+        patterns.push(placed);
+        return;
+    };
+    assert!(
+        (1..=MAX_COLORS).contains(&color),
+        "color {} is outside the palette",
+        color
+    );
+
+    let gap = gap_between(color, others.first());
+    let Some(last_start) = line_length.checked_sub(size + gap + space_needed(others)) else {
+        return;
+    };
+    for at in start..=last_start {
+        let mut planes = placed;
+        planes[color - 1] |= bitblock_of::<LinePattern>(size, at);
+        place_colored_groups(line_length, others, at + size + gap, planes, patterns);
+    }
+}
+
+// Every way the clues can be laid out across the line that doesn't disagree
+// with what we already know. None in known means we don't know that cell yet,
+// otherwise it's the color there (0 for empty).
+pub fn patterns_agreeing_with(
+    known: &[Option<usize>],
+    groups: &[(usize, usize)],
+) -> Vec<ColorPlanes> {
+    let mut patterns = generate_colored_line_pattern(known.len(), groups);
+    patterns.retain(|planes| {
+        known.iter().enumerate().all(|(idx, cell)| match cell {
+            Some(color) => color_at(planes, idx) == *color,
+            None => true,
+        })
+    });
+    patterns
+}
+
+// For each cell of the line, which colors any of the patterns put there as a
+// bit set. Bit 0 is empty, bit n is color n.
+pub fn possible_colors(patterns: &[ColorPlanes], line_length: usize) -> Vec<u32> {
+    // A line with no way to lay out its clues can't tell us anything
+    if patterns.is_empty() {
+        return vec![u32::MAX; line_length];
+    }
+    let mut any_empty = 0;
+    let mut any_painted = [0; MAX_COLORS];
+    for planes in patterns {
+        any_empty |= !filled_cells(planes);
+        for (painted, &plane) in any_painted.iter_mut().zip(planes) {
+            *painted |= plane;
+        }
+    }
+
+    (0..line_length)
+        .map(|idx| {
+            let mut colors = u32::from(is_filled_at(any_empty, idx));
+            for (plane, &painted) in any_painted.iter().enumerate() {
+                if is_filled_at(painted, idx) {
+                    colors |= 1 << (plane + 1);
+                }
+            }
+            colors
+        })
+        .collect()
+}
+
+// For each cell of the line the color every pattern agrees upon, if they do.
+pub fn agreed_colors(patterns: &[ColorPlanes], line_length: usize) -> Vec<Option<usize>> {
+    possible_colors(patterns, line_length)
+        .into_iter()
+        .map(|colors| (colors.count_ones() == 1).then_some(colors.trailing_zeros() as usize))
+        .collect()
+}
+
+fn filled_cells(planes: &ColorPlanes) -> LinePattern {
+    planes.iter().fold(0, |filled, &plane| filled | plane)
+}
+
+fn line_groups(groups: &[Group]) -> Vec<(usize, usize)> {
+    groups
+        .iter()
+        .filter(|g| g.num_cells > 0)
+        .map(|g| (g.num_cells, g.color))
+        .collect()
+}
+
+// What's left of a line once its clues are laid out every way they can go.
+// Cells are bit sets of the colors they could still be, same as possible_colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineLayouts {
+    // Saturates instead of overflowing on very long lines, which still reads
+    // as more than one layout.
+    pub count: u128,
+    pub cells: Vec<u32>,
+}
+
+// Works out the same thing as possible_colors(patterns_agreeing_with(..))
+// without making every pattern. Instead it counts how many ways the clues
+// can fill the line up to each cell from the left and from the right, and a
+// clue can sit somewhere only if both sides of it can still be filled in.
+// That's length * clues work rather than however many patterns there are.
+pub fn solve_line(known: &[u32], clues: &[(usize, usize)]) -> LineLayouts {
+    let length = known.len();
+    let clues: Vec<(usize, usize)> = clues
+        .iter()
+        .copied()
+        .filter(|&(size, _)| size > 0)
+        .collect();
+    let num_clues = clues.len();
+    // Each clue along with the gap it needs before the next one
+    let blocks: Vec<usize> = clues
+        .iter()
+        .enumerate()
+        .map(|(j, &(size, color))| size + gap_between(color, clues.get(j + 1)))
+        .collect();
+    let can_be_empty = |idx: usize| known[idx] & 1 == 1;
+
+    // fits[j][at] is whether the j'th clue and its gap can start at cell `at`
+    let fits: Vec<Vec<bool>> = clues
+        .iter()
+        .zip(&blocks)
+        .map(|(&(size, color), &block)| {
+            assert!(
+                (1..=MAX_COLORS).contains(&color),
+                "color {} is outside the palette",
+                color
+            );
+            // How many cells in a row from each one could be this color
+            let mut run = vec![0; length + 1];
+            for idx in (0..length).rev() {
+                if (known[idx] >> color) & 1 == 1 {
+                    run[idx] = run[idx + 1] + 1;
+                }
+            }
+            (0..length)
+                .map(|at| {
+                    at + block <= length
+                        && run[at] >= size
+                        && (block == size || can_be_empty(at + size))
+                })
+                .collect()
+        })
+        .collect();
+
+    let index = |at: usize, j: usize| at * (num_clues + 1) + j;
+
+    // before[(at, j)] is how many ways the first j clues fill the cells before at
+    let mut before = vec![0u128; (length + 1) * (num_clues + 1)];
+    before[index(0, 0)] = 1;
+    for at in 0..length {
+        for j in 0..=num_clues {
+            let ways = before[index(at, j)];
+            if ways == 0 {
+                continue;
+            }
+            if can_be_empty(at) {
+                let next = index(at + 1, j);
+                before[next] = before[next].saturating_add(ways);
+            }
+            if j < num_clues && fits[j][at] {
+                let next = index(at + blocks[j], j + 1);
+                before[next] = before[next].saturating_add(ways);
+            }
+        }
+    }
+
+    // after[(at, j)] is how many ways the clues from j on fill the cells from at on
+    let mut after = vec![0u128; (length + 1) * (num_clues + 1)];
+    after[index(length, num_clues)] = 1;
+    for at in (0..length).rev() {
+        for j in 0..=num_clues {
+            let mut ways = 0;
+            if can_be_empty(at) {
+                ways = after[index(at + 1, j)];
+            }
+            if j < num_clues && fits[j][at] {
+                ways = ways.saturating_add(after[index(at + blocks[j], j + 1)]);
+            }
+            after[index(at, j)] = ways;
+        }
+    }
+
+    let count = after[index(0, 0)];
+    // Nothing fits, so there's nothing to learn about the cells either
+    if count == 0 {
+        return LineLayouts {
+            count,
+            cells: known.to_vec(),
+        };
+    }
+
+    let mut cells = vec![0; length];
+    for (at, cell) in cells.iter_mut().enumerate() {
+        let empty_here =
+            (0..=num_clues).any(|j| before[index(at, j)] > 0 && after[index(at + 1, j)] > 0);
+        if can_be_empty(at) && empty_here {
+            *cell |= 1;
+        }
+    }
+    for (j, &(size, color)) in clues.iter().enumerate() {
+        // +1 where a placement of this clue starts covering cells, -1 where it stops
+        let mut covered = vec![0isize; length + 1];
+        for at in 0..length {
+            let placed =
+                before[index(at, j)] > 0 && fits[j][at] && after[index(at + blocks[j], j + 1)] > 0;
+            if !placed {
+                continue;
+            }
+            covered[at] += 1;
+            covered[at + size] -= 1;
+            if blocks[j] > size {
+                cells[at + size] |= 1;
+            }
+        }
+        let mut placements = 0;
+        for (cell, change) in cells.iter_mut().zip(covered) {
+            placements += change;
+            if placements > 0 {
+                *cell |= 1 << color;
+            }
+        }
+    }
+
+    LineLayouts { count, cells }
+}
+
+// Any line with no way to lay out its clues makes the whole puzzle unsolvable,
+// otherwise every line needs exactly one to be unique.
+fn solved_state(layouts: impl IntoIterator<Item = u128>) -> SolvedState {
+    let mut state = SolvedState::UniqueSolution;
+    for count in layouts {
+        match count {
+            0 => return SolvedState::Unsolvable,
+            1 => {}
+            _ => state = SolvedState::MultipleSolutions,
+        }
+    }
+    state
+}
+
+#[derive(Debug, Clone)]
+pub struct PossibleLine {
+    clues: Vec<(usize, usize)>,
+    // How many ways the clues can still be laid out along this line
+    pub layouts: u128,
+    // What each cell could still be, as far as this line knows
+    pub cells: Vec<u32>,
+}
+
+impl PossibleLine {
+    fn new(length: usize, groups: &[Group]) -> Self {
+        let clues = line_groups(groups);
+        let solved = solve_line(&vec![u32::MAX; length], &clues);
+        Self {
+            clues,
+            layouts: solved.count,
+            cells: solved.cells,
+        }
+    }
+
+    fn assume(&mut self, idx: usize, colors: u32) {
+        let mut known = self.cells.clone();
+        known[idx] &= colors;
+        self.narrow(&known);
+    }
+
+    fn narrow(&mut self, known: &[u32]) {
+        let solved = solve_line(known, &self.clues);
+        self.layouts = solved.count;
+        self.cells = solved.cells;
+    }
+}
+
+// Why some row or column was left with no way to lay out its clues
+#[derive(Debug, Clone, PartialEq)]
+pub enum Contradiction {
+    // The clues need more cells than the line has
+    CluesTooLong(HintLine),
+    // The cells given for the line don't fit its clues however they're laid out
+    GivenCells(HintLine),
+    // What the crossing lines worked out doesn't leave the clues anywhere to go
+    Crossing {
+        line: HintLine,
+        crossing: Vec<HintLine>,
+    },
+}
+
+impl Contradiction {
+    pub fn line(&self) -> HintLine {
+        match self {
+            Contradiction::CluesTooLong(line) => *line,
+            Contradiction::GivenCells(line) => *line,
+            Contradiction::Crossing { line, .. } => *line,
+        }
+    }
+}
+
+// Rows and columns are counted from 1 for people reading them
+pub(crate) fn describe_line(line: HintLine) -> String {
+    match line {
+        HintLine::Row(r) => format!("row {}", r + 1),
+        HintLine::Column(c) => format!("column {}", c + 1),
+    }
+}
+
+impl std::fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Contradiction::CluesTooLong(line) => {
+                write!(f, "the clues for {} don't fit in it", describe_line(*line))
+            }
+            Contradiction::GivenCells(line) => write!(
+                f,
+                "the cells given in {} don't match its clues",
+                describe_line(*line)
+            ),
+            Contradiction::Crossing { line, crossing } => {
+                let crossing: Vec<String> = crossing.iter().map(|&l| describe_line(l)).collect();
+                write!(
+                    f,
+                    "{} can't fit its clues around what {} worked out",
+                    describe_line(*line),
+                    crossing.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Contradiction {}
+
+// What searching found, row by row grids of the color in each cell (0 for empty)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    // Stops counting at the limit given to search
+    pub solutions: usize,
+    // The first two solutions found, if there were that many
+    pub witnesses: Vec<Vec<usize>>,
+}
+
+impl SearchResult {
+    // What the solutions found say about a puzzle the lines got stuck on
+    pub fn stuck_state(&self) -> SolvedState {
+        match self.solutions {
+            0 => SolvedState::Unsolvable,
+            1 => SolvedState::UniqueRequiresGuessing,
+            _ => SolvedState::MultipleSolutions,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct TheMultiVerseOfLines {
-    pub rows: Vec<Vec<LinePattern>>,
-    pub columns: Vec<Vec<LinePattern>>,
+    pub rows: Vec<PossibleLine>,
+    pub columns: Vec<PossibleLine>,
 }
 
 impl TheMultiVerseOfLines {
     pub fn new(play_state: &PlayState) -> Self {
-        let mut multiverse = Self {
-            rows: Vec::new(),
-            columns: Vec::new(),
+        let rows = play_state
+            .row_groups
+            .iter()
+            .map(|groups| PossibleLine::new(play_state.num_columns, groups))
+            .collect();
+        let columns = play_state
+            .column_groups
+            .iter()
+            .map(|groups| PossibleLine::new(play_state.num_rows, groups))
+            .collect();
+        Self { rows, columns }
+    }
+
+    // Starts from what's already known about the cells as well as the clues,
+    // row by row in the same bit sets solve_line uses, then works out what
+    // follows from that. Fails with the first line left with no layouts.
+    pub fn with_known(play_state: &PlayState, known: &[u32]) -> Result<Self, Contradiction> {
+        let mut multiverse = Self::new(play_state);
+        let given = |line: HintLine, length: usize| -> Vec<u32> {
+            (0..length)
+                .map(|idx| {
+                    let (r, c) = line.cell(idx);
+                    known[r * play_state.num_columns + c]
+                })
+                .collect()
         };
-        for r in 0..play_state.num_rows {
-            let row_groups: Vec<usize> = play_state.row_groups[r]
-                .iter()
-                .map(|g| g.num_cells)
-                .collect();
-            let row_patterns = generate_line_pattern(play_state.num_columns, &row_groups);
-            multiverse.rows.push(row_patterns);
+        let lines = multiverse
+            .rows
+            .iter_mut()
+            .enumerate()
+            .map(|(r, row)| (HintLine::Row(r), row))
+            .chain(
+                multiverse
+                    .columns
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(c, column)| (HintLine::Column(c), column)),
+            );
+        for (line, possible) in lines {
+            if possible.layouts == 0 {
+                return Err(Contradiction::CluesTooLong(line));
+            }
+            possible.narrow(&given(line, possible.cells.len()));
+            if possible.layouts == 0 {
+                return Err(Contradiction::GivenCells(line));
+            }
         }
-        for c in 0..play_state.num_columns {
-            let column_groups: Vec<usize> = play_state.column_groups[c]
-                .iter()
-                .map(|g| g.num_cells)
-                .collect();
-            let column_patterns = generate_line_pattern(play_state.num_rows, &column_groups);
-            multiverse.columns.push(column_patterns);
+
+        // Same order as collapse, but checking each line as it goes so we
+        // know which one ran out first
+        loop {
+            let mut changed = false;
+            for line in (0..multiverse.columns.len()).map(HintLine::Column) {
+                changed |= multiverse.collapse_line(line);
+                multiverse.check_line(line, &given)?;
+            }
+            if !changed {
+                for line in (0..multiverse.rows.len()).map(HintLine::Row) {
+                    changed |= multiverse.collapse_line(line);
+                    multiverse.check_line(line, &given)?;
+                }
+            }
+            if !changed {
+                return Ok(multiverse);
+            }
         }
-        multiverse
     }
 
-    // known_filled = 1's where 1s are in row.
-    // known_empty = 1's where 0s are in row
-    pub fn get_assured_row_cells(&self, row_idx: usize) -> (LinePattern, LinePattern) {
-        Self::assured_cells(&self.rows[row_idx])
+    // Picks up from the player's progress, where anything they've ruled out
+    // could be a mistake
+    pub fn from_progress(play_state: &PlayState) -> Result<Self, Contradiction> {
+        let known: Vec<u32> = play_state
+            .rows()
+            .iter()
+            .enumerate()
+            .flat_map(|(r, cells)| {
+                cells
+                    .iter()
+                    .enumerate()
+                    .map(move |(c, &cell)| known(cell, play_state.goal_color(r, c)))
+            })
+            .collect();
+        Self::with_known(play_state, &known)
     }
 
-    pub fn get_assured_column_cells(&self, column_idx: usize) -> (LinePattern, LinePattern) {
-        Self::assured_cells(&self.columns[column_idx])
+    // When a line has nothing left, blame the crossing lines that narrowed it
+    // down past what its own clues and given cells allow
+    fn check_line(
+        &self,
+        line: HintLine,
+        given: &impl Fn(HintLine, usize) -> Vec<u32>,
+    ) -> Result<(), Contradiction> {
+        let possible = match line {
+            HintLine::Row(r) => &self.rows[r],
+            HintLine::Column(c) => &self.columns[c],
+        };
+        if possible.layouts > 0 {
+            return Ok(());
+        }
+        let alone = solve_line(&given(line, possible.cells.len()), &possible.clues);
+        let crossing = possible
+            .cells
+            .iter()
+            .zip(&alone.cells)
+            .enumerate()
+            .filter(|(_, (narrowed, alone))| narrowed != alone)
+            .map(|(idx, _)| match line {
+                HintLine::Row(_) => HintLine::Column(idx),
+                HintLine::Column(_) => HintLine::Row(idx),
+            })
+            .collect();
+        Err(Contradiction::Crossing { line, crossing })
     }
 
-    fn assured_cells(patterns: &[LinePattern]) -> (LinePattern, LinePattern) {
-        let mut all_filled_together = LinePattern::MAX;
-        let mut all_empty_together = 0;
+    // known_filled = true where a cell in the row has to be filled.
+    // known_empty = true where it has to be empty
+    pub fn get_assured_row_cells(&self, row_idx: usize) -> (Vec<bool>, Vec<bool>) {
+        Self::assured_cells(&self.rows[row_idx].cells)
+    }
 
-        for &pattern in patterns {
-            all_filled_together &= pattern;
-            all_empty_together |= pattern;
-        }
+    pub fn get_assured_column_cells(&self, column_idx: usize) -> (Vec<bool>, Vec<bool>) {
+        Self::assured_cells(&self.columns[column_idx].cells)
+    }
 
-        let must_be_filled = all_filled_together;
-        let must_be_empty = !all_empty_together;
+    // Filled here means filled with any color at all.
+    fn assured_cells(cells: &[u32]) -> (Vec<bool>, Vec<bool>) {
+        let must_be_filled = cells.iter().map(|&colors| colors & 1 == 0).collect();
+        let must_be_empty = cells.iter().map(|&colors| colors == 1).collect();
         (must_be_filled, must_be_empty)
     }
 
     pub fn collapse(&mut self) -> usize {
+        self.collapse_traced(&mut |_, _| {})
+    }
+
+    // Same as collapse, but tells solved about every line it learns something
+    // new about along with what that line knows about its cells afterwards.
+    pub fn collapse_traced(&mut self, solved: &mut impl FnMut(HintLine, &[u32])) -> usize {
         let mut iterations = 0;
         loop {
             iterations += 1;
             let mut changed = false;
-            changed = changed || self.collapse_rows();
-            changed = changed || self.collapse_columns();
+            changed = changed || self.collapse_rows(solved);
+            changed = changed || self.collapse_columns(solved);
             if !changed {
                 break;
             }
@@ -170,98 +713,355 @@ impl TheMultiVerseOfLines {
         iterations
     }
 
-    fn collapse_rows(&mut self) -> bool {
-        Self::collapse_lines(&self.rows, &mut self.columns)
+    fn collapse_rows(&mut self, solved: &mut impl FnMut(HintLine, &[u32])) -> bool {
+        Self::collapse_lines(&self.rows, &mut self.columns, &mut |c, cells| {
+            solved(HintLine::Column(c), cells)
+        })
     }
 
-    fn collapse_columns(&mut self) -> bool {
-        Self::collapse_lines(&self.columns, &mut self.rows)
+    fn collapse_columns(&mut self, solved: &mut impl FnMut(HintLine, &[u32])) -> bool {
+        Self::collapse_lines(&self.columns, &mut self.rows, &mut |r, cells| {
+            solved(HintLine::Row(r), cells)
+        })
     }
 
-    fn collapse_lines(lines: &[Vec<LinePattern>], patterns: &mut [Vec<LinePattern>]) -> bool {
+    fn collapse_lines(
+        lines: &[PossibleLine],
+        crossing: &mut [PossibleLine],
+        learned: &mut impl FnMut(usize, &[u32]),
+    ) -> bool {
         let mut changed = false;
-        for l in 0..lines.len() {
-            let (must_be_filled, must_be_empty) = Self::assured_cells(&lines[l]);
-            for p in 0..patterns.len() {
-                let valid_patterns = patterns[p].len();
-                // This is no longer row and bit, but just source and target sorta, or line and pattern idx
-                let line_idx = MAX_BITS - 1 - l;
-                let pattern_idx = MAX_BITS - 1 - p;
-                patterns[p].retain(|&pattern| {
-                    if (must_be_filled >> pattern_idx) & 1 == 1 {
-                        let fill_agree =
-                            bit_agreed_at(must_be_filled, pattern, pattern_idx, line_idx);
-                        if !fill_agree {
-                            return false;
-                        }
-                    }
-                    if (must_be_empty >> pattern_idx) & 1 == 1 {
-                        let empty_agree =
-                            bit_agreed_at(must_be_empty, !pattern, pattern_idx, line_idx);
-                        if !empty_agree {
-                            return false;
-                        }
-                    }
-                    true
-                });
-                changed = changed || valid_patterns != patterns[p].len();
+        for (p, crossing_line) in crossing.iter_mut().enumerate() {
+            if Self::narrow_line(lines, crossing_line, p) {
+                changed = true;
+                learned(p, &crossing_line.cells);
             }
         }
         changed
     }
 
+    // Narrows down a single line with what the lines crossing it know, for
+    // going through a collapse one line at a time. Returns whether it learned
+    // anything.
+    pub fn collapse_line(&mut self, line: HintLine) -> bool {
+        match line {
+            HintLine::Row(r) => Self::narrow_line(&self.columns, &mut self.rows[r], r),
+            HintLine::Column(c) => Self::narrow_line(&self.rows, &mut self.columns[c], c),
+        }
+    }
+
+    fn narrow_line(crossing: &[PossibleLine], line: &mut PossibleLine, idx: usize) -> bool {
+        // Whatever a line can't put in a cell, the crossing line can't
+        // either. For monochrome puzzles that's the usual must be filled
+        // or must be empty, colors can also rule out just some of them.
+        let known: Vec<u32> = line
+            .cells
+            .iter()
+            .zip(crossing)
+            .map(|(&colors, other)| colors & other.cells[idx])
+            .collect();
+        if known == line.cells {
+            return false;
+        }
+        let solved = solve_line(&known, &line.clues);
+        let changed = solved.count != line.layouts || solved.cells != line.cells;
+        line.layouts = solved.count;
+        line.cells = solved.cells;
+        changed
+    }
+
+    // What line by line logic makes of the puzzle. If we collapsed and found
+    // no options, there is a contradiction. But if each has a unique pattern
+    // left, then it is solveable with only 1 way! Anything else means the
+    // logic got stuck, which may or may not be down to more than one solution.
+    pub fn line_state(&self) -> SolvedState {
+        solved_state(self.rows.iter().chain(&self.columns).map(|l| l.layouts))
+    }
+
+    // Same as line_state, except when the lines get stuck a search works out
+    // whether that's because guesswork is needed or there really is more than
+    // one picture. We only want line solveable puzzles, but knowing which
+    // helps figure out how to fix one.
     pub fn state(&self) -> SolvedState {
-        // If we collapsed and found no options, there is a contradiction
-        // or guesswork required, so fail it because we only want line solveable
-        // puzzles!
-        if self.rows.iter().any(|p| p.is_empty()) {
-            return SolvedState::Unsolvable;
+        match self.line_state() {
+            SolvedState::MultipleSolutions => self.search(2).stuck_state(),
+            state => state,
+        }
+    }
+
+    // One of the solutions that isn't the picture, if there's more than one
+    pub fn alternative_solution(&self, picture: &[usize]) -> Option<Vec<usize>> {
+        self.search(2)
+            .witnesses
+            .into_iter()
+            .find(|witness| witness != picture)
+    }
+
+    // Counts solutions up to limit by guessing a cell whenever the lines get
+    // stuck, collapsing again, and backing out of guesses that end up in a
+    // contradiction.
+    pub fn search(&self, limit: usize) -> SearchResult {
+        self.search_cancellable(limit, || false)
+            .expect("never cancelled")
+    }
+
+    // Same as search, except cancelled is checked before every guess and None
+    // comes back as soon as it says to give up. Big designs can take a while.
+    pub fn search_cancellable(
+        &self,
+        limit: usize,
+        mut cancelled: impl FnMut() -> bool,
+    ) -> Option<SearchResult> {
+        let mut result = SearchResult {
+            solutions: 0,
+            witnesses: Vec::new(),
+        };
+        let mut start = self.clone();
+        start.collapse();
+        start.search_from(limit, &mut result, &mut cancelled)?;
+        Some(result)
+    }
+
+    // None when cancelled
+    fn search_from(
+        &self,
+        limit: usize,
+        result: &mut SearchResult,
+        cancelled: &mut impl FnMut() -> bool,
+    ) -> Option<()> {
+        if result.solutions >= limit {
+            return Some(());
         }
-        if self.columns.iter().any(|p| p.is_empty()) {
-            return SolvedState::Unsolvable;
+        match self.line_state() {
+            SolvedState::Unsolvable => return Some(()),
+            SolvedState::UniqueSolution => {
+                result.solutions += 1;
+                if result.witnesses.len() < 2 {
+                    result.witnesses.push(self.grid());
+                }
+                return Some(());
+            }
+            _ => {}
         }
 
-        // But if each has a unique pattern left, then it is solveable with only 1 way!
-        for pattern in &self.rows {
-            if pattern.len() > 1 {
-                return SolvedState::MultipleSolutions;
+        let Some((r, c, colors)) = self.most_constrained_cell() else {
+            return Some(());
+        };
+        for color in 0..u32::BITS {
+            if (colors >> color) & 1 == 0 {
+                continue;
             }
+            if cancelled() {
+                return None;
+            }
+            let mut guess = self.clone();
+            guess.assume(r, c, 1 << color);
+            guess.collapse();
+            guess.search_from(limit, result, cancelled)?;
+        }
+        Some(())
+    }
+
+    // What the row and column crossing at a cell both allow there
+    pub fn cell_colors(&self, r: usize, c: usize) -> u32 {
+        self.rows[r].cells[c] & self.columns[c].cells[r]
+    }
+
+    // The undecided cell with the fewest colors to pick from, going for the
+    // one whose lines have the fewest layouts left when that's a tie.
+    pub fn most_constrained_cell(&self) -> Option<(usize, usize, u32)> {
+        let mut most_constrained = None;
+        let mut fewest = (u32::MAX, u128::MAX);
+        for (r, row) in self.rows.iter().enumerate() {
+            for (c, column) in self.columns.iter().enumerate() {
+                let colors = self.cell_colors(r, c);
+                if colors.count_ones() < 2 {
+                    continue;
+                }
+                let options = (colors.count_ones(), row.layouts.min(column.layouts));
+                if options < fewest {
+                    fewest = options;
+                    most_constrained = Some((r, c, colors));
+                }
+            }
+        }
+        most_constrained
+    }
+
+    // Narrows a cell down to just these colors, as if the player had worked it out
+    pub fn assume(&mut self, r: usize, c: usize, colors: u32) {
+        self.rows[r].assume(c, colors);
+        self.columns[c].assume(r, colors);
+    }
+
+    // Only makes sense once every line is down to a single layout
+    fn grid(&self) -> Vec<usize> {
+        self.rows
+            .iter()
+            .flat_map(|row| {
+                row.cells
+                    .iter()
+                    .map(|colors| colors.trailing_zeros() as usize)
+            })
+            .collect()
+    }
+}
+
+// Toggling cells in the PBM grid that makes the design uniquely line solvable,
+// a single cell where that works and otherwise pairs of them. Only cells line
+// logic can't decide are tried, since pinning those down is what's missing.
+// Every try is a whole solve, so cancelled is checked before each one and None
+// comes back if it says to give up.
+pub fn uniqueness_repairs(
+    level_settings: &LevelSettings,
+    grids: &EditorGrids,
+    limit: usize,
+    mut cancelled: impl FnMut() -> bool,
+) -> Option<Vec<Vec<(usize, usize)>>> {
+    let mut multiverse = TheMultiVerseOfLines::new(&editor_to_initial_state(level_settings, grids));
+    multiverse.collapse();
+    if multiverse.line_state() == SolvedState::UniqueSolution {
+        return Some(Vec::new());
+    }
+    let undecided: Vec<(usize, usize)> = (0..multiverse.rows.len())
+        .flat_map(|r| (0..multiverse.columns.len()).map(move |c| (r, c)))
+        .filter(|&(r, c)| multiverse.cell_colors(r, c).count_ones() > 1)
+        .collect();
+
+    let mut grids = grids.clone();
+    let mut fixes = |cells: &[(usize, usize)]| {
+        grids.toggle_cells(cells);
+        let fixed = line_solvable(level_settings, &grids);
+        grids.toggle_cells(cells);
+        fixed
+    };
+
+    let mut repairs: Vec<Vec<(usize, usize)>> = Vec::new();
+    for &cell in &undecided {
+        if repairs.len() == limit {
+            return Some(repairs);
+        }
+        if cancelled() {
+            return None;
+        }
+        if fixes(&[cell]) {
+            repairs.push(vec![cell]);
         }
-        for pattern in &self.columns {
-            if pattern.len() > 1 {
-                return SolvedState::MultipleSolutions;
+    }
+    if !repairs.is_empty() {
+        return Some(repairs);
+    }
+    // Every pair gets expensive quickly on big designs
+    let candidates = &undecided[..undecided.len().min(MAX_PAIR_CANDIDATES)];
+    for (i, &first) in candidates.iter().enumerate() {
+        for &second in &candidates[i + 1..] {
+            if repairs.len() == limit {
+                return Some(repairs);
+            }
+            if cancelled() {
+                return None;
             }
+            if fixes(&[first, second]) {
+                repairs.push(vec![first, second]);
+            }
+        }
+    }
+    Some(repairs)
+}
+
+const MAX_PAIR_CANDIDATES: usize = 32;
+
+fn line_solvable(level_settings: &LevelSettings, grids: &EditorGrids) -> bool {
+    // Filling a cell can bring in a color the palette doesn't have room for
+    let kind: PuzzleKind = (level_settings, grids).into();
+    if kind
+        .palette()
+        .is_some_and(|palette| palette.len() > MAX_COLORS)
+    {
+        return false;
+    }
+    let pbm: Pbm = (level_settings, grids).into();
+    let mut multiverse = TheMultiVerseOfLines::new(&kind.play_state(&pbm));
+    multiverse.collapse();
+    multiverse.line_state() == SolvedState::UniqueSolution
+}
+
+// The solver as it was before solve_line, keeping every pattern of every line
+// around and whittling them down. It's only here to check solve_line against
+// and to benchmark it, since lines with lots of small clues blow it up.
+pub fn collapse_by_enumeration(play_state: &PlayState) -> (SolvedState, usize) {
+    let mut rows: Vec<Vec<ColorPlanes>> = play_state
+        .row_groups
+        .iter()
+        .map(|groups| generate_colored_line_pattern(play_state.num_columns, &line_groups(groups)))
+        .collect();
+    let mut columns: Vec<Vec<ColorPlanes>> = play_state
+        .column_groups
+        .iter()
+        .map(|groups| generate_colored_line_pattern(play_state.num_rows, &line_groups(groups)))
+        .collect();
+
+    let mut iterations = 0;
+    loop {
+        iterations += 1;
+        let mut changed = false;
+        changed = changed || filter_crossing_patterns(&rows, &mut columns);
+        changed = changed || filter_crossing_patterns(&columns, &mut rows);
+        if !changed {
+            break;
+        }
+    }
+
+    let layouts = rows.iter().chain(&columns).map(|p| p.len() as u128);
+    (solved_state(layouts), iterations)
+}
+
+fn filter_crossing_patterns(lines: &[Vec<ColorPlanes>], patterns: &mut [Vec<ColorPlanes>]) -> bool {
+    let mut changed = false;
+    for (l, line) in lines.iter().enumerate() {
+        let possible = possible_colors(line, patterns.len());
+        for (p, colors) in possible.into_iter().enumerate() {
+            let valid_patterns = patterns[p].len();
+            patterns[p].retain(|planes| (colors >> color_at(planes, l)) & 1 == 1);
+            changed = changed || valid_patterns != patterns[p].len();
         }
-        SolvedState::UniqueSolution
     }
+    changed
 }
 
-pub fn bit_agreed_at(line1: LinePattern, line2: LinePattern, idx_1: usize, idx_2: usize) -> bool {
-    let bit1 = (line1 >> idx_1) & 1;
-    let bit2 = (line2 >> idx_2) & 1;
+pub fn bit_agreed_at<B: LineBits>(line1: B, line2: B, idx_1: usize, idx_2: usize) -> bool {
+    let bit1 = (line1 >> idx_1) & B::ONE;
+    let bit2 = (line2 >> idx_2) & B::ONE;
     bit1 == bit2
 }
 
 impl std::fmt::Display for TheMultiVerseOfLines {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         write!(f, "Rows:\n")?;
-        for patterns in &self.rows {
-            write!(f, "Row:\n")?;
-            for line in patterns {
-                write!(f, "{:032b}\n", line)?;
-            }
+        for line in &self.rows {
+            write_line(f, line)?;
         }
         write!(f, "Columns:\n")?;
-        for patterns in &self.columns {
-            write!(f, "Column:\n")?;
-            for line in patterns {
-                write!(f, "{:032b}\n", line)?;
-            }
+        for line in &self.columns {
+            write_line(f, line)?;
         }
         Ok(())
     }
 }
 
+// One character per cell, the color if it's certain (0 when empty) or a ?
+// if it could still be more than one thing.
+fn write_line(f: &mut std::fmt::Formatter<'_>, line: &PossibleLine) -> std::fmt::Result {
+    for &colors in &line.cells {
+        if colors.count_ones() == 1 {
+            write!(f, "{}", colors.trailing_zeros())?;
+        } else {
+            write!(f, "?")?;
+        }
+    }
+    writeln!(f, " ({} layouts)", line.layouts)
+}
+
 /* The tests are beneath here and I like having something to cleanly
    separate the code and the tests for easy scanning.
 =====================================================================
@@ -288,35 +1088,35 @@ impl std::fmt::Display for TheMultiVerseOfLines {
 mod solver_tests {
     use super::*;
 
-    fn print_patterns(patterns: &[u32]) {
+    fn print_patterns<B: LineBits>(patterns: &[B]) {
         eprintln!("Debug pattern list:");
         for pattern in patterns {
-            eprintln!("{:032b}", pattern);
+            eprintln!("{:0width$b}", pattern, width = B::BITS);
         }
     }
 
     #[test]
     fn bitblock_works_as_expected() {
-        let block = bitblock_of(1, 31);
+        let block = bitblock_of::<u32>(1, 31);
         assert_eq!(0b00000000000000000000000000000001, block);
-        let block = bitblock_of(2, 30);
+        let block = bitblock_of::<u32>(2, 30);
         assert_eq!(0b00000000000000000000000000000011, block);
-        let block = bitblock_of(2, 2);
+        let block = bitblock_of::<u32>(2, 2);
         assert_eq!(0b00110000000000000000000000000000, block);
-        let block = bitblock_of(32, 0);
+        let block = bitblock_of::<u32>(32, 0);
         assert_eq!(0b11111111111111111111111111111111, block);
     }
 
     #[should_panic]
     #[test]
     fn bitblock_panics_as_expected() {
-        bitblock_of(33, 0);
+        bitblock_of::<u32>(33, 0);
     }
 
     #[test]
     fn empty_line_handled_correctly() {
         let empty: Vec<usize> = vec![];
-        let patterns = generate_line_pattern(1, &empty);
+        let patterns = generate_line_pattern::<u32>(1, &empty);
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 1);
         assert_eq!(0, patterns[0]);
@@ -324,63 +1124,63 @@ mod solver_tests {
 
     #[test]
     fn can_gen_the_only_option_as_needed() {
-        let mut patterns = generate_line_pattern(1, &[1]);
+        let mut patterns = generate_line_pattern::<u32>(1, &[1]);
         patterns.sort();
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 1);
         // 1000...
-        assert_eq!(bitblock_of(1, 0), patterns[0]);
+        assert_eq!(bitblock_of::<u32>(1, 0), patterns[0]);
     }
 
     #[test]
     fn can_gen_a_10_and_01_type_pattern() {
-        let mut patterns = generate_line_pattern(2, &[1]);
+        let mut patterns = generate_line_pattern::<u32>(2, &[1]);
         patterns.sort();
         assert_eq!(patterns.len(), 2);
         print_patterns(&patterns);
         // 0100...
-        assert_eq!(bitblock_of(1, 1), patterns[0]);
+        assert_eq!(bitblock_of::<u32>(1, 1), patterns[0]);
         // 1000...
-        assert_eq!(bitblock_of(1, 0), patterns[1]);
+        assert_eq!(bitblock_of::<u32>(1, 0), patterns[1]);
     }
 
     #[test]
     fn can_gen_a_1_across_3_cells_pattern() {
-        let mut patterns = generate_line_pattern(3, &[1]);
+        let mut patterns = generate_line_pattern::<u32>(3, &[1]);
         patterns.sort();
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 3);
         // 001...
-        assert_eq!(bitblock_of(1, 2), patterns[0]);
+        assert_eq!(bitblock_of::<u32>(1, 2), patterns[0]);
         // 010...
-        assert_eq!(bitblock_of(1, 1), patterns[1]);
+        assert_eq!(bitblock_of::<u32>(1, 1), patterns[1]);
         // 100...
-        assert_eq!(bitblock_of(1, 0), patterns[2]);
+        assert_eq!(bitblock_of::<u32>(1, 0), patterns[2]);
     }
 
     #[test]
     fn can_gen_two_1s_in_a_3_pattern() {
-        let mut patterns = generate_line_pattern(3, &[1, 1]);
+        let mut patterns = generate_line_pattern::<u32>(3, &[1, 1]);
         patterns.sort();
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 1);
         // 101
-        let one_in_3rd_place = bitblock_of(1, 2);
-        let one_in_1st_place = bitblock_of(1, 0);
+        let one_in_3rd_place = bitblock_of::<u32>(1, 2);
+        let one_in_1st_place = bitblock_of::<u32>(1, 0);
         assert_eq!(one_in_1st_place | one_in_3rd_place, patterns[0]);
     }
 
     #[test]
     fn can_gen_two_groups_in_five_example() {
-        let mut patterns = generate_line_pattern(5, &[2, 1]);
+        let mut patterns = generate_line_pattern::<u32>(5, &[2, 1]);
         patterns.sort();
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 3);
-        let one_in_1st_place = bitblock_of(1, 0);
-        let one_in_2nd_place = bitblock_of(1, 1);
-        let one_in_3rd_place = bitblock_of(1, 2);
-        let one_in_4th_place = bitblock_of(1, 3);
-        let one_in_5th_place = bitblock_of(1, 4);
+        let one_in_1st_place = bitblock_of::<u32>(1, 0);
+        let one_in_2nd_place = bitblock_of::<u32>(1, 1);
+        let one_in_3rd_place = bitblock_of::<u32>(1, 2);
+        let one_in_4th_place = bitblock_of::<u32>(1, 3);
+        let one_in_5th_place = bitblock_of::<u32>(1, 4);
         /*  Only valid patterns are:
             11010
             11001
@@ -396,14 +1196,14 @@ mod solver_tests {
 
     #[test]
     fn can_gen_three_1s_in_a_5_pattern() {
-        let mut patterns = generate_line_pattern(5, &[1, 1, 1]);
+        let mut patterns = generate_line_pattern::<u32>(5, &[1, 1, 1]);
         patterns.sort();
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 1);
         // 101
-        let one_in_5th_place = bitblock_of(1, 4);
-        let one_in_3rd_place = bitblock_of(1, 2);
-        let one_in_1st_place = bitblock_of(1, 0);
+        let one_in_5th_place = bitblock_of::<u32>(1, 4);
+        let one_in_3rd_place = bitblock_of::<u32>(1, 2);
+        let one_in_1st_place = bitblock_of::<u32>(1, 0);
         assert_eq!(
             one_in_1st_place | one_in_3rd_place | one_in_5th_place,
             patterns[0]
@@ -412,15 +1212,15 @@ mod solver_tests {
 
     #[test]
     fn can_gen_two_2s_in_a_5_pattern() {
-        let mut patterns = generate_line_pattern(5, &[2, 2]);
+        let mut patterns = generate_line_pattern::<u32>(5, &[2, 2]);
         patterns.sort();
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 1);
         // 101
-        let one_in_1st_place = bitblock_of(1, 0);
-        let one_in_2nd_place = bitblock_of(1, 1);
-        let one_in_4th_place = bitblock_of(1, 3);
-        let one_in_5th_place = bitblock_of(1, 4);
+        let one_in_1st_place = bitblock_of::<u32>(1, 0);
+        let one_in_2nd_place = bitblock_of::<u32>(1, 1);
+        let one_in_4th_place = bitblock_of::<u32>(1, 3);
+        let one_in_5th_place = bitblock_of::<u32>(1, 4);
         assert_eq!(
             one_in_1st_place | one_in_2nd_place | one_in_4th_place | one_in_5th_place,
             patterns[0]
@@ -429,13 +1229,13 @@ mod solver_tests {
 
     #[test]
     fn can_gen_two_2s_in_a_6_pattern() {
-        let mut patterns = generate_line_pattern(6, &[2, 2]);
+        let mut patterns = generate_line_pattern::<u32>(6, &[2, 2]);
         patterns.sort();
         print_patterns(&patterns);
         assert_eq!(patterns.len(), 3);
         // 101
-        let first = bitblock_of(2, 0);
-        let second = bitblock_of(2, 3);
+        let first = bitblock_of::<u32>(2, 0);
+        let second = bitblock_of::<u32>(2, 3);
         assert_eq!(first >> 1 | second >> 1, patterns[0]);
         assert_eq!(first | second >> 1, patterns[1]);
         assert_eq!(first | second, patterns[2]);
@@ -444,27 +1244,27 @@ mod solver_tests {
     #[test]
     fn confirm_number_of_compositions_aligns_to_formula_test() {
         //(25 - 2 + 1) choose 1 aka
-        let patterns = generate_line_pattern(25, &[1]);
+        let patterns = generate_line_pattern::<u32>(25, &[1]);
         assert_eq!(patterns.len(), 25);
 
         //(25 - 2 + 1) choose 2 aka
-        let patterns = generate_line_pattern(25, &[1, 1]);
+        let patterns = generate_line_pattern::<u32>(25, &[1, 1]);
         assert_eq!(patterns.len(), 276);
 
         //(25 - 3 + 1) choose 3 aka
-        let patterns = generate_line_pattern(25, &[1, 1, 1]);
+        let patterns = generate_line_pattern::<u32>(25, &[1, 1, 1]);
         assert_eq!(patterns.len(), 1771);
 
         //(25 - 4 + 1) choose 3 aka
-        let patterns = generate_line_pattern(25, &[1, 2, 1]);
+        let patterns = generate_line_pattern::<u32>(25, &[1, 2, 1]);
         assert_eq!(patterns.len(), 1540);
     }
 
     #[test]
     fn bit_agreed_at_works_as_expected() {
-        assert!(bit_agreed_at(1, 1, 0, 0));
-        let three_on_left_offset_one = bitblock_of(3, 1);
-        let three_on_left_offset_two = bitblock_of(3, 2);
+        assert!(bit_agreed_at(1u32, 1, 0, 0));
+        let three_on_left_offset_one = bitblock_of::<u32>(3, 1);
+        let three_on_left_offset_two = bitblock_of::<u32>(3, 2);
         // zero
         assert!(bit_agreed_at(
             three_on_left_offset_one,
@@ -510,18 +1310,18 @@ mod solver_tests {
         let multiverse = TheMultiVerseOfLines::new(&tps);
         eprintln!("{multiverse}");
         // the possibilites for the cells decrease as we go down
-        assert_eq!(5, multiverse.rows[0].len());
-        assert_eq!(4, multiverse.rows[1].len());
-        assert_eq!(3, multiverse.rows[2].len());
-        assert_eq!(2, multiverse.rows[3].len());
-        assert_eq!(1, multiverse.rows[4].len());
+        assert_eq!(5, multiverse.rows[0].layouts);
+        assert_eq!(4, multiverse.rows[1].layouts);
+        assert_eq!(3, multiverse.rows[2].layouts);
+        assert_eq!(2, multiverse.rows[3].layouts);
+        assert_eq!(1, multiverse.rows[4].layouts);
 
         // the possibilites for the columns increase as we go to the right
-        assert_eq!(1, multiverse.columns[0].len());
-        assert_eq!(2, multiverse.columns[1].len());
-        assert_eq!(3, multiverse.columns[2].len());
-        assert_eq!(4, multiverse.columns[3].len());
-        assert_eq!(5, multiverse.columns[4].len());
+        assert_eq!(1, multiverse.columns[0].layouts);
+        assert_eq!(2, multiverse.columns[1].layouts);
+        assert_eq!(3, multiverse.columns[2].layouts);
+        assert_eq!(4, multiverse.columns[3].layouts);
+        assert_eq!(5, multiverse.columns[4].layouts);
     }
 
     #[test]
@@ -529,12 +1329,9 @@ mod solver_tests {
         let tps = test_play_state();
         let multiverse = TheMultiVerseOfLines::new(&tps);
         let potential = multiverse.get_assured_row_cells(2);
-        let must_fill = bitblock_of(1, 2);
-        let empty_first_5 = LinePattern::MAX >> 5;
-        // For debugging when fail:
-        print_patterns(&[must_fill, potential.0, potential.1]);
+        let must_fill = vec![false, false, true, false, false];
         assert_eq!(must_fill, potential.0);
-        assert_eq!(empty_first_5, potential.1);
+        assert_eq!(vec![false; 5], potential.1);
     }
 
     #[test]
@@ -542,12 +1339,9 @@ mod solver_tests {
         let tps = test_play_state();
         let multiverse = TheMultiVerseOfLines::new(&tps);
         let potential = multiverse.get_assured_column_cells(2);
-        let must_fill = bitblock_of(1, 2);
-        let empty_first_5 = LinePattern::MAX >> 5;
-        // For debugging when fail:
-        print_patterns(&[must_fill, potential.0, potential.1]);
+        let must_fill = vec![false, false, true, false, false];
         assert_eq!(must_fill, potential.0);
-        assert_eq!(empty_first_5, potential.1);
+        assert_eq!(vec![false; 5], potential.1);
     }
 
     #[test]
@@ -555,12 +1349,8 @@ mod solver_tests {
         let tps = test_play_state();
         let multiverse = TheMultiVerseOfLines::new(&tps);
         let potential = multiverse.get_assured_row_cells(0);
-        let must_fill = 0;
-        let empty_first_5 = LinePattern::MAX >> 5;
-        // For debugging when fail:
-        print_patterns(&[must_fill, potential.0, potential.1]);
-        assert_eq!(must_fill, potential.0);
-        assert_eq!(empty_first_5, potential.1);
+        assert_eq!(vec![false; 5], potential.0);
+        assert_eq!(vec![false; 5], potential.1);
     }
 
     #[test]
@@ -580,6 +1370,57 @@ mod solver_tests {
         let mut multiverse = TheMultiVerseOfLines::new(&tps);
         multiverse.collapse();
         assert_eq!(SolvedState::MultipleSolutions, multiverse.state());
+
+        let found = multiverse.search(10);
+        assert_eq!(2, found.solutions);
+        assert!(found.witnesses.contains(&vec![1, 0, 0, 1]));
+        assert!(found.witnesses.contains(&vec![0, 1, 1, 0]));
+
+        // and it stops looking once it's found enough
+        assert_eq!(1, multiverse.search(1).solutions);
+
+        // or as soon as it's told to, checking before every guess
+        let mut checks = 0;
+        let gave_up = multiverse.search_cancellable(10, || {
+            checks += 1;
+            checks > 1
+        });
+        assert_eq!(None, gave_up);
+        assert_eq!(2, checks);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn unique_puzzles_that_need_a_guess_are_told_apart() {
+        let picture = vec![
+            0, 0, 1, 1, 0,
+            0, 1, 1, 0, 1,
+            1, 1, 0, 0, 0,
+            0, 1, 0, 0, 1,
+            0, 0, 0, 0, 1,
+        ];
+        let pbm = Pbm {
+            width: 5,
+            height: 5,
+            cells: picture.iter().map(|&cell| cell == 1).collect(),
+        };
+        let tps: PlayState = (&pbm).into();
+        let mut multiverse = TheMultiVerseOfLines::new(&tps);
+        multiverse.collapse();
+        assert_eq!(SolvedState::MultipleSolutions, multiverse.line_state());
+        assert_eq!(SolvedState::UniqueRequiresGuessing, multiverse.state());
+
+        let found = multiverse.search(10);
+        assert_eq!(1, found.solutions);
+        assert_eq!(vec![picture], found.witnesses);
+    }
+
+    #[test]
+    fn line_solvable_puzzles_need_no_searching() {
+        let tps = test_play_state();
+        let found = TheMultiVerseOfLines::new(&tps).search(10);
+        assert_eq!(1, found.solutions);
+        assert_eq!(vec![tps.picture().to_vec()], found.witnesses);
     }
 
     #[test]
@@ -590,4 +1431,422 @@ mod solver_tests {
         eprintln!("{multiverse}");
         assert_eq!(SolvedState::UniqueSolution, multiverse.state());
     }
+
+    fn colors_of(planes: &ColorPlanes, length: usize) -> Vec<usize> {
+        (0..length).map(|idx| color_at(planes, idx)).collect()
+    }
+
+    #[test]
+    fn different_colors_can_touch() {
+        let patterns = generate_colored_line_pattern(3, &[(1, 1), (2, 2)]);
+        assert_eq!(1, patterns.len());
+        assert_eq!(vec![1, 2, 2], colors_of(&patterns[0], 3));
+
+        // but the same color still needs its gap
+        let patterns = generate_colored_line_pattern(4, &[(1, 1), (1, 1)]);
+        assert_eq!(3, patterns.len());
+        let patterns = generate_colored_line_pattern(4, &[(1, 1), (1, 2)]);
+        assert_eq!(6, patterns.len());
+    }
+
+    #[test]
+    fn agreed_colors_finds_overlap_per_color() {
+        // 2 red then 1 blue in 4: the second cell is always red
+        let patterns = generate_colored_line_pattern(4, &[(2, 1), (1, 2)]);
+        let agreed = agreed_colors(&patterns, 4);
+        assert_eq!(vec![None, Some(1), None, None], agreed);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn colors_can_make_a_guessing_puzzle_unique() {
+        use crate::netpbm::Pgm;
+        // The same diagonal as requires_guessing_multi_solution, but the two
+        // cells are different colors so there's only one way to place them.
+        let mask = Pgm {
+            width: 2,
+            height: 2,
+            max_value: 2,
+            cells: vec![
+                1, 0,
+                0, 2,
+            ],
+        };
+        let tps = PlayState::painted(&mask, vec![[1., 0., 0., 1.], [0., 0., 1., 1.]]);
+        let mut multiverse = TheMultiVerseOfLines::new(&tps);
+        multiverse.collapse();
+        eprintln!("{multiverse}");
+        assert_eq!(SolvedState::UniqueSolution, multiverse.state());
+    }
+
+    #[test]
+    fn bitblocks_come_in_any_width() {
+        assert_eq!(1u64, bitblock_of(1, 63));
+        assert_eq!(0b11u128 << 27, bitblock_of(2, 99));
+        assert!(is_filled_at(bitblock_of::<u128>(1, 100), 100));
+    }
+
+    #[test]
+    fn lines_wider_than_any_bit_pattern_can_be_solved() {
+        let width = 300;
+        let mut cells = vec![true; width];
+        cells.extend(vec![false; width]);
+        let pbm = Pbm {
+            width,
+            height: 2,
+            cells,
+        };
+        let tps: PlayState = (&pbm).into();
+        let mut multiverse = TheMultiVerseOfLines::new(&tps);
+        multiverse.collapse();
+        assert_eq!(SolvedState::UniqueSolution, multiverse.state());
+        let (must_fill, must_be_empty) = multiverse.get_assured_row_cells(0);
+        assert_eq!(vec![true; width], must_fill);
+        assert_eq!(vec![true; width], multiverse.get_assured_row_cells(1).1);
+        assert_eq!(vec![false; width], must_be_empty);
+    }
+
+    #[test]
+    fn solve_line_counts_without_making_patterns() {
+        // C(21, 10) ways to fit ten 1s into 30 cells
+        let solved = solve_line(&[u32::MAX; 30], &[(1, 1); 10]);
+        assert_eq!(352716, solved.count);
+        assert!(solved.cells.iter().all(|&colors| colors == 0b11));
+
+        let solved = solve_line(&[u32::MAX; 5], &[(3, 1)]);
+        assert_eq!(3, solved.count);
+        assert_eq!(vec![0b11, 0b11, 0b10, 0b11, 0b11], solved.cells);
+
+        let solved = solve_line(&[u32::MAX; 3], &[(2, 1), (2, 1)]);
+        assert_eq!(0, solved.count);
+    }
+
+    #[test]
+    fn solve_line_agrees_with_enumerating_patterns() {
+        let length = 6;
+        let clue_sets: [&[(usize, usize)]; 5] = [
+            &[],
+            &[(2, 1), (1, 1)],
+            &[(1, 1), (2, 2)],
+            &[(1, 2), (1, 2), (1, 1)],
+            &[(3, 1), (2, 1)],
+        ];
+        // Every mix of unknown, empty, red and blue cells
+        let cell_options = [u32::MAX, 1, 1 << 1, 1 << 2];
+        for clues in clue_sets {
+            for mix in 0..cell_options.len().pow(length as u32) {
+                let known: Vec<u32> = (0..length)
+                    .map(|idx| cell_options[mix / cell_options.len().pow(idx as u32) % 4])
+                    .collect();
+                let as_options: Vec<Option<usize>> = known
+                    .iter()
+                    .map(|&colors| (colors != u32::MAX).then_some(colors.trailing_zeros() as usize))
+                    .collect();
+
+                let patterns = patterns_agreeing_with(&as_options, clues);
+                let solved = solve_line(&known, clues);
+                assert_eq!(patterns.len() as u128, solved.count, "{clues:?} {known:?}");
+                if !patterns.is_empty() {
+                    assert_eq!(possible_colors(&patterns, length), solved.cells);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn both_solvers_agree_on_shipped_levels() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("levels");
+        let levels = crate::levels::load_levels_from_dir(&dir).unwrap();
+        for level in &levels {
+            let tps: PlayState = level.into();
+            let mut multiverse = TheMultiVerseOfLines::new(&tps);
+            let iterations = multiverse.collapse();
+            assert_eq!(
+                collapse_by_enumeration(&tps),
+                (multiverse.line_state(), iterations),
+                "{}",
+                level.path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn alternative_solutions_differ_from_the_picture() {
+        let pbm = Pbm {
+            width: 2,
+            height: 2,
+            cells: vec![true, false, false, true],
+        };
+        let tps: PlayState = (&pbm).into();
+        let multiverse = TheMultiVerseOfLines::new(&tps);
+        assert_eq!(
+            Some(vec![0, 1, 1, 0]),
+            multiverse.alternative_solution(tps.picture())
+        );
+
+        let tps = test_play_state();
+        let multiverse = TheMultiVerseOfLines::new(&tps);
+        assert_eq!(None, multiverse.alternative_solution(tps.picture()));
+    }
+
+    fn diagonal_design() -> (LevelSettings, EditorGrids) {
+        let level_settings = LevelSettings {
+            width: 2,
+            height: 2,
+            ..LevelSettings::default()
+        };
+        let mut grids = EditorGrids::default();
+        grids.pbm_grid[0][0] = true;
+        grids.pbm_grid[1][1] = true;
+        (level_settings, grids)
+    }
+
+    #[test]
+    fn toggling_a_single_cell_can_make_a_design_unique() {
+        let (level_settings, mut grids) = diagonal_design();
+        let repairs = uniqueness_repairs(&level_settings, &grids, 10, || false).unwrap();
+        // Any of the four cells settles which diagonal it is
+        assert_eq!(4, repairs.len());
+        for cells in &repairs {
+            assert_eq!(1, cells.len());
+            grids.toggle_cells(cells);
+            assert!(line_solvable(&level_settings, &grids));
+            grids.toggle_cells(cells);
+        }
+        let repairs = uniqueness_repairs(&level_settings, &grids, 2, || false).unwrap();
+        assert_eq!(2, repairs.len());
+        assert!(uniqueness_repairs(&level_settings, &grids, 10, || true).is_none());
+    }
+
+    #[test]
+    fn unique_designs_need_no_repairs() {
+        let (level_settings, mut grids) = diagonal_design();
+        grids.toggle_cells(&[(0, 1)]);
+        let repairs = uniqueness_repairs(&level_settings, &grids, 10, || true);
+        assert_eq!(Some(Vec::new()), repairs);
+    }
+
+    fn identity_3x3() -> PlayState {
+        let pbm = Pbm {
+            width: 3,
+            height: 3,
+            cells: vec![true, false, false, false, true, false, false, false, true],
+        };
+        (&pbm).into()
+    }
+
+    const UNKNOWN: u32 = u32::MAX;
+    const EMPTY: u32 = 1;
+    const FILLED: u32 = 1 << 1;
+
+    #[test]
+    fn known_cells_narrow_down_the_solutions() {
+        // Any of the six ways to place three rooks fits the clues
+        let tps = identity_3x3();
+        let mut clues_only = TheMultiVerseOfLines::new(&tps);
+        clues_only.collapse();
+        assert_eq!(SolvedState::MultipleSolutions, clues_only.line_state());
+
+        #[rustfmt::skip]
+        let known = [
+            FILLED, UNKNOWN, UNKNOWN,
+            UNKNOWN, FILLED, UNKNOWN,
+            UNKNOWN, UNKNOWN, UNKNOWN,
+        ];
+        let multiverse = TheMultiVerseOfLines::with_known(&tps, &known).unwrap();
+        assert_eq!(SolvedState::UniqueSolution, multiverse.line_state());
+        assert_eq!(tps.picture(), multiverse.grid());
+    }
+
+    #[test]
+    fn contradictions_say_which_line_ran_out_and_why() {
+        let mut tps = identity_3x3();
+        #[rustfmt::skip]
+        let known = [
+            FILLED, UNKNOWN, UNKNOWN,
+            UNKNOWN, FILLED, UNKNOWN,
+            UNKNOWN, UNKNOWN, EMPTY,
+        ];
+        let contradiction = TheMultiVerseOfLines::with_known(&tps, &known).unwrap_err();
+        // Rows 1 and 2 fill the first two columns, so the last column has
+        // nowhere left for its clue
+        assert_eq!(
+            Contradiction::Crossing {
+                line: HintLine::Column(2),
+                crossing: vec![HintLine::Row(0), HintLine::Row(1)],
+            },
+            contradiction
+        );
+        assert_eq!(
+            "column 3 can't fit its clues around what row 1, row 2 worked out",
+            contradiction.to_string()
+        );
+
+        #[rustfmt::skip]
+        let known = [
+            FILLED, UNKNOWN, FILLED,
+            UNKNOWN, UNKNOWN, UNKNOWN,
+            UNKNOWN, UNKNOWN, UNKNOWN,
+        ];
+        assert_eq!(
+            Err(Contradiction::GivenCells(HintLine::Row(0))),
+            TheMultiVerseOfLines::with_known(&tps, &known).map(|_| ())
+        );
+
+        tps.row_groups[1] = vec![Group {
+            num_cells: 4,
+            filled: true,
+            color: 1,
+        }];
+        assert_eq!(
+            Err(Contradiction::CluesTooLong(HintLine::Row(1))),
+            TheMultiVerseOfLines::with_known(&tps, &[UNKNOWN; 9]).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn progress_that_went_wrong_is_caught() {
+        let mut tps = identity_3x3();
+        let multiverse = TheMultiVerseOfLines::from_progress(&tps).unwrap();
+        assert_eq!(SolvedState::MultipleSolutions, multiverse.line_state());
+
+        tps.attempt_fill(0, 0);
+        tps.attempt_fill(1, 1);
+        let multiverse = TheMultiVerseOfLines::from_progress(&tps).unwrap();
+        assert_eq!(SolvedState::UniqueSolution, multiverse.line_state());
+
+        tps.mark_cell(2, 2);
+        assert_eq!(
+            HintLine::Column(2),
+            TheMultiVerseOfLines::from_progress(&tps)
+                .unwrap_err()
+                .line()
+        );
+    }
+
+    // The tests from here down check properties over lots of randomly made
+    // lines and pictures rather than hand picked ones. They're seeded, so a
+    // failure always comes back the same way and the case number says which.
+
+    fn runs_in(pattern: u32, length: usize) -> Vec<usize> {
+        let mut runs = Vec::new();
+        let mut run = 0;
+        for idx in 0..length {
+            if is_filled_at(pattern, idx) {
+                run += 1;
+            } else if run > 0 {
+                runs.push(run);
+                run = 0;
+            }
+        }
+        if run > 0 {
+            runs.push(run);
+        }
+        runs
+    }
+
+    fn choose(n: usize, k: usize) -> usize {
+        (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+    }
+
+    #[test]
+    fn generated_line_patterns_always_give_back_their_clue() {
+        let mut rng = crate::generator::SeededRng::new(44);
+        for case in 0..500 {
+            let length = 1 + rng.below(20);
+            let mut clue = Vec::new();
+            let mut used = 0;
+            while rng.below(3) > 0 {
+                let gap = if clue.is_empty() { 0 } else { 1 };
+                if used + gap >= length {
+                    break;
+                }
+                let group = 1 + rng.below(length - used - gap);
+                used += gap + group;
+                clue.push(group);
+            }
+
+            let mut patterns = generate_line_pattern::<u32>(length, &clue);
+            for &pattern in &patterns {
+                assert_eq!(clue, runs_in(pattern, length), "case {}", case);
+                // Nothing spills past the end of the line
+                assert_eq!(0, pattern << length, "case {}", case);
+            }
+            // A clue of k groups leaves length - sum + 1 gaps to put them in
+            let filled: usize = clue.iter().sum();
+            let expected = choose(length - filled + 1, clue.len());
+            assert_eq!(
+                expected,
+                patterns.len(),
+                "case {} {:?} in {}",
+                case,
+                clue,
+                length
+            );
+            patterns.sort();
+            patterns.dedup();
+            assert_eq!(expected, patterns.len(), "case {}", case);
+        }
+    }
+
+    #[test]
+    fn random_pictures_are_always_among_the_solutions() {
+        let mut rng = crate::generator::SeededRng::new(4444);
+        for case in 0..300 {
+            let width = 1 + rng.below(15);
+            let height = 1 + rng.below(15);
+            let density = rng.next_f32();
+            // Every third picture gets a few colors
+            let colors = if case % 3 == 0 { 1 + rng.below(3) } else { 0 };
+            let mut paint = || match rng.next_f32() < density {
+                true => 1 + rng.below(colors.max(1)),
+                false => 0,
+            };
+            let tps: PlayState = if colors == 0 {
+                let cells = (0..width * height).map(|_| paint() == 1).collect();
+                (&Pbm {
+                    width,
+                    height,
+                    cells,
+                })
+                    .into()
+            } else {
+                let mask = crate::netpbm::Pgm {
+                    width,
+                    height,
+                    max_value: colors as u16,
+                    cells: (0..width * height).map(|_| paint() as u16).collect(),
+                };
+                PlayState::painted(&mask, vec![[0., 0., 0., 1.]; colors])
+            };
+            let picture = tps.picture().to_vec();
+
+            let mut multiverse = TheMultiVerseOfLines::new(&tps);
+            multiverse.collapse();
+            assert_ne!(
+                SolvedState::Unsolvable,
+                multiverse.line_state(),
+                "case {}",
+                case
+            );
+            for (idx, &color) in picture.iter().enumerate() {
+                let colors = multiverse.cell_colors(idx / width, idx % width);
+                assert_ne!(0, colors & (1 << color), "case {} cell {}", case, idx);
+                if multiverse.line_state() == SolvedState::UniqueSolution {
+                    assert_eq!(1 << color, colors, "case {} cell {}", case, idx);
+                }
+            }
+
+            // Knowing every cell up front leaves only the original
+            let known: Vec<u32> = picture.iter().map(|&color| 1 << color).collect();
+            let pinned = TheMultiVerseOfLines::with_known(&tps, &known)
+                .unwrap_or_else(|contradiction| panic!("case {}: {}", case, contradiction));
+            assert_eq!(
+                SolvedState::UniqueSolution,
+                pinned.line_state(),
+                "case {}",
+                case
+            );
+        }
+    }
 }
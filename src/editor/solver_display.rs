@@ -1,7 +1,11 @@
+use crate::editor::difficulty::{DifficultyBand, SolveTrace, trace_solve};
 use crate::editor::editor_grids::EditorGrids;
 use crate::editor::editor_settings::LevelSettings;
-use crate::editor::solver::SolvedState;
+use crate::editor::editor_ui_actions::{SolverWorkerRequest, UiActions};
 use crate::editor::solver::TheMultiVerseOfLines;
+use crate::editor::solver::{MAX_COLORS, SolvedState, editor_to_initial_state, uniqueness_repairs};
+use crate::editor::solver_replay::Replay;
+use crate::levels::PuzzleKind;
 use crate::netpbm::Pbm;
 use crate::playstate::PlayState;
 use egor::app::egui::Ui;
@@ -9,45 +13,415 @@ use egor::app::egui::Ui;
 pub struct SolverDisplay {
     pub iterations: usize,
     pub state: SolvedState,
+    pub too_many_colors: bool,
+    // Where another solution disagrees with the drawing, row by row
+    pub differing_cells: Vec<(usize, usize)>,
+    // Cells to toggle that would fix things, None until asked for
+    pub repairs: Option<Vec<Vec<(usize, usize)>>>,
+    // Asked for repairs and the solver worker hasn't sent them yet
+    pub finding_repairs: bool,
+    // Only rated when there's a single solution to rate
+    pub trace: Option<SolveTrace>,
+    // Stepping through the solve, None when not
+    pub replay: Option<Replay>,
+    // The most recent drawing sent off to be solved, anything older that comes
+    // back is thrown away
+    pub latest_job: u64,
+    // Everything above is about an older drawing until this goes false
+    pub solving: bool,
 }
 
+// What solving one drawing worked out, made on the solver worker and handed
+// back to the display
+pub struct SolveOutcome {
+    pub job: u64,
+    pub iterations: usize,
+    pub state: SolvedState,
+    pub too_many_colors: bool,
+    pub trace: Option<SolveTrace>,
+    pub differing_cells: Vec<(usize, usize)>,
+    pub possibilities: TheMultiVerseOfLines,
+}
+
+const MAX_REPAIRS: usize = 5;
+
 impl Default for SolverDisplay {
     fn default() -> Self {
         Self {
             iterations: 0,
             state: SolvedState::UniqueSolution,
+            too_many_colors: false,
+            differing_cells: Vec::new(),
+            repairs: None,
+            finding_repairs: false,
+            trace: None,
+            replay: None,
+            latest_job: 0,
+            solving: false,
         }
     }
 }
 
 impl SolverDisplay {
-    pub fn ui(&mut self, ui: &mut Ui) {
+    pub fn ui(&mut self, ui: &mut Ui) -> UiActions {
+        let mut result = UiActions::Nothing;
         ui.separator();
         ui.heading("Level Correctness");
         let state = match self.state {
             SolvedState::UniqueSolution => "Good: No guesses needed!",
             SolvedState::Unsolvable => "Bad: Unsolvable",
-            SolvedState::MultipleSolutions => "Bad: Guessing or multiple solutions found.",
+            SolvedState::UniqueRequiresGuessing => "Bad: Only one solution, but it needs guessing.",
+            SolvedState::MultipleSolutions => "Bad: Multiple solutions found.",
         };
         ui.label(state);
+        if self.solving {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Solving...");
+            });
+        }
+        if self.too_many_colors {
+            ui.label(format!(
+                "Bad: Color puzzles can use at most {} colors, checking it as monochrome.",
+                MAX_COLORS
+            ));
+        }
         ui.label(format!("Solved in {}", self.iterations));
+        if let Some(trace) = &self.trace {
+            let rating = trace.rating();
+            ui.label(format!(
+                "Difficulty: {} ({})",
+                rating,
+                DifficultyBand::of(rating)
+            ));
+            ui.label(format!(
+                "{} passes, longest chain of {}, {} contradictions, {} guesses",
+                trace.passes,
+                trace.max_chain(),
+                trace.contradictions(),
+                trace.guesses()
+            ));
+        }
+        match self.replay.as_mut() {
+            Some(replay) => {
+                if !replay.ui(ui) {
+                    self.replay = None;
+                }
+            }
+            None => {
+                if ui.button("Step through").clicked() {
+                    result = UiActions::StartReplay;
+                }
+            }
+        }
         ui.label("If multiple solutions are possible, or if there is no solution,");
         ui.label("corresponding rows and columns will be highlighted.");
         ui.label("The number shown is the number of potential solutions.");
         ui.label("If your puzzle is good, the highlight will be green.");
+        if !self.differing_cells.is_empty() {
+            ui.label(format!(
+                "Another solution differs in the {} cells marked in pink.",
+                self.differing_cells.len()
+            ));
+        }
+        if self.state != SolvedState::UniqueSolution && !self.too_many_colors {
+            if ui.button("Suggest fixes").clicked() {
+                result = UiActions::FindRepairs;
+            }
+            if self.finding_repairs {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Looking for fixes...");
+                });
+            }
+            match &self.repairs {
+                None => {}
+                Some(repairs) if repairs.is_empty() => {
+                    ui.label("No fix found by toggling one or two cells.");
+                }
+                Some(repairs) => {
+                    for cells in repairs {
+                        if ui.button(describe_repair(cells)).clicked() {
+                            result = UiActions::ToggleCells(cells.clone());
+                        }
+                    }
+                }
+            }
+        }
         ui.separator();
+        result
+    }
+
+    // What to send the solver worker to look for repairs to the drawing as it
+    // is now. Trying out every cell is a solve each, far too slow to do here.
+    pub fn request_repairs(
+        &mut self,
+        level_settings: &LevelSettings,
+        editor_grids: &EditorGrids,
+    ) -> SolverWorkerRequest {
+        self.finding_repairs = true;
+        SolverWorkerRequest::FindRepairs {
+            job: self.latest_job,
+            level_settings: Box::new(level_settings.clone()),
+            grids: Box::new(editor_grids.clone()),
+        }
+    }
+
+    // Repairs for a drawing that's since been changed are thrown away
+    pub fn receive_repairs(&mut self, job: u64, repairs: Vec<Vec<(usize, usize)>>) {
+        if job != self.latest_job {
+            return;
+        }
+        self.finding_repairs = false;
+        self.repairs = Some(repairs);
     }
 
+    pub fn start_replay(&mut self, level_settings: &LevelSettings, editor_grids: &EditorGrids) {
+        let (ps, _) = checked_play_state(level_settings, editor_grids);
+        self.replay = Some(Replay::new(&ps));
+    }
+
+    // Solves right here and now, for when there's nothing worth waiting on
     pub fn recompute(
         &mut self,
         level_settings: &LevelSettings,
         editor_grids: &EditorGrids,
     ) -> TheMultiVerseOfLines {
+        let (job, play_state, too_many_colors) = self.start_job(level_settings, editor_grids);
+        let outcome = solve_for_display(job, &play_state, too_many_colors, || false)
+            .expect("never cancelled");
+        self.receive(outcome).expect("nothing newer was asked for")
+    }
+
+    // What to send the solver worker for the drawing as it is now
+    pub fn request(
+        &mut self,
+        level_settings: &LevelSettings,
+        editor_grids: &EditorGrids,
+    ) -> SolverWorkerRequest {
+        let (job, play_state, too_many_colors) = self.start_job(level_settings, editor_grids);
+        SolverWorkerRequest::Solve {
+            job,
+            play_state: Box::new(play_state),
+            too_many_colors,
+        }
+    }
+
+    // A new job for the drawing makes any job still out there out of date.
+    // What was worked out for the old drawing stays up until the new one
+    // comes back.
+    fn start_job(
+        &mut self,
+        level_settings: &LevelSettings,
+        editor_grids: &EditorGrids,
+    ) -> (u64, PlayState, bool) {
+        let (play_state, too_many_colors) = checked_play_state(level_settings, editor_grids);
+        self.latest_job += 1;
+        self.solving = true;
+        self.repairs = None;
+        self.finding_repairs = false;
+        // The drawing it was stepping through is gone
+        self.replay = None;
+        (self.latest_job, play_state, too_many_colors)
+    }
+
+    // Returns the solve to show on the grid, or None when the outcome is for a
+    // drawing that's already been changed
+    pub fn receive(&mut self, outcome: SolveOutcome) -> Option<TheMultiVerseOfLines> {
+        if outcome.job != self.latest_job {
+            return None;
+        }
+        self.solving = false;
+        self.iterations = outcome.iterations;
+        self.state = outcome.state;
+        self.too_many_colors = outcome.too_many_colors;
+        self.trace = outcome.trace;
+        self.differing_cells = outcome.differing_cells;
+        Some(outcome.possibilities)
+    }
+}
+
+// Everything the display shows about a drawing. Searching and rating are the
+// slow parts, so cancelled is checked all through the search and before rating,
+// and None comes back if it says to give up.
+pub fn solve_for_display(
+    job: u64,
+    ps: &PlayState,
+    too_many_colors: bool,
+    mut cancelled: impl FnMut() -> bool,
+) -> Option<SolveOutcome> {
+    let mut possibilities = TheMultiVerseOfLines::new(ps);
+    let iterations = possibilities.collapse();
+    // One search says both whether there's more than one picture and what
+    // another one looks like
+    let search = match possibilities.line_state() {
+        SolvedState::MultipleSolutions => {
+            Some(possibilities.search_cancellable(2, &mut cancelled)?)
+        }
+        _ => None,
+    };
+    let state = match &search {
+        Some(found) => found.stuck_state(),
+        None => possibilities.line_state(),
+    };
+    if cancelled() {
+        return None;
+    }
+    let trace = match state {
+        SolvedState::UniqueSolution | SolvedState::UniqueRequiresGuessing => Some(trace_solve(ps)),
+        _ => None,
+    };
+    if cancelled() {
+        return None;
+    }
+    let differing_cells = match (state, search) {
+        (SolvedState::MultipleSolutions, Some(found)) => {
+            let picture = ps.picture();
+            let alternative = found.witnesses.iter().find(|witness| *witness != picture);
+            alternative
+                .iter()
+                .flat_map(|alternative| alternative.iter().zip(picture).enumerate())
+                .filter(|(_, (alternative, drawn))| alternative != drawn)
+                .map(|(idx, _)| (idx / ps.num_columns, idx % ps.num_columns))
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    Some(SolveOutcome {
+        job,
+        iterations,
+        state,
+        too_many_colors,
+        trace,
+        differing_cells,
+        possibilities,
+    })
+}
+
+// Looking for repairs on the solver worker, None if cancelled says to give up
+pub fn repairs_for_display(
+    level_settings: &LevelSettings,
+    editor_grids: &EditorGrids,
+    cancelled: impl FnMut() -> bool,
+) -> Option<Vec<Vec<(usize, usize)>>> {
+    uniqueness_repairs(level_settings, editor_grids, MAX_REPAIRS, cancelled)
+}
+
+// What the solver checks, which is only the outline when the palette is too
+// big for it, along with whether that happened
+fn checked_play_state(
+    level_settings: &LevelSettings,
+    editor_grids: &EditorGrids,
+) -> (PlayState, bool) {
+    let kind: PuzzleKind = (level_settings, editor_grids).into();
+    let too_many_colors = kind.palette().is_some_and(|p| p.len() > MAX_COLORS);
+    let ps = if too_many_colors {
         let pbm: Pbm = (level_settings, editor_grids).into();
-        let ps: PlayState = (&pbm).into();
-        let mut possibilities = TheMultiVerseOfLines::new(&ps);
-        self.iterations = possibilities.collapse();
-        self.state = possibilities.state();
-        possibilities
+        (&pbm).into()
+    } else {
+        editor_to_initial_state(level_settings, editor_grids)
+    };
+    (ps, too_many_colors)
+}
+
+// Rows and columns are counted from 1 for people reading them
+fn describe_repair(cells: &[(usize, usize)]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|(r, c)| format!("row {} column {}", r + 1, c + 1))
+        .collect();
+    format!("Toggle {}", cells.join(" and "))
+}
+
+#[cfg(test)]
+mod solver_display_tests {
+    use super::*;
+    use crate::editor::editor_grids::save_grid_as_level;
+
+    #[test]
+    fn outcomes_for_older_drawings_are_thrown_away() {
+        let level_settings = LevelSettings::default();
+        let editor_grids = EditorGrids::default();
+        let mut display = SolverDisplay::default();
+        let SolverWorkerRequest::Solve {
+            job,
+            play_state,
+            too_many_colors,
+        } = display.request(&level_settings, &editor_grids)
+        else {
+            panic!("drawings are always sent to be solved");
+        };
+        let stale = solve_for_display(job, &play_state, too_many_colors, || false).unwrap();
+        assert!(solve_for_display(job, &play_state, too_many_colors, || true).is_none());
+
+        let newer = display.request(&level_settings, &editor_grids);
+        assert!(display.receive(stale).is_none());
+        assert!(display.solving);
+        let SolverWorkerRequest::Solve {
+            job,
+            play_state,
+            too_many_colors,
+        } = newer
+        else {
+            panic!("drawings are always sent to be solved");
+        };
+        let current = solve_for_display(job, &play_state, too_many_colors, || false).unwrap();
+        assert!(display.receive(current).is_some());
+        assert!(!display.solving);
+    }
+
+    #[test]
+    fn repairs_for_older_drawings_are_thrown_away() {
+        let level_settings = LevelSettings::default();
+        let editor_grids = EditorGrids::default();
+        let mut display = SolverDisplay::default();
+        display.request(&level_settings, &editor_grids);
+        let SolverWorkerRequest::FindRepairs {
+            job,
+            level_settings: asked_settings,
+            grids,
+        } = display.request_repairs(&level_settings, &editor_grids)
+        else {
+            panic!("repairs are asked for on their own");
+        };
+        assert!(display.finding_repairs);
+        let repairs = repairs_for_display(&asked_settings, &grids, || false).unwrap();
+
+        display.request(&level_settings, &editor_grids);
+        assert!(!display.finding_repairs);
+        display.receive_repairs(job, repairs.clone());
+        assert!(display.repairs.is_none());
+
+        display.request_repairs(&level_settings, &editor_grids);
+        display.receive_repairs(display.latest_job, repairs);
+        assert!(display.repairs.is_some());
+        assert!(!display.finding_repairs);
+    }
+
+    #[test]
+    fn opening_a_level_drops_repairs_and_replay_for_the_old_design() {
+        let mut level_settings = LevelSettings::default();
+        let mut editor_grids = EditorGrids::default();
+        editor_grids.pbm_grid[0][0] = true;
+        editor_grids.pbm_grid[1][1] = true;
+        let mut display = SolverDisplay::default();
+        display.recompute(&level_settings, &editor_grids);
+        display.request_repairs(&level_settings, &editor_grids);
+        display.receive_repairs(display.latest_job, vec![vec![(0, 1)]]);
+        display.start_replay(&level_settings, &editor_grids);
+
+        let other_settings = LevelSettings {
+            width: 6,
+            height: 7,
+            ..LevelSettings::default()
+        };
+        let level = save_grid_as_level(&other_settings, &EditorGrids::new(6, 7));
+        editor_grids.load_level(&level);
+        level_settings.load_level(&level);
+        // What the editor does once a level's been opened
+        display.request(&level_settings, &editor_grids);
+        assert!(display.repairs.is_none());
+        assert!(display.replay.is_none());
+        assert!(display.solving);
     }
 }
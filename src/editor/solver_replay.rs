@@ -0,0 +1,177 @@
+use crate::editor::difficulty::{SolverStepper, Step, Technique};
+use crate::editor::solver::describe_line;
+use crate::playstate::PlayState;
+use egor::app::egui::Ui;
+
+// How long each step stays on screen while playing, in seconds
+const STEP_SECONDS: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayCell {
+    // Not worked out yet as of the step being shown
+    Unknown,
+    Known,
+    // Worked out by the step being shown, fading from 1 down to 0
+    JustSettled(f32),
+}
+
+// Walks through how the solver works a level out, one deduction at a time.
+// Steps are only worked out when they're first asked for and kept around so
+// going back doesn't mean solving again.
+pub struct Replay {
+    stepper: SolverStepper,
+    steps: Vec<Step>,
+    // How many steps are on screen, so 0 is the empty grid
+    shown: usize,
+    num_columns: usize,
+    // Which step settled each cell, row by row
+    settled_by: Vec<Option<usize>>,
+    playing: bool,
+    since_step: f32,
+}
+
+impl Replay {
+    pub fn new(play_state: &PlayState) -> Self {
+        Self {
+            stepper: SolverStepper::new(play_state),
+            steps: Vec::new(),
+            shown: 0,
+            num_columns: play_state.num_columns,
+            settled_by: vec![None; play_state.num_rows * play_state.num_columns],
+            playing: false,
+            since_step: 0.,
+        }
+    }
+
+    pub fn step_forward(&mut self) -> bool {
+        if self.shown == self.steps.len() {
+            let Some(step) = self.stepper.next_step() else {
+                return false;
+            };
+            for &(r, c, _) in &step.cells {
+                self.settled_by[r * self.num_columns + c] = Some(self.steps.len());
+            }
+            self.steps.push(step);
+        }
+        self.shown += 1;
+        self.since_step = 0.;
+        true
+    }
+
+    pub fn step_back(&mut self) {
+        self.shown = self.shown.saturating_sub(1);
+        self.since_step = 0.;
+    }
+
+    pub fn current_step(&self) -> Option<&Step> {
+        self.shown.checked_sub(1).map(|idx| &self.steps[idx])
+    }
+
+    pub fn cell(&self, r: usize, c: usize) -> ReplayCell {
+        // The grid can be bigger than the level while it's being resized
+        if c >= self.num_columns {
+            return ReplayCell::Unknown;
+        }
+        match self.settled_by.get(r * self.num_columns + c) {
+            Some(&Some(step)) if step + 1 == self.shown => {
+                ReplayCell::JustSettled(1. - (self.since_step / STEP_SECONDS).min(1.))
+            }
+            Some(&Some(step)) if step < self.shown => ReplayCell::Known,
+            _ => ReplayCell::Unknown,
+        }
+    }
+
+    // Returns false once the replay should be closed
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        let finished = self.shown == self.steps.len() && self.stepper.is_done();
+        if self.playing {
+            self.since_step += ui.input(|i| i.stable_dt);
+            if self.since_step >= STEP_SECONDS {
+                self.playing = self.step_forward();
+            }
+        }
+
+        ui.label(match self.current_step() {
+            None => "Nothing worked out yet.".to_owned(),
+            Some(step) => format!("Step {}: {}", self.shown, describe_step(step)),
+        });
+        if finished {
+            ui.label("That's everything the solver could work out.");
+        }
+        let mut open = true;
+        ui.horizontal(|ui| {
+            if ui.button("Previous").clicked() {
+                self.playing = false;
+                self.step_back();
+            }
+            if ui.button("Next").clicked() {
+                self.playing = false;
+                self.step_forward();
+            }
+            let play = if self.playing { "Pause" } else { "Play" };
+            if ui.button(play).clicked() {
+                self.playing = !self.playing && !finished;
+            }
+            if ui.button("Stop").clicked() {
+                open = false;
+            }
+        });
+        open
+    }
+}
+
+// Rows and columns are counted from 1 for people reading them
+fn describe_step(step: &Step) -> String {
+    let line = describe_line(step.line);
+    let how = match step.technique {
+        Technique::Overlap => format!("the clues overlap in {}", line),
+        Technique::EdgeForcing => format!("crossing lines push the clues of {} into place", line),
+        Technique::Contradiction => {
+            let (r, c, _) = step.cells[0];
+            format!(
+                "anything else at row {} column {} breaks a line",
+                r + 1,
+                c + 1
+            )
+        }
+        Technique::Search { depth } => {
+            let (r, c, _) = step.cells[0];
+            format!("guess {} at row {} column {}", depth, r + 1, c + 1)
+        }
+    };
+    format!("{}, settling {} cells", how, step.cells.len())
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use crate::netpbm::Pbm;
+
+    #[test]
+    fn going_back_hides_what_the_step_settled() {
+        let pbm = Pbm {
+            width: 3,
+            height: 3,
+            cells: vec![true, true, true, false, false, false, true, false, true],
+        };
+        let tps: PlayState = (&pbm).into();
+        let mut replay = Replay::new(&tps);
+        assert_eq!(ReplayCell::Unknown, replay.cell(0, 0));
+        assert!(replay.step_forward());
+        // The first step is the full top row
+        assert_eq!(ReplayCell::JustSettled(1.), replay.cell(0, 0));
+        assert!(replay.step_forward());
+        assert_eq!(ReplayCell::Known, replay.cell(0, 0));
+        replay.step_back();
+        replay.step_back();
+        assert_eq!(ReplayCell::Unknown, replay.cell(0, 0));
+        assert_eq!(None, replay.current_step());
+
+        while replay.step_forward() {}
+        for r in 0..3 {
+            for c in 0..3 {
+                assert_ne!(ReplayCell::Unknown, replay.cell(r, c));
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use crate::editor::editor_grids::{BLANK, EditorGrids};
+use crate::editor::editor_settings::{LevelSettings, MIN_SIZE};
+
+// One off changes to the whole design. Anything that moves cells around moves
+// them on both grids together, so the image stays lined up with the puzzle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    FlipHorizontal,
+    FlipVertical,
+    RotateClockwise,
+    RotateAnticlockwise,
+    ShiftUp,
+    ShiftDown,
+    ShiftLeft,
+    ShiftRight,
+    InvertPbm,
+    CropToContent,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 10] = [
+        Transform::FlipHorizontal,
+        Transform::FlipVertical,
+        Transform::RotateClockwise,
+        Transform::RotateAnticlockwise,
+        Transform::ShiftUp,
+        Transform::ShiftDown,
+        Transform::ShiftLeft,
+        Transform::ShiftRight,
+        Transform::InvertPbm,
+        Transform::CropToContent,
+    ];
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            Transform::FlipHorizontal => "Flip left/right",
+            Transform::FlipVertical => "Flip top/bottom",
+            Transform::RotateClockwise => "Rotate clockwise",
+            Transform::RotateAnticlockwise => "Rotate anticlockwise",
+            Transform::ShiftUp => "Shift up",
+            Transform::ShiftDown => "Shift down",
+            Transform::ShiftLeft => "Shift left",
+            Transform::ShiftRight => "Shift right",
+            Transform::InvertPbm => "Invert PBM",
+            Transform::CropToContent => "Crop to content",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Where each cell of the transformed level gets taken from, None for cells
+// that start out blank
+type Source = Box<dyn Fn(usize, usize) -> Option<(usize, usize)>>;
+
+// Returns whether anything changed. Rotating and cropping change the level's
+// size too. Only what's in the level gets transformed, so any cells an earlier
+// resize hid away are forgotten once the level's cells have actually moved.
+pub fn apply(
+    transform: Transform,
+    level_settings: &mut LevelSettings,
+    grids: &mut EditorGrids,
+) -> bool {
+    let rows = level_settings.height;
+    let columns = level_settings.width;
+    let ((new_rows, new_columns), source): ((usize, usize), Source) = match transform {
+        Transform::FlipHorizontal => (
+            (rows, columns),
+            Box::new(move |r, c| Some((r, columns - 1 - c))),
+        ),
+        Transform::FlipVertical => (
+            (rows, columns),
+            Box::new(move |r, c| Some((rows - 1 - r, c))),
+        ),
+        Transform::RotateClockwise => (
+            (columns, rows),
+            Box::new(move |r, c| Some((rows - 1 - c, r))),
+        ),
+        Transform::RotateAnticlockwise => (
+            (columns, rows),
+            Box::new(move |r, c| Some((c, columns - 1 - r))),
+        ),
+        // Whatever falls off one edge comes back on the other
+        Transform::ShiftUp => (
+            (rows, columns),
+            Box::new(move |r, c| Some(((r + 1) % rows, c))),
+        ),
+        Transform::ShiftDown => (
+            (rows, columns),
+            Box::new(move |r, c| Some(((r + rows - 1) % rows, c))),
+        ),
+        Transform::ShiftLeft => (
+            (rows, columns),
+            Box::new(move |r, c| Some((r, (c + 1) % columns))),
+        ),
+        Transform::ShiftRight => (
+            (rows, columns),
+            Box::new(move |r, c| Some((r, (c + columns - 1) % columns))),
+        ),
+        Transform::InvertPbm => {
+            for filled in grids.pbm_grid.iter_mut().flatten() {
+                *filled = !*filled;
+            }
+            return true;
+        }
+        Transform::CropToContent => {
+            let Some((top, left, bottom, right)) = content_bounds(&grids.pbm_grid) else {
+                return false;
+            };
+            // Levels can't get smaller than the editor allows, so tiny
+            // pictures get blank space below and to the right of them
+            let size = (
+                (bottom - top + 1).max(MIN_SIZE),
+                (right - left + 1).max(MIN_SIZE),
+            );
+            let source: Source = Box::new(move |r, c| {
+                let (r, c) = (r + top, c + left);
+                (r <= bottom && c <= right).then_some((r, c))
+            });
+            (size, source)
+        }
+    };
+
+    let pbm_grid = remap(&grids.pbm_grid, new_rows, new_columns, false, &source);
+    let ppm_grid = remap(&grids.ppm_grid, new_rows, new_columns, BLANK, &source);
+    let changed = pbm_grid != grids.pbm_grid || ppm_grid != grids.ppm_grid;
+    if changed {
+        grids.forget_hidden();
+    }
+    grids.pbm_grid = pbm_grid;
+    grids.ppm_grid = ppm_grid;
+    level_settings.height = new_rows;
+    level_settings.width = new_columns;
+    changed
+}
+
+fn remap<T: Copy>(
+    grid: &[Vec<T>],
+    rows: usize,
+    columns: usize,
+    blank: T,
+    source: &Source,
+) -> Vec<Vec<T>> {
+    (0..rows)
+        .map(|r| {
+            (0..columns)
+                .map(|c| match source(r, c) {
+                    Some((from_r, from_c)) => grid[from_r][from_c],
+                    None => blank,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Top, left, bottom and right of the filled cells, None when there aren't any
+fn content_bounds(pbm_grid: &[Vec<bool>]) -> Option<(usize, usize, usize, usize)> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (r, row) in pbm_grid.iter().enumerate() {
+        for (c, _) in row.iter().enumerate().filter(|(_, filled)| **filled) {
+            bounds = Some(match bounds {
+                None => (r, c, r, c),
+                Some((top, left, bottom, right)) => {
+                    (top.min(r), left.min(c), bottom.max(r), right.max(c))
+                }
+            });
+        }
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod transforms_tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    fn design(width: usize, height: usize) -> (LevelSettings, EditorGrids) {
+        let level_settings = LevelSettings {
+            width,
+            height,
+            ..LevelSettings::default()
+        };
+        (level_settings, EditorGrids::new(width, height))
+    }
+
+    #[test]
+    fn rotating_keeps_the_image_on_the_mask() {
+        let (mut level_settings, mut grids) = design(8, 6);
+        grids.pbm_grid[0][0] = true;
+        grids.ppm_grid[0][0] = RED;
+        grids.pbm_grid[5][1] = true;
+
+        assert!(apply(
+            Transform::RotateClockwise,
+            &mut level_settings,
+            &mut grids
+        ));
+        assert_eq!((6, 8), (level_settings.width, level_settings.height));
+        // The top left corner ends up top right
+        assert!(grids.pbm_grid[0][5]);
+        assert_eq!(RED, grids.ppm_grid[0][5]);
+        assert!(grids.pbm_grid[1][0]);
+        assert_eq!(BLANK, grids.ppm_grid[0][0]);
+
+        let start = grids.pbm_grid.clone();
+        for _ in 0..4 {
+            apply(
+                Transform::RotateAnticlockwise,
+                &mut level_settings,
+                &mut grids,
+            );
+        }
+        assert_eq!(start, grids.pbm_grid);
+        apply(Transform::FlipHorizontal, &mut level_settings, &mut grids);
+        apply(Transform::FlipVertical, &mut level_settings, &mut grids);
+        apply(Transform::RotateClockwise, &mut level_settings, &mut grids);
+        apply(Transform::RotateClockwise, &mut level_settings, &mut grids);
+        assert_eq!(start, grids.pbm_grid);
+    }
+
+    #[test]
+    fn shifting_wraps_around_the_level() {
+        let (mut level_settings, mut grids) = design(5, 5);
+        grids.pbm_grid[0][4] = true;
+        grids.ppm_grid[0][4] = RED;
+        apply(Transform::ShiftRight, &mut level_settings, &mut grids);
+        assert!(grids.pbm_grid[0][0] && !grids.pbm_grid[0][4]);
+        assert_eq!(RED, grids.ppm_grid[0][0]);
+        apply(Transform::ShiftUp, &mut level_settings, &mut grids);
+        assert!(grids.pbm_grid[4][0]);
+        apply(Transform::ShiftDown, &mut level_settings, &mut grids);
+        apply(Transform::ShiftLeft, &mut level_settings, &mut grids);
+        assert!(grids.pbm_grid[0][4]);
+        assert_eq!(RED, grids.ppm_grid[0][4]);
+    }
+
+    #[test]
+    fn cropping_trims_to_the_filled_cells() {
+        let (mut level_settings, mut grids) = design(20, 20);
+        assert!(!apply(
+            Transform::CropToContent,
+            &mut level_settings,
+            &mut grids
+        ));
+        for r in 3..10 {
+            grids.pbm_grid[r][4] = true;
+            grids.pbm_grid[r][11] = true;
+        }
+        grids.ppm_grid[3][11] = RED;
+
+        assert!(apply(
+            Transform::CropToContent,
+            &mut level_settings,
+            &mut grids
+        ));
+        assert_eq!((8, 7), (level_settings.width, level_settings.height));
+        assert!(grids.pbm_grid[0][0] && grids.pbm_grid[6][7]);
+        assert_eq!(RED, grids.ppm_grid[0][7]);
+        assert_eq!(7, grids.pbm_grid.len());
+        assert_eq!(8, grids.ppm_grid[6].len());
+        assert!(!apply(
+            Transform::CropToContent,
+            &mut level_settings,
+            &mut grids
+        ));
+
+        // Too small for a level gets padded out
+        let (mut level_settings, mut grids) = design(10, 10);
+        grids.pbm_grid[6][6] = true;
+        apply(Transform::CropToContent, &mut level_settings, &mut grids);
+        assert_eq!(
+            (MIN_SIZE, MIN_SIZE),
+            (level_settings.width, level_settings.height)
+        );
+        assert!(grids.pbm_grid[0][0]);
+    }
+
+    #[test]
+    fn inverting_only_touches_the_mask_inside_the_level() {
+        let (mut level_settings, mut grids) = design(5, 6);
+        grids.ppm_grid[1][1] = RED;
+        assert!(apply(Transform::InvertPbm, &mut level_settings, &mut grids));
+        assert!(grids.pbm_grid.iter().flatten().all(|&filled| filled));
+        assert_eq!(RED, grids.ppm_grid[1][1]);
+    }
+
+    #[test]
+    fn hidden_cells_are_only_forgotten_once_cells_move() {
+        let (mut level_settings, mut grids) = design(10, 10);
+        level_settings.keep_hidden_cells = true;
+        grids.pbm_grid[9][9] = true;
+        grids.resize(&mut level_settings, 5, 5);
+        assert!(!apply(
+            Transform::CropToContent,
+            &mut level_settings,
+            &mut grids
+        ));
+        apply(Transform::InvertPbm, &mut level_settings, &mut grids);
+        apply(Transform::InvertPbm, &mut level_settings, &mut grids);
+        grids.resize(&mut level_settings, 10, 10);
+        assert!(grids.pbm_grid[9][9]);
+
+        grids.resize(&mut level_settings, 5, 5);
+        grids.pbm_grid[0][0] = true;
+        assert!(apply(
+            Transform::FlipHorizontal,
+            &mut level_settings,
+            &mut grids
+        ));
+        grids.resize(&mut level_settings, 10, 10);
+        assert!(!grids.pbm_grid[9][9]);
+        assert_eq!(
+            1,
+            grids
+                .pbm_grid
+                .iter()
+                .flatten()
+                .filter(|&&filled| filled)
+                .count()
+        );
+    }
+}
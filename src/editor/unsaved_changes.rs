@@ -0,0 +1,67 @@
+use egor::app::{egui::Id, egui::Modal, egui::Ui};
+
+// Things that throw away the design being edited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Discarding {
+    Quit,
+    OpenLevel,
+    NewLevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsavedChoice {
+    SaveFirst,
+    Discard,
+    Cancel,
+}
+
+// Asks what to do about unsaved changes before going ahead with whatever was
+// about to lose them
+#[derive(Default)]
+pub struct UnsavedChangesPrompt {
+    pending: Option<Discarding>,
+}
+
+impl UnsavedChangesPrompt {
+    pub fn ask(&mut self, discarding: Discarding) {
+        self.pending = Some(discarding);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // Gives back what was waiting once a choice is made
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<(Discarding, UnsavedChoice)> {
+        let discarding = self.pending?;
+        let mut choice = None;
+        let doing = match discarding {
+            Discarding::Quit => "quitting",
+            Discarding::OpenLevel => "opening another level",
+            Discarding::NewLevel => "starting a new level",
+        };
+        // No backing out by clicking away or pressing escape, that's what
+        // opened it in the first place when quitting
+        Modal::new(Id::new("unsaved_changes")).show(ui.ctx(), |ui| {
+            ui.set_width(240.);
+            ui.heading("Unsaved changes");
+            ui.add_space(18.);
+            ui.label(format!("Save your level before {}?", doing));
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    choice = Some(UnsavedChoice::SaveFirst);
+                }
+                if ui.button("Don't save").clicked() {
+                    choice = Some(UnsavedChoice::Discard);
+                }
+                if ui.button("Cancel").clicked() {
+                    choice = Some(UnsavedChoice::Cancel);
+                }
+            });
+        });
+        if choice.is_some() {
+            self.pending = None;
+        }
+        choice.map(|choice| (discarding, choice))
+    }
+}
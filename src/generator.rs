@@ -0,0 +1,256 @@
+use crate::editor::difficulty::{DifficultyBand, trace_solve};
+use crate::editor::solver::{SolvedState, TheMultiVerseOfLines};
+use crate::levels::{Level, LevelStats, PuzzleKind};
+use crate::netpbm::{Pbm, Ppm};
+use crate::playstate::PlayState;
+use std::path::PathBuf;
+
+// How many cells get flipped looking for a puzzle before settling for the
+// closest one found
+const MAX_MUTATIONS: usize = 500;
+
+// SplitMix64, small and good enough for picking cells. We roll our own so
+// the same seed gives the same puzzle on every platform and every build,
+// which a library RNG doesn't promise across versions.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Somewhere in 0..1
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Somewhere in 0..bound, bound has to be more than 0
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PuzzleRequest {
+    pub width: usize,
+    pub height: usize,
+    // Roughly how much of the grid starts filled, from 0 to 1
+    pub density: f32,
+    // Any difficulty will do when None
+    pub difficulty: Option<DifficultyBand>,
+    pub seed: u64,
+}
+
+impl Default for PuzzleRequest {
+    fn default() -> Self {
+        Self {
+            width: 10,
+            height: 10,
+            density: 0.55,
+            difficulty: None,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GeneratedPuzzle {
+    pub info: Pbm,
+    pub image: Ppm,
+    pub difficulty: u32,
+}
+
+impl GeneratedPuzzle {
+    pub fn to_level(self, path: PathBuf) -> Level {
+        Level {
+            info: self.info,
+            image: self.image,
+            kind: PuzzleKind::Monochrome,
+            completed: false,
+            stats: LevelStats::default(),
+            difficulty: Some(self.difficulty),
+            path,
+        }
+    }
+}
+
+// Scatters cells about and then keeps flipping them until line logic alone
+// can solve the picture. Flipping a cell line logic couldn't decide is what
+// pins the picture down, flipping any other cell shakes up a puzzle that's
+// the wrong difficulty. If the difficulty asked for never turns up, the
+// closest puzzle found is used instead.
+pub fn generate(request: &PuzzleRequest) -> GeneratedPuzzle {
+    let width = request.width.max(1);
+    let height = request.height.max(1);
+    let mut rng = SeededRng::new(request.seed);
+    let mut cells: Vec<bool> = (0..width * height)
+        .map(|_| rng.next_f32() < request.density)
+        .collect();
+
+    let mut closest: Option<(Vec<bool>, u32)> = None;
+    for _ in 0..MAX_MUTATIONS {
+        let info = Pbm {
+            width,
+            height,
+            cells: cells.clone(),
+        };
+        let play_state: PlayState = (&info).into();
+        let mut multiverse = TheMultiVerseOfLines::new(&play_state);
+        multiverse.collapse();
+        if multiverse.line_state() != SolvedState::UniqueSolution {
+            let undecided: Vec<usize> = (0..width * height)
+                .filter(|idx| {
+                    multiverse
+                        .cell_colors(idx / width, idx % width)
+                        .count_ones()
+                        != 1
+                })
+                .collect();
+            let flip = match undecided.is_empty() {
+                true => rng.below(cells.len()),
+                false => undecided[rng.below(undecided.len())],
+            };
+            cells[flip] = !cells[flip];
+            continue;
+        }
+
+        let rating = trace_solve(&play_state).rating();
+        let distance = |rating| request.difficulty.map_or(0, |band| band.distance(rating));
+        if distance(rating) == 0 {
+            return finish(info, rating, &mut rng);
+        }
+        if closest
+            .as_ref()
+            .is_none_or(|&(_, best)| distance(rating) < distance(best))
+        {
+            closest = Some((cells.clone(), rating));
+        }
+        let flip = rng.below(cells.len());
+        cells[flip] = !cells[flip];
+    }
+
+    // A completely filled grid is always line solvable, for when nothing else
+    // turned up at all
+    let (cells, rating) = closest.unwrap_or_else(|| {
+        let info = Pbm {
+            width,
+            height,
+            cells: vec![true; width * height],
+        };
+        (info.cells.clone(), trace_solve(&(&info).into()).rating())
+    });
+    let info = Pbm {
+        width,
+        height,
+        cells,
+    };
+    finish(info, rating, &mut rng)
+}
+
+// The reward is the picture tinted in a random hue, darkening towards the
+// bottom
+fn finish(info: Pbm, difficulty: u32, rng: &mut SeededRng) -> GeneratedPuzzle {
+    let hue = rng.next_f32() * 360.;
+    let image = Ppm::shaded(
+        &info,
+        hsv_to_rgb(hue, 0.55, 0.9),
+        hsv_to_rgb((hue + 40.) % 360., 0.7, 0.55),
+        [245, 240, 225],
+    );
+    GeneratedPuzzle {
+        info,
+        image,
+        difficulty,
+    }
+}
+
+// Hue in degrees, saturation and value from 0 to 1, out to 255
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [u16; 3] {
+    let chroma = value * saturation;
+    let sector = hue / 60.;
+    let x = chroma * (1. - (sector % 2. - 1.).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    };
+    let m = value - chroma;
+    [r, g, b].map(|channel| ((channel + m) * 255.).round() as u16)
+}
+
+#[cfg(test)]
+mod generator_tests {
+    use super::*;
+
+    #[test]
+    fn seeded_numbers_never_change() {
+        // If these change, every seed makes a different puzzle than it used to
+        let mut rng = SeededRng::new(0);
+        assert_eq!(0xE220A8397B1DCDAF, rng.next_u64());
+        assert_eq!(0x6E789E6AA1B965F4, rng.next_u64());
+        let mut rng = SeededRng::new(1234567);
+        assert_eq!(6457827717110365317, rng.next_u64());
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_puzzle() {
+        let request = PuzzleRequest {
+            seed: 42,
+            ..PuzzleRequest::default()
+        };
+        let first = generate(&request);
+        let again = generate(&request);
+        assert_eq!(first.info.cells, again.info.cells);
+        assert_eq!(first.image.cells, again.image.cells);
+        let other = generate(&PuzzleRequest {
+            seed: 43,
+            ..request
+        });
+        assert_ne!(first.info.cells, other.info.cells);
+    }
+
+    #[test]
+    fn generated_puzzles_are_line_solvable() {
+        for seed in 0..10 {
+            let request = PuzzleRequest {
+                width: 5 + seed as usize,
+                height: 8,
+                seed,
+                ..PuzzleRequest::default()
+            };
+            let puzzle = generate(&request);
+            assert_eq!(request.width, puzzle.info.width);
+            let play_state: PlayState = (&puzzle.info).into();
+            let mut multiverse = TheMultiVerseOfLines::new(&play_state);
+            multiverse.collapse();
+            assert_eq!(SolvedState::UniqueSolution, multiverse.line_state());
+            assert_eq!(trace_solve(&play_state).rating(), puzzle.difficulty);
+        }
+    }
+
+    #[test]
+    fn difficulty_requests_are_met_when_they_can_be() {
+        for band in [DifficultyBand::Easy, DifficultyBand::Medium] {
+            let puzzle = generate(&PuzzleRequest {
+                difficulty: Some(band),
+                seed: 7,
+                ..PuzzleRequest::default()
+            });
+            assert_eq!(band, DifficultyBand::of(puzzle.difficulty));
+        }
+    }
+}
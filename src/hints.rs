@@ -0,0 +1,254 @@
+use crate::editor::solver::solve_line;
+use crate::playstate::{CellState, PlayState};
+
+pub const HINTS_PER_PUZZLE: usize = 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HintLine {
+    Row(usize),
+    Column(usize),
+}
+
+impl HintLine {
+    // The (row, column) of the idx'th cell along this line
+    pub fn cell(&self, idx: usize) -> (usize, usize) {
+        match *self {
+            HintLine::Row(row) => (row, idx),
+            HintLine::Column(column) => (idx, column),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Hint {
+    // The clues for the line force these cells, as (row, column, color)
+    // where a color of 0 means the cell has to be empty.
+    Deduction {
+        line: HintLine,
+        cells: Vec<(usize, usize, usize)>,
+    },
+    // Something the player ruled out in this line can't be right.
+    Mistake {
+        line: HintLine,
+    },
+}
+
+impl Hint {
+    pub fn line(&self) -> HintLine {
+        match self {
+            Hint::Deduction { line, .. } => *line,
+            Hint::Mistake { line } => *line,
+        }
+    }
+}
+
+// What the player has established about a cell as a bit set of the colors it
+// could be, with bit 0 for empty. Fills are always right since the game won't
+// let you fill a wrong cell, but user marks might not be.
+pub(crate) fn known(cell: CellState, color: usize) -> u32 {
+    match cell {
+        CellState::Empty => u32::MAX,
+        CellState::Filled => 1 << color,
+        CellState::Incorrect | CellState::UserRuledOut | CellState::RuledOut => 1,
+    }
+}
+
+// Looks for the first row (then column) where the clues plus what the player
+// has already marked force a cell they haven't dealt with yet. Only a single
+// line is considered at a time, the same as a person would.
+pub fn next_hint(play_state: &PlayState) -> Option<Hint> {
+    let rows = play_state
+        .rows()
+        .into_iter()
+        .zip(&play_state.row_groups)
+        .enumerate()
+        .map(|(r, (cells, groups))| (HintLine::Row(r), cells, groups));
+    let columns = play_state
+        .cols()
+        .into_iter()
+        .zip(&play_state.column_groups)
+        .enumerate()
+        .map(|(c, (cells, groups))| (HintLine::Column(c), cells, groups));
+
+    let mut first_deduction = None;
+    for (line, cells, groups) in rows.chain(columns) {
+        let clues: Vec<(usize, usize)> = groups.iter().map(|g| (g.num_cells, g.color)).collect();
+        let knowledge: Vec<u32> = cells
+            .iter()
+            .enumerate()
+            .map(|(idx, &cell)| {
+                let (row, column) = line.cell(idx);
+                known(cell, play_state.goal_color(row, column))
+            })
+            .collect();
+        let solved = solve_line(&knowledge, &clues);
+        if solved.count == 0 {
+            return Some(Hint::Mistake { line });
+        }
+        if first_deduction.is_some() {
+            continue;
+        }
+
+        let forced: Vec<(usize, usize, usize)> = knowledge
+            .iter()
+            .zip(solved.cells)
+            .enumerate()
+            .filter(|(_, (cell, colors))| **cell == u32::MAX && colors.count_ones() == 1)
+            .map(|(idx, (_, colors))| {
+                let (row, column) = line.cell(idx);
+                (row, column, colors.trailing_zeros() as usize)
+            })
+            .collect();
+        if forced.is_empty() {
+            continue;
+        }
+
+        // A wrong mark can still leave the line with possibilities, just the
+        // wrong ones. So double check against the picture before handing out
+        // advice that would lead the player astray.
+        let misled = forced
+            .iter()
+            .any(|&(row, column, color)| play_state.goal_color(row, column) != color);
+        if misled {
+            return Some(Hint::Mistake { line });
+        }
+        first_deduction = Some(Hint::Deduction {
+            line,
+            cells: forced,
+        });
+    }
+    first_deduction
+}
+
+#[cfg(test)]
+mod hint_tests {
+    use super::*;
+    use crate::netpbm::Pbm;
+
+    #[rustfmt::skip]
+    fn test_play_state() -> PlayState {
+        let pbm = Pbm {
+            width: 5,
+            height: 5,
+            cells: vec![
+                false, false, false, false, false,
+                true , true , false, false ,true,
+                false, true , true , true , false,
+                true , false, true , false, true,
+                true , false, false, true , true,
+            ]
+        };
+        (&pbm).into()
+    }
+
+    #[test]
+    fn finds_the_empty_first_row_before_anything_else() {
+        let state = test_play_state();
+        let Some(Hint::Deduction { line, cells }) = next_hint(&state) else {
+            panic!("expected a deduction");
+        };
+        assert_eq!(HintLine::Row(0), line);
+        assert_eq!(5, cells.len());
+        assert!(cells.iter().all(|&(r, _, color)| r == 0 && color == 0));
+    }
+
+    #[test]
+    fn overlap_in_a_line_is_found_once_earlier_lines_are_done() {
+        let mut state = test_play_state();
+        for c in 0..5 {
+            state.mark_cell(0, c);
+        }
+        // Row 1 is a 2 1 in 5, so only the second cell is certain
+        let Some(Hint::Deduction { line, cells }) = next_hint(&state) else {
+            panic!("expected a deduction");
+        };
+        assert_eq!(HintLine::Row(1), line);
+        assert_eq!(vec![(1, 1, 1)], cells);
+    }
+
+    #[test]
+    fn wrong_marks_are_called_out_as_mistakes() {
+        let mut state = test_play_state();
+        // Row 2 is a 3 in 5, ruling out the middle can't work
+        state.mark_cell(2, 2);
+        assert_eq!(
+            Some(Hint::Mistake {
+                line: HintLine::Row(2)
+            }),
+            next_hint(&state)
+        );
+    }
+
+    #[test]
+    fn wrong_marks_that_still_fit_the_clues_are_mistakes_too() {
+        let mut state = test_play_state();
+        for c in 0..5 {
+            state.mark_cell(0, c);
+        }
+        // Marking the last cell of row 1 fits a "2 1" line's clues only if the
+        // 1 moves left, which isn't the picture.
+        state.mark_cell(1, 4);
+        assert_eq!(
+            Some(Hint::Mistake {
+                line: HintLine::Row(1)
+            }),
+            next_hint(&state)
+        );
+    }
+
+    #[test]
+    fn requesting_a_hint_uses_one_up() {
+        let mut state = test_play_state();
+        state.request_hint();
+        assert!(state.hint.is_some());
+        assert_eq!(HINTS_PER_PUZZLE - 1, state.hints_remaining);
+        assert_eq!(1, state.hints_used);
+
+        state.apply_hint();
+        assert!(state.hint.is_none());
+        assert_eq!(vec![CellState::RuledOut; 5], state.rows()[0]);
+    }
+
+    #[test]
+    fn asking_again_while_a_hint_is_showing_is_free() {
+        let mut state = test_play_state();
+        state.request_hint();
+        let shown = state.hint.clone();
+        state.request_hint();
+        assert_eq!(shown, state.hint);
+        assert_eq!(HINTS_PER_PUZZLE - 1, state.hints_remaining);
+        assert_eq!(1, state.hints_used);
+
+        // Once it's been dealt with the next one costs as usual
+        state.apply_hint();
+        state.request_hint();
+        assert_eq!(2, state.hints_used);
+    }
+
+    #[test]
+    fn no_hints_are_given_once_they_run_out() {
+        let mut state = test_play_state();
+        state.hints_remaining = 0;
+        state.request_hint();
+        assert!(state.hint.is_none());
+        assert_eq!(0, state.hints_used);
+    }
+
+    #[test]
+    fn color_hints_say_which_color_to_fill() {
+        use crate::netpbm::Pgm;
+        let mask = Pgm {
+            width: 3,
+            height: 1,
+            max_value: 2,
+            cells: vec![1, 2, 2],
+        };
+        let state = PlayState::painted(&mask, vec![[1., 0., 0., 1.], [0., 0., 1., 1.]]);
+        // A 1 then a 2 of another color fits a 3 wide row with no gap at all
+        let Some(Hint::Deduction { line, cells }) = next_hint(&state) else {
+            panic!("expected a deduction");
+        };
+        assert_eq!(HintLine::Row(0), line);
+        assert_eq!(vec![(0, 0, 1), (0, 1, 2), (0, 2, 2)], cells);
+    }
+}
@@ -1,29 +1,116 @@
+use crate::editor::solver::MAX_COLORS;
 use crate::netpbm::{LoadPbmErr, Pbm};
+use crate::netpbm::{LoadPgmErr, Pgm};
 use crate::netpbm::{LoadPpmErr, Ppm};
+use crate::playstate::PlayState;
 
 use std::fs::{read_dir, read_to_string, write};
 use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LevelStats {
+    // The fewest hints the player has needed to finish the level
+    pub hints_used: Option<usize>,
+}
+
+// Monochrome puzzles are played straight from the .pbm. Color ones use a
+// .pgm instead, whose values index into a palette kept in the .level file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PuzzleKind {
+    Monochrome,
+    Color { mask: Pgm, palette: Vec<[u16; 3]> },
+}
+
+impl PuzzleKind {
+    pub fn palette(&self) -> Option<Vec<[u16; 3]>> {
+        match self {
+            PuzzleKind::Monochrome => None,
+            PuzzleKind::Color { palette, .. } => Some(palette.clone()),
+        }
+    }
+
+    pub fn play_state(&self, info: &Pbm) -> PlayState {
+        match self {
+            PuzzleKind::Monochrome => info.into(),
+            PuzzleKind::Color { mask, palette } => {
+                PlayState::painted(mask, palette.iter().map(|&c| palette_rgba(c)).collect())
+            }
+        }
+    }
+}
+
+// Palette entries are always stored out of 255
+pub fn palette_rgba(color: [u16; 3]) -> [f32; 4] {
+    let [r, g, b] = color;
+    [r as f32 / 255., g as f32 / 255., b as f32 / 255., 1.0]
+}
 
 #[derive(Debug)]
 pub struct Level {
+    // Which cells are filled in. For color puzzles this is flattened from the
+    // mask so the rest of the game doesn't have to care.
     pub info: Pbm,
     pub image: Ppm,
+    pub kind: PuzzleKind,
     pub completed: bool,
+    pub stats: LevelStats,
+    // How hard the solver found it, see SolveTrace::rating
+    pub difficulty: Option<u32>,
     pub path: PathBuf,
 }
 
+impl From<&Level> for PlayState {
+    fn from(level: &Level) -> PlayState {
+        level.kind.play_state(&level.info)
+    }
+}
+
 impl Level {
-    pub fn mark_completed(&mut self) -> std::io::Result<()> {
+    pub fn record_completion(&mut self, hints_used: usize) -> std::io::Result<()> {
+        let fewer_hints = self.stats.hints_used.is_none_or(|best| hints_used < best);
+        if self.completed && !fewer_hints {
+            return Ok(());
+        }
         self.completed = true;
-        write(&self.path, "1")
+        if fewer_hints {
+            self.stats.hints_used = Some(hints_used);
+        }
+        let level_file = LevelFile {
+            completed: self.completed,
+            stats: self.stats.clone(),
+            palette: self.kind.palette(),
+            difficulty: self.difficulty,
+        };
+        write(&self.path, level_file.to_string())
     }
 
     pub fn save(&self) -> std::io::Result<()> {
-        write(&self.path, "0")?;
-        let pbm_path = self.path.with_extension("pbm");
+        if let Some(palette) = self.kind.palette()
+            && palette.len() > MAX_COLORS
+        {
+            return Err(std::io::Error::other(LevelFileErr::TooManyColors {
+                found: palette.len(),
+            }));
+        }
+        let level_file = LevelFile {
+            palette: self.kind.palette(),
+            difficulty: self.difficulty,
+            ..LevelFile::default()
+        };
+        write(&self.path, level_file.to_string())?;
+        match &self.kind {
+            PuzzleKind::Monochrome => {
+                let pbm_path = self.path.with_extension("pbm");
+                write(&pbm_path, format!("{}", self.info))?;
+            }
+            PuzzleKind::Color { mask, .. } => {
+                let pgm_path = self.path.with_extension("pgm");
+                write(&pgm_path, format!("{}", mask))?;
+            }
+        }
         let ppm_path = self.path.with_extension("ppm");
-        write(&pbm_path, format!("{}", self.info))?;
         write(&ppm_path, format!("{}", self.image))?;
         Ok(())
     }
@@ -43,6 +130,19 @@ pub enum LevelLoadError {
         path: PathBuf,
         source: LoadPpmErr,
     },
+    ParsePgm {
+        path: PathBuf,
+        source: LoadPgmErr,
+    },
+    ParseLevel {
+        path: PathBuf,
+        source: LevelFileErr,
+    },
+    ColorOutsidePalette {
+        path: PathBuf,
+        found: u16,
+        palette_size: usize,
+    },
     InvalidDirectory(PathBuf),
 }
 
@@ -60,6 +160,21 @@ impl std::fmt::Display for LevelLoadError {
             LevelLoadError::ParsePpm { path, source } => {
                 write!(f, "could not parse PPM {:?}: {}", path, source)
             }
+            LevelLoadError::ParsePgm { path, source } => {
+                write!(f, "could not parse PGM {:?}: {}", path, source)
+            }
+            LevelLoadError::ParseLevel { path, source } => {
+                write!(f, "could not parse level {:?}: {}", path, source)
+            }
+            LevelLoadError::ColorOutsidePalette {
+                path,
+                found,
+                palette_size,
+            } => write!(
+                f,
+                "{:?} uses color {} but the palette only has {}",
+                path, found, palette_size
+            ),
             LevelLoadError::InvalidDirectory(path) => {
                 write!(f, "{:?} is not a directory", path)
             }
@@ -73,11 +188,150 @@ impl std::error::Error for LevelLoadError {
             LevelLoadError::Io { source, .. } => Some(source),
             LevelLoadError::ParsePbm { source, .. } => Some(source),
             LevelLoadError::ParsePpm { source, .. } => Some(source),
+            LevelLoadError::ParsePgm { source, .. } => Some(source),
+            LevelLoadError::ParseLevel { source, .. } => Some(source),
+            LevelLoadError::ColorOutsidePalette { .. } => None,
             LevelLoadError::InvalidDirectory(_) => None,
         }
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum LevelFileErr {
+    UnknownKind { found: String },
+    MissingPalette,
+    InvalidPaletteColor { found: String },
+    TooManyColors { found: usize },
+}
+
+impl std::error::Error for LevelFileErr {}
+
+impl std::fmt::Display for LevelFileErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        use LevelFileErr::*;
+        let s = match self {
+            UnknownKind { found } => {
+                format!("unknown kind {}, expected monochrome or color", found)
+            }
+            MissingPalette => "color levels need a palette".to_owned(),
+            InvalidPaletteColor { found } => format!("invalid palette color: {}", found),
+            TooManyColors { found } => {
+                format!(
+                    "palette has {} colors, at most {} allowed",
+                    found, MAX_COLORS
+                )
+            }
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// The .level file holds the player's progress and what kind of puzzle it is.
+// The first line is 1 or 0 for whether the level has been completed, any lines
+// after are key=value settings and stats. A color level looks like:
+//   0
+//   kind=color
+//   palette=255 0 0,0 0 255
+//   difficulty=12
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LevelFile {
+    pub completed: bool,
+    pub stats: LevelStats,
+    // Only color puzzles have a palette, one 0-255 rgb entry per mask index
+    pub palette: Option<Vec<[u16; 3]>>,
+    pub difficulty: Option<u32>,
+}
+
+fn parse_palette(value: &str) -> Result<Vec<[u16; 3]>, LevelFileErr> {
+    let palette = value
+        .split(',')
+        .map(|color| {
+            let channels: Vec<u16> = color
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| LevelFileErr::InvalidPaletteColor {
+                    found: color.to_owned(),
+                })?;
+            match channels[..] {
+                [r, g, b] if r.max(g).max(b) <= 255 => Ok([r, g, b]),
+                _ => Err(LevelFileErr::InvalidPaletteColor {
+                    found: color.to_owned(),
+                }),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if palette.len() > MAX_COLORS {
+        return Err(LevelFileErr::TooManyColors {
+            found: palette.len(),
+        });
+    }
+    Ok(palette)
+}
+
+impl FromStr for LevelFile {
+    type Err = LevelFileErr;
+    fn from_str(contents: &str) -> Result<LevelFile, LevelFileErr> {
+        let mut lines = contents.lines();
+        let completed = lines.next().is_some_and(|line| line.trim() == "1");
+        let mut stats = LevelStats::default();
+        let mut is_color = false;
+        let mut palette = None;
+        let mut difficulty = None;
+        for line in lines {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "hints_used" => stats.hints_used = value.trim().parse().ok(),
+                "kind" => {
+                    is_color = match value.trim() {
+                        "monochrome" => false,
+                        "color" => true,
+                        found => {
+                            return Err(LevelFileErr::UnknownKind {
+                                found: found.to_owned(),
+                            });
+                        }
+                    }
+                }
+                "palette" => palette = Some(parse_palette(value)?),
+                "difficulty" => difficulty = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+        if is_color && palette.is_none() {
+            return Err(LevelFileErr::MissingPalette);
+        }
+        Ok(LevelFile {
+            completed,
+            stats,
+            palette: palette.filter(|_| is_color),
+            difficulty,
+        })
+    }
+}
+
+impl std::fmt::Display for LevelFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", if self.completed { "1" } else { "0" })?;
+        if let Some(palette) = &self.palette {
+            let colors: Vec<String> = palette
+                .iter()
+                .map(|[r, g, b]| format!("{} {} {}", r, g, b))
+                .collect();
+            write!(f, "\nkind=color\npalette={}", colors.join(","))?;
+        }
+        if let Some(difficulty) = self.difficulty {
+            write!(f, "\ndifficulty={}", difficulty)?;
+        }
+        if let Some(hints_used) = self.stats.hints_used {
+            write!(f, "\nhints_used={}", hints_used)?;
+        }
+        Ok(())
+    }
+}
+
 pub fn load_levels_from_dir(dir: &Path) -> LevelsLoadResult<Vec<Level>> {
     if !dir.is_dir() {
         return Err(LevelLoadError::InvalidDirectory(dir.to_path_buf()));
@@ -110,21 +364,20 @@ pub fn load_level(level_file: PathBuf) -> LevelsLoadResult<Level> {
         source: e,
     })?;
 
-    let completed = contents.trim() == "1";
+    let header: LevelFile = contents.parse().map_err(|e| LevelLoadError::ParseLevel {
+        path: level_file.clone(),
+        source: e,
+    })?;
 
-    let pbm_path = level_file.with_extension("pbm");
-    let ppm_path = level_file.with_extension("ppm");
+    let (pbm, kind) = match header.palette {
+        None => (load_pbm(&level_file)?, PuzzleKind::Monochrome),
+        Some(palette) => {
+            let mask = load_pgm(&level_file, palette.len())?;
+            (mask.to_pbm(), PuzzleKind::Color { mask, palette })
+        }
+    };
 
-    let pbm: Pbm = read_to_string(&pbm_path)
-        .map_err(|e| LevelLoadError::Io {
-            path: pbm_path.clone(),
-            source: e,
-        })?
-        .parse()
-        .map_err(|e| LevelLoadError::ParsePbm {
-            path: pbm_path.clone(),
-            source: e,
-        })?;
+    let ppm_path = level_file.with_extension("ppm");
 
     let ppm: Ppm = read_to_string(&ppm_path)
         .map_err(|e| LevelLoadError::Io {
@@ -140,7 +393,129 @@ pub fn load_level(level_file: PathBuf) -> LevelsLoadResult<Level> {
     Ok(Level {
         info: pbm,
         image: ppm,
-        completed,
+        kind,
+        completed: header.completed,
+        stats: header.stats,
+        difficulty: header.difficulty,
         path: level_file.clone(),
     })
 }
+
+fn load_pbm(level_file: &Path) -> LevelsLoadResult<Pbm> {
+    let pbm_path = level_file.with_extension("pbm");
+    read_to_string(&pbm_path)
+        .map_err(|e| LevelLoadError::Io {
+            path: pbm_path.clone(),
+            source: e,
+        })?
+        .parse()
+        .map_err(|e| LevelLoadError::ParsePbm {
+            path: pbm_path.clone(),
+            source: e,
+        })
+}
+
+fn load_pgm(level_file: &Path, palette_size: usize) -> LevelsLoadResult<Pgm> {
+    let pgm_path = level_file.with_extension("pgm");
+    let pgm: Pgm = read_to_string(&pgm_path)
+        .map_err(|e| LevelLoadError::Io {
+            path: pgm_path.clone(),
+            source: e,
+        })?
+        .parse()
+        .map_err(|e| LevelLoadError::ParsePgm {
+            path: pgm_path.clone(),
+            source: e,
+        })?;
+
+    let highest = pgm.cells.iter().copied().max().unwrap_or(0);
+    if usize::from(highest) > palette_size {
+        return Err(LevelLoadError::ColorOutsidePalette {
+            path: pgm_path,
+            found: highest,
+            palette_size,
+        });
+    }
+    Ok(pgm)
+}
+
+#[cfg(test)]
+mod level_tests {
+    use super::*;
+
+    fn progress(completed: bool, stats: LevelStats) -> LevelFile {
+        LevelFile {
+            completed,
+            stats,
+            palette: None,
+            difficulty: None,
+        }
+    }
+
+    #[test]
+    fn reads_old_style_progress_files() {
+        let default = LevelStats::default();
+        assert_eq!(Ok(progress(true, default.clone())), "1".parse());
+        assert_eq!(Ok(progress(false, default.clone())), "0\n".parse());
+        assert_eq!(Ok(progress(false, default)), "".parse());
+    }
+
+    #[test]
+    fn progress_round_trips_through_a_string() {
+        let stats = LevelStats {
+            hints_used: Some(2),
+        };
+        let contents = progress(true, stats.clone()).to_string();
+        assert_eq!("1\nhints_used=2", contents);
+        assert_eq!(Ok(progress(true, stats)), contents.parse());
+    }
+
+    #[test]
+    fn unknown_stats_are_ignored() {
+        let level_file: LevelFile = "1\nfavorite_color=blue\nhints_used=0".parse().unwrap();
+        assert!(level_file.completed);
+        assert_eq!(Some(0), level_file.stats.hints_used);
+    }
+
+    #[test]
+    fn color_levels_declare_their_palette() {
+        let contents = "0\nkind=color\npalette=255 0 0,0 0 255";
+        let level_file: LevelFile = contents.parse().unwrap();
+        assert_eq!(Some(vec![[255, 0, 0], [0, 0, 255]]), level_file.palette);
+        assert_eq!(contents, level_file.to_string());
+
+        let level_file: LevelFile = "0\nkind=monochrome".parse().unwrap();
+        assert_eq!(None, level_file.palette);
+    }
+
+    #[test]
+    fn color_levels_without_a_usable_palette_are_rejected() {
+        assert_eq!(
+            Err(LevelFileErr::MissingPalette),
+            "0\nkind=color".parse::<LevelFile>()
+        );
+        assert_eq!(
+            Err(LevelFileErr::InvalidPaletteColor {
+                found: "255 0".to_owned()
+            }),
+            "0\nkind=color\npalette=255 0".parse::<LevelFile>()
+        );
+        assert_eq!(
+            Err(LevelFileErr::UnknownKind {
+                found: "plaid".to_owned()
+            }),
+            "0\nkind=plaid".parse::<LevelFile>()
+        );
+    }
+
+    #[test]
+    fn difficulty_is_kept_alongside_progress() {
+        let contents = "1\ndifficulty=12\nhints_used=1";
+        let level_file: LevelFile = contents.parse().unwrap();
+        assert_eq!(Some(12), level_file.difficulty);
+        assert_eq!(contents, level_file.to_string());
+
+        let level_file: LevelFile = "0\ndifficulty=hard".parse().unwrap();
+        assert_eq!(None, level_file.difficulty);
+    }
+}
@@ -1,10 +1,16 @@
+pub mod clues;
+pub mod controls;
+pub mod daily;
 pub mod editor;
+pub mod generator;
+pub mod hints;
 pub mod levels;
 pub mod netpbm;
 pub mod playstate;
 pub mod pop_up;
 pub mod screens;
 pub mod ui;
+pub mod validate;
 
 pub fn base_dir() -> std::path::PathBuf {
     let dir = std::env::current_exe()
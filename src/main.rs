@@ -1,4 +1,6 @@
 use logicpaint::base_dir;
+use logicpaint::controls::Controls;
+use logicpaint::daily::{DailyProgress, DayResult, daily_puzzle};
 use logicpaint::levels;
 use logicpaint::playstate::PlayState;
 use logicpaint::pop_up::PopUp;
@@ -12,31 +14,51 @@ use egor::{
     input::KeyCode,
 };
 
-use crate::screens::{ScreenAction, Screens};
+use crate::screens::{LevelListing, LevelOrder, ScreenAction, Screens};
 use crate::ui::ColorPalette;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let exe_dir = base_dir();
     let assets = exe_dir.join("assets");
     let level_dir_path = exe_dir.join("levels");
+    let daily_path = exe_dir.join("daily.progress");
 
     let loaded_ppms = LoadedPpms::load(assets)?;
 
     let mut current_screen = Screens::ChooseLevelScreen { page: 0 };
+    let mut level_order = LevelOrder::default();
     let mut levels = levels::load_levels_from_dir(&level_dir_path)?;
+    // Losing the daily streak shouldn't stop the rest of the game from starting
+    let mut maybe_popup: Option<PopUp> = None;
+    let mut daily_progress = DailyProgress::load(&daily_path).unwrap_or_else(|error| {
+        maybe_popup = Some(PopUp {
+            heading: "Error".to_owned(),
+            msg: format!(
+                "There was a problem loading the daily progress, so it starts over and replaces the old file once a daily is finished: {}",
+                error
+            ),
+            visible: true,
+        });
+        DailyProgress::default()
+    });
     let mut palette = ColorPalette::meeks();
     let mut transition_duration = 1.8;
 
     // TODO: Refactor this to be one struct passed around
-    assert!(levels.len() > 0, "You must have levels in the levels folder to boot the game");
+    assert!(
+        levels.len() > 0,
+        "You must have levels in the levels folder to boot the game"
+    );
     let mut win_image = levels[0].image.clone();
     let mut current_level = levels[0].path.clone();
-    let mut game_state: PlayState = (&levels[0].info).into();
+    let mut game_state: PlayState = (&levels[0]).into();
+    // Which day's puzzle is being played, if it's a daily
+    let mut current_daily = None;
 
     let mut wipe_progress = 0.0;
     let mut show_wipe = false;
     let mut last_action = ScreenAction::NoAction;
-    let mut maybe_popup: Option<PopUp> = None;
+    let mut controls = Controls::default();
 
     App::new()
         .window_size(1280, 720)
@@ -76,6 +98,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 Screens::GameScreen => screens::play_game_screen(
                     &mut game_state,
                     frame_context,
+                    &mut controls,
                     &mut palette,
                     &loaded_ppms,
                 ),
@@ -83,21 +106,47 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &mut game_state,
                     &mut win_image,
                     frame_context,
+                    &mut controls,
                     &palette,
                     &loaded_ppms,
                 ),
                 Screens::ChooseLevelScreen { page } => screens::level_select_screen(
-                    &levels,
-                    *page,
+                    LevelListing {
+                        levels: &levels,
+                        order: &level_order,
+                        page: *page,
+                    },
                     frame_context,
+                    &mut controls,
                     &mut game_state,
                     &mut win_image,
                     &mut current_level,
                     &loaded_ppms,
                     &palette,
                 ),
+                Screens::DailyScreen { date } => screens::daily_screen(
+                    *date,
+                    &daily_progress,
+                    frame_context,
+                    &mut controls,
+                    &palette,
+                    &loaded_ppms,
+                ),
                 _ => ScreenAction::NoAction,
             };
+            if let Screens::ChooseLevelScreen { .. } = current_screen {
+                current_daily = None;
+            }
+            if let ScreenAction::StartDaily { date } = action {
+                let puzzle = daily_puzzle(date);
+                game_state = (&puzzle.info).into();
+                win_image = puzzle.image;
+                current_level = std::path::PathBuf::new();
+                current_daily = Some(date);
+                action = ScreenAction::ChangeScreen {
+                    to: Screens::GameScreen,
+                };
+            }
             if show_wipe {
                 action = screens::wipe_screen(
                     &mut wipe_progress,
@@ -132,6 +181,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     transition_duration = duration;
                     show_wipe = false;
                     current_screen = *to.clone();
+                    controls.reset_cursor();
                 }
                 ScreenAction::NextPage => {
                     let Screens::ChooseLevelScreen { page } = current_screen else {
@@ -145,19 +195,49 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     };
                     current_screen = Screens::ChooseLevelScreen { page: page - 1 };
                 }
+                ScreenAction::ToggleLevelSort => {
+                    level_order.by_difficulty = !level_order.by_difficulty;
+                    current_screen = Screens::ChooseLevelScreen { page: 0 };
+                }
+                ScreenAction::NextDifficultyFilter => {
+                    level_order = level_order.next_filter();
+                    current_screen = Screens::ChooseLevelScreen { page: 0 };
+                }
+                ScreenAction::PreviousDay | ScreenAction::NextDay => {
+                    let Screens::DailyScreen { date } = current_screen else {
+                        panic!("screen was not daily {:?}", current_screen);
+                    };
+                    let date = match action {
+                        ScreenAction::PreviousDay => date.previous(),
+                        _ => date.next(),
+                    };
+                    current_screen = Screens::DailyScreen { date };
+                }
                 ScreenAction::MarkLevelComplete => {
                     let found_level = levels.iter_mut().find(|level| level.path == current_level);
-                    if let Some(played_level) = found_level {
-                        if !played_level.completed {
-                            match played_level.mark_completed() {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    maybe_popup = Some(PopUp {
-                                        heading: "Error".to_owned(),
-                                        msg: format!("There was a problem {}", error).to_owned(),
-                                        visible: true,
-                                    });
-                                }
+                    if let Some(date) = current_daily {
+                        let result = DayResult {
+                            hints_used: game_state.hints_used,
+                            mistakes: game_state.number_incorrect(),
+                        };
+                        if daily_progress.record(date, result)
+                            && let Err(error) = daily_progress.save(&daily_path)
+                        {
+                            maybe_popup = Some(PopUp {
+                                heading: "Error".to_owned(),
+                                msg: format!("There was a problem saving the daily {}", error),
+                                visible: true,
+                            });
+                        }
+                    } else if let Some(played_level) = found_level {
+                        match played_level.record_completion(game_state.hints_used) {
+                            Ok(_) => {}
+                            Err(error) => {
+                                maybe_popup = Some(PopUp {
+                                    heading: "Error".to_owned(),
+                                    msg: format!("There was a problem {}", error).to_owned(),
+                                    visible: true,
+                                });
                             }
                         }
                     }
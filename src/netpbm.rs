@@ -338,6 +338,30 @@ impl Ppm {
         result
     }
 
+    // Paints a mask's filled cells in a shade running from top to bottom, over
+    // a plain background. For puzzles that don't come with a picture.
+    pub fn shaded(mask: &Pbm, top: [u16; 3], bottom: [u16; 3], background: [u16; 3]) -> Ppm {
+        let shade = |r: usize| -> [u16; 3] {
+            let t = r as f32 / mask.height.max(2).saturating_sub(1) as f32;
+            let mix = |from: u16, to: u16| (from as f32 + (to as f32 - from as f32) * t).round();
+            [0, 1, 2].map(|channel| mix(top[channel], bottom[channel]) as u16)
+        };
+        Ppm {
+            width: mask.width,
+            height: mask.height,
+            max_value: 255,
+            cells: mask
+                .cells
+                .iter()
+                .enumerate()
+                .map(|(idx, &filled)| match filled {
+                    true => shade(idx / mask.width),
+                    false => background,
+                })
+                .collect(),
+        }
+    }
+
     pub fn to_rgba(&self, cell: [u16; 3]) -> [f32; 4] {
         let max = self.max_value;
         let r = cell[0] as f32 / max as f32;
@@ -745,3 +769,236 @@ mod ppm_tests {
         assert_eq!(rgba[3], 1.0);
     }
 }
+
+// Grayscale, but used by color puzzles as a palette-indexed mask: 0 is an
+// empty cell and anything else is the palette entry the cell is painted with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pgm {
+    pub width: usize,
+    pub height: usize,
+    pub max_value: u16,
+    pub cells: Vec<u16>,
+}
+
+impl Pgm {
+    pub fn rows(&self) -> Vec<Vec<u16>> {
+        let mut result = vec![];
+        for chunk in self.cells.chunks(self.width) {
+            result.push(chunk.to_vec());
+        }
+        result
+    }
+
+    pub fn cols(&self) -> Vec<Vec<u16>> {
+        let mut cols = vec![vec![]; self.width];
+        for row in self.rows() {
+            for (c, cell) in row.into_iter().enumerate() {
+                cols[c].push(cell);
+            }
+        }
+        cols
+    }
+
+    // Flattens the mask down to filled or not, which is all a monochrome view
+    // of the puzzle (thumbnails, the editor's grid) cares about.
+    pub fn to_pbm(&self) -> Pbm {
+        Pbm {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(|&cell| cell != 0).collect(),
+        }
+    }
+}
+
+pub type PgmResult<T> = Result<T, LoadPgmErr>;
+
+#[derive(Debug)]
+pub enum LoadPgmErr {
+    MissingHeader,
+    InvalidHeader { found: String },
+    MissingWidthError,
+    MissingHeightError,
+    InvalidWidthError { found: String, reason: String },
+    InvalidHeightError { found: String, reason: String },
+    InvalidMatrixSize { expected: usize, got: usize },
+    UnexpectedCellValue { found: String },
+    MissingMaxValueError,
+    InvalidMaxValueError { found: String, reason: String },
+}
+
+impl std::error::Error for LoadPgmErr {}
+
+impl std::fmt::Display for LoadPgmErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        use LoadPgmErr::*;
+        let s = match self {
+            MissingHeader => "missing expected header for pgm file (should be P2)".to_owned(),
+            InvalidHeader { found } => format!("invalid header found: {}", found),
+            MissingWidthError => "missing width in pgm file".to_owned(),
+            MissingHeightError => "missing height in pgm file".to_owned(),
+            InvalidWidthError { found, reason } => {
+                "invalid width of ".to_owned() + found + ": " + reason
+            }
+            InvalidHeightError { found, reason } => {
+                "invalid height of ".to_owned() + found + ": " + reason
+            }
+            InvalidMatrixSize { expected, got } => {
+                format!("invalid matrix cell, expected: {} got {}", expected, got)
+            }
+            UnexpectedCellValue { found } => format!("invalid pgm cell value: {}", found),
+            MissingMaxValueError => "missing max value in pgm file".to_owned(),
+            InvalidMaxValueError { found, reason } => {
+                "invalid max value of ".to_owned() + found + ": " + reason
+            }
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::fmt::Display for Pgm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "P2\n{} {}\n{}\n",
+            self.width, self.height, self.max_value
+        )?;
+        for cell in &self.cells {
+            writeln!(f, "{}", cell)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Pgm {
+    type Err = LoadPgmErr;
+    fn from_str(string: &str) -> PgmResult<Pgm> {
+        /* Ignore comment lines, but grab all the characters out otherwise. */
+        let mut characters = string
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(str::split_whitespace);
+
+        let header = characters.next().ok_or(LoadPgmErr::MissingHeader)?;
+        let "P2" = header else {
+            return Err(LoadPgmErr::InvalidHeader {
+                found: header.to_owned(),
+            });
+        };
+
+        let width = characters.next().ok_or(LoadPgmErr::MissingWidthError)?;
+        let width = width
+            .parse::<usize>()
+            .map_err(|e| LoadPgmErr::InvalidWidthError {
+                found: width.to_owned(),
+                reason: e.to_string(),
+            })?;
+
+        let height = characters.next().ok_or(LoadPgmErr::MissingHeightError)?;
+        let height = height
+            .parse::<usize>()
+            .map_err(|e| LoadPgmErr::InvalidHeightError {
+                found: height.to_owned(),
+                reason: e.to_string(),
+            })?;
+
+        let max_value = characters.next().ok_or(LoadPgmErr::MissingMaxValueError)?;
+        let max_value = max_value
+            .parse::<u16>()
+            .map_err(|e| LoadPgmErr::InvalidMaxValueError {
+                found: max_value.to_owned(),
+                reason: e.to_string(),
+            })?;
+
+        let cells: Vec<u16> = characters
+            .map(|c| match c.parse::<u16>() {
+                Ok(value) if value <= max_value => Ok(value),
+                _ => Err(LoadPgmErr::UnexpectedCellValue {
+                    found: c.to_owned(),
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+
+        let expected_count = width * height;
+        if cells.len() != expected_count {
+            return Err(LoadPgmErr::InvalidMatrixSize {
+                expected: expected_count,
+                got: cells.len(),
+            });
+        }
+
+        Ok(Pgm {
+            width,
+            height,
+            max_value,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod pgm_tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    #[test]
+    fn can_load_palette_indexed_mask() {
+        let data = "P2\n# two colors\n3 2\n2\n0 1 1\n2 2 0\n";
+        let result: PgmResult<Pgm> = data.parse();
+        if let Ok(pgm) = result {
+            assert_eq!(pgm.width, 3);
+            assert_eq!(pgm.height, 2);
+            assert_eq!(pgm.max_value, 2);
+            assert_eq!(pgm.cells, vec![
+                0, 1, 1,
+                2, 2, 0,
+            ]);
+            assert_eq!(pgm.cols()[0], vec![0, 2]);
+            assert_eq!(pgm.to_pbm().cells, vec![
+                false, true, true,
+                true , true, false,
+            ]);
+        } else {
+            panic!("Failed to load PGM file, got {:?}", result);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let pgm = Pgm {
+            width: 2,
+            height: 1,
+            max_value: 3,
+            cells: vec![3, 0],
+        };
+        let result: PgmResult<Pgm> = pgm.to_string().parse();
+        assert_eq!(Some(pgm), result.ok());
+    }
+
+    #[test]
+    fn fails_to_load_bad_header() {
+        let data = "P1\n1 1\n1";
+        let result: PgmResult<Pgm> = data.parse();
+        match result {
+            Err(LoadPgmErr::InvalidHeader { found }) => {
+                assert_eq!(found, "P1");
+            }
+            weird => {
+                panic!("Should not have parsed: {:?}", weird);
+            }
+        }
+    }
+
+    #[test]
+    fn fails_to_load_index_past_max_value() {
+        let data = "P2\n1 1\n1\n2";
+        let result: PgmResult<Pgm> = data.parse();
+        match result {
+            Err(LoadPgmErr::UnexpectedCellValue { found }) => {
+                assert_eq!(found, "2");
+            }
+            weird => {
+                panic!("Should not have parsed: {:?}", weird);
+            }
+        }
+    }
+}
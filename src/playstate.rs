@@ -1,10 +1,14 @@
-use crate::netpbm::Pbm;
+use crate::hints::{HINTS_PER_PUZZLE, Hint, next_hint};
+use crate::netpbm::{Pbm, Pgm};
 use std::iter::zip;
 
 #[derive(Debug, PartialEq)]
 pub struct Group {
     pub num_cells: usize,
     pub filled: bool,
+    // Palette index of the group, always 1 for monochrome puzzles and 0 for
+    // the placeholder clue of an empty line.
+    pub color: usize,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -61,14 +65,63 @@ impl CellState {
 type PlayerSetState = CellState;
 type GoalState = CellState;
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StrokeKind {
+    Fill,
+    Mark,
+}
+
+// What a stroke does to every cell it passes over. Captured once when the
+// stroke begins so that dragging over already marked cells doesn't flip them.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum StrokeIntent {
+    Fill,
+    Mark,
+    Unmark,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StrokeAxis {
+    Row,
+    Column,
+}
+
+#[derive(Debug)]
+pub struct Stroke {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub axis: Option<StrokeAxis>,
+    intent: StrokeIntent,
+    touched: Vec<bool>,
+}
+
+impl Stroke {
+    pub fn length(&self) -> usize {
+        let (start_row, start_column) = self.start;
+        let (end_row, end_column) = self.end;
+        start_row.abs_diff(end_row) + start_column.abs_diff(end_column) + 1
+    }
+}
+
 #[derive(Debug)]
 pub struct PlayState {
     cells: Vec<CellState>,
     pub column_groups: Vec<Vec<Group>>,
     pub row_groups: Vec<Vec<Group>>,
     goal_state: Vec<CellState>,
+    // Palette index the goal is painted with per cell, 0 for empty ones
+    goal_colors: Vec<usize>,
+    // Colors by palette index - 1. Empty for monochrome puzzles, which just
+    // use whatever fill color the UI likes.
+    pub palette: Vec<[f32; 4]>,
+    pub active_color: usize,
+    color_mistakes: usize,
     pub num_rows: usize,
     pub num_columns: usize,
+    pub stroke: Option<Stroke>,
+    pub hint: Option<Hint>,
+    pub hints_remaining: usize,
+    pub hints_used: usize,
 }
 
 // Simple, not full fields display, of PlayState for easy test debugging
@@ -95,7 +148,7 @@ impl std::fmt::Display for PlayState {
         writeln!(f, "goals:")?;
         for r in 0..self.num_rows {
             for c in 0..self.num_columns {
-                write!(f, "{:>10?} ", self.goal_state[r * self.num_rows + c])?;
+                write!(f, "{:>10?} ", self.goal_state[self.offset(r, c)])?;
             }
             writeln!(f)?;
         }
@@ -105,25 +158,128 @@ impl std::fmt::Display for PlayState {
 
 impl From<&Pbm> for PlayState {
     fn from(pbm: &Pbm) -> PlayState {
+        let to_color = |line: Vec<bool>| line.into_iter().map(usize::from).collect();
+        let goal_colors = pbm
+            .cells
+            .iter()
+            .map(|&filled| usize::from(filled))
+            .collect();
+        let rows: Vec<Vec<usize>> = pbm.rows().into_iter().map(to_color).collect();
+        let cols: Vec<Vec<usize>> = pbm.cols().into_iter().map(to_color).collect();
+        PlayState::new(pbm.width, pbm.height, goal_colors, &rows, &cols, vec![])
+    }
+}
+
+impl PlayState {
+    // A color puzzle, where the mask holds a palette index for every cell
+    pub fn painted(mask: &Pgm, palette: Vec<[f32; 4]>) -> PlayState {
+        let to_color = |line: Vec<u16>| line.into_iter().map(usize::from).collect();
+        let goal_colors = mask.cells.iter().map(|&cell| usize::from(cell)).collect();
+        let rows: Vec<Vec<usize>> = mask.rows().into_iter().map(to_color).collect();
+        let cols: Vec<Vec<usize>> = mask.cols().into_iter().map(to_color).collect();
+        PlayState::new(mask.width, mask.height, goal_colors, &rows, &cols, palette)
+    }
+
+    // A puzzle only known by its (count, color) clues. There's no picture to
+    // check moves against, so it's for the solver rather than for playing.
+    pub fn unpainted(
+        row_clues: &[Vec<(usize, usize)>],
+        column_clues: &[Vec<(usize, usize)>],
+        palette: Vec<[f32; 4]>,
+    ) -> PlayState {
+        let to_groups = |clues: &[Vec<(usize, usize)>]| -> Vec<Vec<Group>> {
+            clues
+                .iter()
+                .map(|clue| match clue.is_empty() {
+                    true => vec![Group {
+                        num_cells: 0,
+                        filled: true,
+                        color: 0,
+                    }],
+                    false => clue
+                        .iter()
+                        .map(|&(num_cells, color)| Group {
+                            num_cells,
+                            filled: false,
+                            color,
+                        })
+                        .collect(),
+                })
+                .collect()
+        };
+        let (width, height) = (column_clues.len(), row_clues.len());
+        let mut play_state =
+            PlayState::new(width, height, vec![0; width * height], &[], &[], palette);
+        play_state.row_groups = to_groups(row_clues);
+        play_state.column_groups = to_groups(column_clues);
+        play_state
+    }
+
+    fn new(
+        width: usize,
+        height: usize,
+        goal_colors: Vec<usize>,
+        rows: &[Vec<usize>],
+        cols: &[Vec<usize>],
+        palette: Vec<[f32; 4]>,
+    ) -> PlayState {
         PlayState {
-            cells: pbm.cells.iter().map(|_| CellState::Empty).collect(),
-            goal_state: pbm
-                .cells
+            cells: vec![CellState::Empty; goal_colors.len()],
+            goal_state: goal_colors
                 .iter()
-                .map(|filled| match filled {
-                    true => CellState::Filled,
-                    false => CellState::Empty,
+                .map(|&color| match color {
+                    0 => CellState::Empty,
+                    _ => CellState::Filled,
                 })
                 .collect(),
-            column_groups: groups(&pbm.cols()),
-            row_groups: groups(&pbm.rows()),
-            num_rows: pbm.height,
-            num_columns: pbm.width,
+            goal_colors,
+            palette,
+            active_color: 1,
+            color_mistakes: 0,
+            column_groups: groups(cols),
+            row_groups: groups(rows),
+            num_rows: height,
+            num_columns: width,
+            stroke: None,
+            hint: None,
+            hints_remaining: HINTS_PER_PUZZLE,
+            hints_used: 0,
         }
     }
 }
 
 impl PlayState {
+    fn offset(&self, row: usize, column: usize) -> usize {
+        row * self.num_columns + column
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> CellState {
+        self.cells[self.offset(row, column)]
+    }
+
+    pub fn goal(&self, row: usize, column: usize) -> CellState {
+        self.goal_state[self.offset(row, column)]
+    }
+
+    pub fn goal_color(&self, row: usize, column: usize) -> usize {
+        self.goal_colors[self.offset(row, column)]
+    }
+
+    // The color of every cell row by row, 0 where it's empty
+    pub fn picture(&self) -> &[usize] {
+        &self.goal_colors
+    }
+
+    pub fn is_color(&self) -> bool {
+        !self.palette.is_empty()
+    }
+
+    pub fn select_color(&mut self, color: usize) {
+        if (1..=self.palette.len()).contains(&color) {
+            self.active_color = color;
+        }
+    }
+
     pub fn rows(&self) -> Vec<Vec<CellState>> {
         let mut result = vec![];
         for chunk in self.cells.chunks(self.num_columns) {
@@ -182,30 +338,196 @@ impl PlayState {
         if row >= self.num_rows || column >= self.num_columns {
             return;
         }
-        let offset = row * self.num_rows + column;
-        let goal = self.goal_state[offset];
-        self.cells[offset] = self.cells[offset].attempt_fill(goal);
+        let offset = self.offset(row, column);
+        self.fill_with(offset, self.active_color);
+    }
+
+    // Filling a cell with the wrong color still shows its real color, since
+    // the player has found a painted cell, but it counts against them like
+    // filling an empty one would.
+    fn fill_with(&mut self, offset: usize, color: usize) {
+        let cell = self.cells[offset];
+        let filled = cell.attempt_fill(self.goal_state[offset]);
+        let wrong_color = self.goal_colors[offset] != color;
+        if cell == CellState::Empty && filled == CellState::Filled && wrong_color {
+            self.color_mistakes += 1;
+        }
+        self.cells[offset] = filled;
     }
 
     pub fn mark_cell(&mut self, row: usize, column: usize) {
         if row >= self.num_rows || column >= self.num_columns {
             return;
         }
-        let offset = row * self.num_rows + column;
+        let offset = self.offset(row, column);
         self.cells[offset] = self.cells[offset].mark_cell();
     }
 
+    pub fn begin_stroke(&mut self, row: usize, column: usize, kind: StrokeKind) {
+        if row >= self.num_rows || column >= self.num_columns {
+            return;
+        }
+        let intent = match (kind, self.cells[self.offset(row, column)]) {
+            (StrokeKind::Fill, _) => StrokeIntent::Fill,
+            (StrokeKind::Mark, CellState::UserRuledOut) => StrokeIntent::Unmark,
+            (StrokeKind::Mark, _) => StrokeIntent::Mark,
+        };
+        if let Some(Hint::Mistake { .. }) = self.hint {
+            self.hint = None;
+        }
+        self.stroke = Some(Stroke {
+            start: (row, column),
+            end: (row, column),
+            axis: None,
+            intent,
+            touched: vec![false; self.cells.len()],
+        });
+        self.apply_stroke_to(row, column);
+    }
+
+    pub fn extend_stroke(&mut self, row: usize, column: usize) {
+        if row >= self.num_rows || column >= self.num_columns {
+            return;
+        }
+        let Some(stroke) = self.stroke.as_mut() else {
+            return;
+        };
+        let (start_row, start_column) = stroke.start;
+        if stroke.axis.is_none() && (row, column) != stroke.start {
+            // Lock onto whichever direction the player moved furthest in first,
+            // a diagonal wobble shouldn't smear the stroke across rows.
+            let axis = if start_row.abs_diff(row) > start_column.abs_diff(column) {
+                StrokeAxis::Column
+            } else {
+                StrokeAxis::Row
+            };
+            stroke.axis = Some(axis);
+        }
+        let end = match stroke.axis {
+            None => stroke.start,
+            Some(StrokeAxis::Row) => (start_row, column),
+            Some(StrokeAxis::Column) => (row, start_column),
+        };
+        stroke.end = end;
+
+        // Fill in everything between the start and the cursor in case the
+        // mouse moved faster than one cell per frame.
+        let cells_in_run: Vec<(usize, usize)> = match stroke.axis {
+            None => vec![end],
+            Some(StrokeAxis::Row) => (start_column.min(end.1)..=start_column.max(end.1))
+                .map(|c| (start_row, c))
+                .collect(),
+            Some(StrokeAxis::Column) => (start_row.min(end.0)..=start_row.max(end.0))
+                .map(|r| (r, start_column))
+                .collect(),
+        };
+        for (r, c) in cells_in_run {
+            self.apply_stroke_to(r, c);
+        }
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.stroke = None;
+    }
+
+    fn apply_stroke_to(&mut self, row: usize, column: usize) {
+        let offset = self.offset(row, column);
+        let Some(stroke) = self.stroke.as_mut() else {
+            return;
+        };
+        if stroke.touched[offset] {
+            return;
+        }
+        stroke.touched[offset] = true;
+
+        match (stroke.intent, self.cells[offset]) {
+            (StrokeIntent::Fill, _) => self.fill_with(offset, self.active_color),
+            (StrokeIntent::Mark, CellState::Empty) => self.cells[offset] = CellState::UserRuledOut,
+            (StrokeIntent::Unmark, CellState::UserRuledOut) => {
+                self.cells[offset] = CellState::Empty
+            }
+            _ => {}
+        }
+    }
+
+    pub fn request_hint(&mut self) {
+        // The hint on screen is still the one to act on, so asking again
+        // (say, a double click) doesn't cost another
+        self.clear_stale_hint();
+        if self.hints_remaining == 0 || self.hint.is_some() {
+            return;
+        }
+        self.hint = next_hint(self);
+        if self.hint.is_some() {
+            self.hints_remaining -= 1;
+            self.hints_used += 1;
+        }
+    }
+
+    pub fn apply_hint(&mut self) {
+        let Some(Hint::Deduction { cells, .. }) = self.hint.take() else {
+            return;
+        };
+        for (row, column, color) in cells {
+            let offset = self.offset(row, column);
+            if color != 0 {
+                self.fill_with(offset, color);
+            } else if self.cells[offset] == CellState::Empty {
+                self.cells[offset] = CellState::RuledOut;
+            }
+        }
+    }
+
+    // Once the player has dealt with every cell a hint pointed at there's
+    // no reason to keep drawing it.
+    fn clear_stale_hint(&mut self) {
+        let Some(Hint::Deduction { cells, .. }) = &self.hint else {
+            return;
+        };
+        let resolved = cells
+            .iter()
+            .all(|&(row, column, _)| self.cell(row, column) != CellState::Empty);
+        if resolved {
+            self.hint = None;
+        }
+    }
+
     pub fn number_incorrect(&self) -> usize {
-        self.cells
+        let incorrect = self
+            .cells
             .iter()
             .filter(|&&cell| cell == CellState::Incorrect)
-            .count()
+            .count();
+        incorrect + self.color_mistakes
+    }
+
+    // The player's cells paired with the color each one is painted. Fills
+    // are always the goal's color, so it's safe to take it from there.
+    fn painted_rows(&self) -> Vec<Vec<(CellState, usize)>> {
+        let pairs: Vec<(CellState, usize)> =
+            zip(self.cells.clone(), self.goal_colors.clone()).collect();
+        pairs
+            .chunks(self.num_columns)
+            .map(|chunk| chunk.to_vec())
+            .collect()
+    }
+
+    fn painted_cols(&self) -> Vec<Vec<(CellState, usize)>> {
+        let rows = self.painted_rows();
+        (0..self.num_columns)
+            .map(|c| rows.iter().map(|row| row[c]).collect())
+            .collect()
     }
 
     pub fn update_groups(&mut self) {
-        self.row_groups = groups_from_goal_pairs(&self.row_goal_pairs());
-        self.column_groups = groups_from_goal_pairs(&self.column_goal_pairs());
+        for (cells, groups) in zip(self.painted_rows(), self.row_groups.iter_mut()) {
+            cross_off_groups(&cells, groups);
+        }
+        for (cells, groups) in zip(self.painted_cols(), self.column_groups.iter_mut()) {
+            cross_off_groups(&cells, groups);
+        }
         self.fill_in_completed_groups();
+        self.clear_stale_hint();
     }
 
     fn fill_in_completed_groups(&mut self) {
@@ -224,7 +546,8 @@ impl PlayState {
 
             for (column, (state, goal)) in row_pairs[row].iter().enumerate() {
                 let new_value = state.to_goal(*goal);
-                self.cells[row * self.num_rows + column] = new_value;
+                let offset = self.offset(row, column);
+                self.cells[offset] = new_value;
             }
         }
     }
@@ -240,22 +563,38 @@ impl PlayState {
 
             for (row, (state, goal)) in column_pairs[column].iter().enumerate() {
                 let new_value = state.to_goal(*goal);
-                self.cells[row * self.num_rows + column] = new_value;
+                let offset = self.offset(row, column);
+                self.cells[offset] = new_value;
             }
         }
     }
 }
 
-fn groups(cells: &[Vec<bool>]) -> Vec<Vec<Group>> {
-    cells
+// Runs of the same color within a line, as (length, color). 0 is empty.
+pub fn color_runs(line: &[usize]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    let mut previous = 0;
+    for &color in line {
+        match runs.last_mut() {
+            Some((length, _)) if color != 0 && color == previous => *length += 1,
+            _ if color != 0 => runs.push((1, color)),
+            _ => {}
+        }
+        previous = color;
+    }
+    runs
+}
+
+fn groups(lines: &[Vec<usize>]) -> Vec<Vec<Group>> {
+    lines
         .iter()
-        .map(|row| {
-            let groups: Vec<Group> = row
-                .split(|boolean| !*boolean)
-                .filter(|v| !v.is_empty())
-                .map(|run| Group {
-                    num_cells: run.len(),
+        .map(|line| {
+            let groups: Vec<Group> = color_runs(line)
+                .into_iter()
+                .map(|(num_cells, color)| Group {
+                    num_cells,
                     filled: false,
+                    color,
                 })
                 .collect();
 
@@ -263,6 +602,7 @@ fn groups(cells: &[Vec<bool>]) -> Vec<Vec<Group>> {
                 vec![Group {
                     num_cells: 0,
                     filled: true,
+                    color: 0,
                 }]
             } else {
                 groups
@@ -271,31 +611,85 @@ fn groups(cells: &[Vec<bool>]) -> Vec<Vec<Group>> {
         .collect()
 }
 
-fn groups_from_goal_pairs(
-    state_and_goal_pairs: &[Vec<(PlayerSetState, GoalState)>],
-) -> Vec<Vec<Group>> {
-    state_and_goal_pairs
+fn is_known_empty(cell: &CellState) -> bool {
+    matches!(
+        cell,
+        CellState::Incorrect | CellState::UserRuledOut | CellState::RuledOut
+    )
+}
+
+fn cross_off_groups(cells: &[(CellState, usize)], groups: &mut [Group]) {
+    let clues: Vec<(usize, usize)> = groups.iter().map(|g| (g.num_cells, g.color)).collect();
+    for (group, crossed) in groups.iter_mut().zip(crossed_off_clues(cells, &clues)) {
+        group.filled = crossed;
+    }
+}
+
+// Works out which clues the player has provably satisfied with what they've
+// filled and ruled out so far. Cells and clues both come with their color.
+// This deliberately doesn't peek at the goal beyond the color of a fill.
+fn crossed_off_clues(cells: &[(CellState, usize)], clues: &[(usize, usize)]) -> Vec<bool> {
+    if clues.iter().all(|&(size, _)| size == 0) {
+        return vec![true; clues.len()];
+    }
+
+    // The game never lets a wrong fill stick, so if the filled runs are
+    // exactly the clues then the line is done, marked out or not.
+    let filled: Vec<usize> = cells
         .iter()
-        .map(|row| {
-            if row.iter().all(|(_, goal)| *goal == CellState::Empty) {
-                return vec![Group {
-                    num_cells: 0,
-                    filled: true,
-                }];
-            }
+        .map(|&(cell, color)| if cell == CellState::Filled { color } else { 0 })
+        .collect();
+    if color_runs(&filled) == clues {
+        return vec![true; clues.len()];
+    }
 
-            let groups: Vec<Group> = row
-                .split(|(_, goal)| *goal == CellState::Empty)
-                .filter(|v| !v.is_empty())
-                .map(|run| Group {
-                    num_cells: run.len(),
-                    filled: run.iter().all(|(state, _)| *state == CellState::Filled),
-                })
-                .collect();
+    let mut crossed = vec![false; clues.len()];
+    cross_off_from_edge(cells.iter(), clues.iter().enumerate(), &mut crossed);
+    cross_off_from_edge(
+        cells.iter().rev(),
+        clues.iter().enumerate().rev(),
+        &mut crossed,
+    );
+    crossed
+}
 
-            groups
-        })
-        .collect()
+// Walks in from one edge of the line matching runs to clues for as long as
+// every cell along the way is known. A run touching the known part of the
+// line has to belong to the next clue, so once it's as long as that clue
+// it's satisfied, even if the far side of it hasn't been marked yet. A run
+// that bumps into a different color is just as closed as one that hits a mark.
+fn cross_off_from_edge<'a>(
+    cells: impl Iterator<Item = &'a (CellState, usize)>,
+    clues: impl Iterator<Item = (usize, &'a (usize, usize))>,
+    crossed: &mut [bool],
+) {
+    let mut cells = cells.peekable();
+    for (idx, &(clue, clue_color)) in clues {
+        while cells.next_if(|(cell, _)| is_known_empty(cell)).is_some() {}
+
+        let Some(&&(CellState::Filled, color)) = cells.peek() else {
+            // Either an unknown cell or the end of the line, we can't go further.
+            return;
+        };
+        let mut run = 0;
+        while cells
+            .next_if(|&&(cell, c)| cell == CellState::Filled && c == color)
+            .is_some()
+        {
+            run += 1;
+        }
+
+        let matches = run == clue && color == clue_color;
+        if matches {
+            crossed[idx] = true;
+        }
+        let closed = cells
+            .peek()
+            .is_none_or(|(cell, _)| is_known_empty(cell) || *cell == CellState::Filled);
+        if !closed || !matches {
+            return;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -322,6 +716,7 @@ mod pbm_tests {
         vec![Group {
             num_cells: 0,
             filled: true,
+            color: 0,
         }]
     }
 
@@ -331,10 +726,34 @@ mod pbm_tests {
             .map(|count| Group {
                 num_cells: *count,
                 filled: false,
+                color: 1,
             })
             .collect()
     }
 
+    // Monochrome cells and clues for crossed_off_clues, everything in color 1
+    fn mono(cells: &[CellState]) -> Vec<(CellState, usize)> {
+        cells.iter().map(|&cell| (cell, 1)).collect()
+    }
+
+    fn mono_clues(clues: &[usize]) -> Vec<(usize, usize)> {
+        clues.iter().map(|&clue| (clue, 1)).collect()
+    }
+
+    #[rustfmt::skip]
+    fn test_color_state() -> PlayState {
+        let mask = Pgm {
+            width: 4,
+            height: 2,
+            max_value: 2,
+            cells: vec![
+                1, 1, 2, 0,
+                0, 2, 2, 1,
+            ],
+        };
+        PlayState::painted(&mask, vec![[1., 0., 0., 1.], [0., 0., 1., 1.]])
+    }
+
     #[test]
     #[rustfmt::skip]
     fn constructs_row_groups_correctly() {
@@ -540,4 +959,182 @@ mod pbm_tests {
         }
         assert_eq!(count, state.number_incorrect());
     }
+
+    #[test]
+    fn dragging_a_mark_does_not_toggle_cells_back_each_frame() {
+        let mut state = test_play_state();
+        state.begin_stroke(0, 0, StrokeKind::Mark);
+        state.extend_stroke(0, 0);
+        state.extend_stroke(0, 1);
+        state.extend_stroke(0, 1);
+        state.extend_stroke(0, 1);
+        state.end_stroke();
+        assert_eq!(CellState::UserRuledOut, state.cells[0]);
+        assert_eq!(CellState::UserRuledOut, state.cells[1]);
+    }
+
+    #[test]
+    fn mark_stroke_starting_on_a_mark_only_clears_marks() {
+        use CellState::*;
+        let mut state = test_play_state();
+        state.cells[0] = UserRuledOut;
+        state.cells[2] = UserRuledOut;
+        state.begin_stroke(0, 0, StrokeKind::Mark);
+        state.extend_stroke(0, 3);
+        state.end_stroke();
+        assert_eq!(vec![Empty, Empty, Empty, Empty, Empty], state.rows()[0]);
+    }
+
+    #[test]
+    fn stroke_locks_to_the_first_axis_moved() {
+        let mut state = test_play_state();
+        state.begin_stroke(2, 0, StrokeKind::Fill);
+        state.extend_stroke(2, 1);
+        // wobbling down a row should keep filling along row 2
+        state.extend_stroke(3, 2);
+        assert_eq!(Some(StrokeAxis::Row), state.stroke.as_ref().unwrap().axis);
+        assert_eq!((2, 2), state.stroke.as_ref().unwrap().end);
+        assert_eq!(3, state.stroke.as_ref().unwrap().length());
+        state.end_stroke();
+
+        assert_eq!(CellState::Filled, state.rows()[2][2]);
+        assert_eq!(CellState::Empty, state.rows()[3][2]);
+        assert!(state.stroke.is_none());
+    }
+
+    #[test]
+    fn stroke_fills_cells_skipped_between_frames() {
+        let mut state = test_play_state();
+        state.begin_stroke(1, 4, StrokeKind::Fill);
+        state.extend_stroke(4, 4);
+        assert_eq!(
+            Some(StrokeAxis::Column),
+            state.stroke.as_ref().unwrap().axis
+        );
+        assert_eq!(4, state.stroke.as_ref().unwrap().length());
+        for row in 1..=4 {
+            assert_eq!(CellState::Filled, state.rows()[row][4]);
+        }
+    }
+
+    #[test]
+    fn clues_are_crossed_from_either_edge() {
+        use CellState::*;
+        let cells = [Filled, Filled, Empty, Empty, Empty, Empty, Filled];
+        assert_eq!(
+            vec![true, false, true],
+            crossed_off_clues(&mono(&cells), &mono_clues(&[2, 1, 1]))
+        );
+    }
+
+    #[test]
+    fn clues_are_not_crossed_until_the_run_is_long_enough() {
+        use CellState::*;
+        let cells = [Filled, Empty, Empty, Empty, Empty];
+        assert_eq!(
+            vec![false, false],
+            crossed_off_clues(&mono(&cells), &mono_clues(&[2, 1]))
+        );
+    }
+
+    #[test]
+    fn marks_let_the_scan_carry_on_past_a_finished_run() {
+        use CellState::*;
+        let cells = [
+            UserRuledOut,
+            Filled,
+            RuledOut,
+            Filled,
+            Filled,
+            Empty,
+            Empty,
+            Empty,
+        ];
+        assert_eq!(
+            vec![true, true, false],
+            crossed_off_clues(&mono(&cells), &mono_clues(&[1, 2, 1]))
+        );
+    }
+
+    #[test]
+    fn runs_in_the_middle_of_unknown_cells_are_not_crossed() {
+        use CellState::*;
+        // the filled cell could be either 1, so neither can be crossed off
+        let cells = [Empty, Empty, Filled, Empty, Empty];
+        assert_eq!(
+            vec![false, false],
+            crossed_off_clues(&mono(&cells), &mono_clues(&[1, 1]))
+        );
+    }
+
+    #[test]
+    fn a_run_cut_short_by_a_mark_stops_the_scan() {
+        use CellState::*;
+        let cells = [Filled, UserRuledOut, Filled, UserRuledOut, Empty];
+        assert_eq!(
+            vec![false, false],
+            crossed_off_clues(&mono(&cells), &mono_clues(&[2, 1]))
+        );
+    }
+
+    #[test]
+    fn matching_every_run_crosses_off_the_whole_line() {
+        use CellState::*;
+        let cells = [Empty, Filled, Empty, Filled, Filled];
+        assert_eq!(
+            vec![true, true],
+            crossed_off_clues(&mono(&cells), &mono_clues(&[1, 2]))
+        );
+    }
+
+    #[test]
+    fn color_runs_split_where_the_color_changes() {
+        let state = test_color_state();
+        let groups: Vec<(usize, usize)> = state.row_groups[0]
+            .iter()
+            .map(|g| (g.num_cells, g.color))
+            .collect();
+        assert_eq!(vec![(2, 1), (1, 2)], groups);
+        let groups: Vec<(usize, usize)> = state.row_groups[1]
+            .iter()
+            .map(|g| (g.num_cells, g.color))
+            .collect();
+        assert_eq!(vec![(2, 2), (1, 1)], groups);
+    }
+
+    #[test]
+    fn filling_with_the_wrong_color_counts_as_a_mistake() {
+        let mut state = test_color_state();
+        state.select_color(2);
+        state.attempt_fill(0, 0);
+        assert_eq!(CellState::Filled, state.cell(0, 0));
+        assert_eq!(1, state.number_incorrect());
+
+        state.attempt_fill(0, 2);
+        assert_eq!(1, state.number_incorrect());
+
+        // There's no third color to pick
+        state.select_color(3);
+        assert_eq!(2, state.active_color);
+    }
+
+    #[test]
+    fn touching_runs_of_different_colors_are_both_crossed() {
+        use CellState::*;
+        let cells = [(Filled, 1), (Filled, 1), (Filled, 2), (Empty, 0)];
+        assert_eq!(
+            vec![true, true],
+            crossed_off_clues(&cells, &[(2, 1), (1, 2)])
+        );
+    }
+
+    #[test]
+    fn a_run_of_the_wrong_color_is_not_crossed() {
+        use CellState::*;
+        let cells = [(Filled, 2), (Filled, 2), (Empty, 0), (Empty, 0)];
+        assert_eq!(
+            vec![false, false],
+            crossed_off_clues(&cells, &[(2, 1), (2, 2)])
+        );
+    }
 }
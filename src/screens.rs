@@ -1,17 +1,22 @@
+use crate::controls::{Controls, FocusGrid, InputFrame, InputSource, color_key_pressed};
+use crate::daily::{DailyProgress, Date};
+use crate::editor::difficulty::DifficultyBand;
+use crate::generator::{PuzzleRequest, generate};
 use crate::levels::Level;
 use crate::netpbm::Ppm;
 use crate::playstate::PlayState;
 use crate::ui::{
-    ColorPalette, GridLayout, LoadedPpms, PlayArea, PlayerInput, draw_centered_text, draw_ppm_at,
-    draw_quit_button, draw_x_at, world_unit_size,
+    ColorPalette, GridLayout, LoadedPpms, PlayArea, draw_centered_text, draw_ppm_at,
+    draw_quit_button, draw_swatch_button, draw_text_button, draw_x_at, mouse_in_world,
+    world_unit_size,
 };
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use egor::math::Rect;
 use egor::{
     app::FrameContext,
     app::egui::lerp,
-    input::MouseButton,
     math::{Vec2, vec2},
     render::Color,
 };
@@ -28,6 +33,9 @@ pub enum Screens {
     ChooseLevelScreen {
         page: usize,
     },
+    DailyScreen {
+        date: Date,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -40,12 +48,64 @@ pub enum ScreenAction {
     PreviousPage,
     NextPage,
     MarkLevelComplete,
+    ToggleLevelSort,
+    NextDifficultyFilter,
+    PreviousDay,
+    NextDay,
+    StartDaily { date: Date },
     QuitGame,
 }
 
+// Which levels the level select shows and in what order
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LevelOrder {
+    pub by_difficulty: bool,
+    pub only: Option<DifficultyBand>,
+}
+
+impl LevelOrder {
+    pub fn apply<'a>(&self, levels: &'a [Level]) -> Vec<&'a Level> {
+        let mut shown: Vec<&Level> = levels
+            .iter()
+            .filter(|level| match self.only {
+                None => true,
+                Some(band) => level
+                    .difficulty
+                    .is_some_and(|rating| DifficultyBand::of(rating) == band),
+            })
+            .collect();
+        if self.by_difficulty {
+            // Unrated levels go last, and the sort is stable so ties keep file order
+            shown.sort_by_key(|level| level.difficulty.unwrap_or(u32::MAX));
+        }
+        shown
+    }
+
+    // Cycles through showing everything, then each band in turn
+    pub fn next_filter(&self) -> LevelOrder {
+        let only = match self.only {
+            None => Some(DifficultyBand::ALL[0]),
+            Some(band) => DifficultyBand::ALL
+                .iter()
+                .skip_while(|&&b| b != band)
+                .nth(1)
+                .copied(),
+        };
+        LevelOrder { only, ..*self }
+    }
+}
+
+// The level select's view of the levels, sorted and filtered down to one page
+pub struct LevelListing<'a> {
+    pub levels: &'a [Level],
+    pub order: &'a LevelOrder,
+    pub page: usize,
+}
+
 pub fn play_game_screen(
     game_state: &mut PlayState,
     frame_context: &mut FrameContext,
+    controls: &mut Controls,
     palette: &ColorPalette,
     loaded_ppms: &LoadedPpms,
 ) -> ScreenAction {
@@ -69,7 +129,12 @@ pub fn play_game_screen(
     let instruction_text_position = unit_size * vec2(1., 1.);
     let font_size = 18;
 
-    let player_input = PlayerInput::from(input, gfx);
+    let hint_btn = Rect::new(unit_size * vec2(1., 14.), unit_size * vec2(4., 1.5));
+    let apply_hint_btn = Rect::new(unit_size * vec2(6., 14.), unit_size * vec2(4., 1.5));
+    let swatches: Vec<Rect> = (0..game_state.palette.len())
+        .map(|i| Rect::new(unit_size * vec2(1. + i as f32 * 1.25, 16.), unit_size))
+        .collect();
+
     let play_area = PlayArea {
         top_left: bg_position,
         size: Vec2::splat(bg_size as f32),
@@ -77,8 +142,36 @@ pub fn play_game_screen(
         palette: *palette,
     };
 
+    let mut focus = FocusGrid::default();
+    let mut buttons = vec![hint_btn, apply_hint_btn];
+    buttons.extend(&swatches);
+    buttons.push(Rect::new(quit_position, quit_btn_size));
+    focus.push_row(buttons);
+    for row in play_area.cell_rects(game_state) {
+        focus.push_row(row);
+    }
+    let player_input = controls.next_input(&InputFrame {
+        input,
+        mouse_position: mouse_in_world(input, gfx),
+        delta: frame_context.timer.delta,
+        focus: &focus,
+    });
+
+    if let Some(color) = color_key_pressed(input) {
+        game_state.select_color(color);
+    }
+    for (i, &rect) in swatches.iter().enumerate() {
+        let color = i + 1;
+        let selected = game_state.active_color == color;
+        let paint = game_state.palette[i];
+        if draw_swatch_button(rect, paint, selected, palette, &player_input, gfx) {
+            game_state.select_color(color);
+        }
+    }
+
     play_area.draw_backgrounds(&game_state, &player_input, gfx);
     play_area.draw_grid(game_state, &player_input, gfx);
+    play_area.draw_hint(game_state, gfx);
     play_area.draw_row_groups(&game_state, gfx);
     play_area.draw_column_groups(&game_state, gfx);
     game_state.update_groups();
@@ -92,7 +185,9 @@ pub fn play_game_screen(
     gfx.rect()
         .at(mouse_left_position + vec2(mouse_left_size.x, 0.))
         .size(mouse_left_size)
-        .color(Color::new(palette.cell_filled_in));
+        .color(Color::new(
+            play_area.paint_color(game_state, game_state.active_color),
+        ));
     draw_ppm_at(
         &loaded_ppms.mouse_right,
         mouse_right_position,
@@ -106,7 +201,7 @@ pub fn play_game_screen(
         gfx,
     );
 
-    let instructions = [
+    let mut instructions = vec![
         "Fill in the groups of cells based on ",
         "the hints to their group sizes.",
         "Groups are always separated by at ",
@@ -114,7 +209,21 @@ pub fn play_game_screen(
         "Right click to mark a cell as empty",
         "Left click to fill and find out if",
         "you're correct.",
+        "Hold and drag to fill or mark a",
+        "whole run along a row or column.",
+        "",
+        "Keyboard: arrows or WASD to move,",
+        "Space to fill, X to mark. Hold them",
+        "while moving to paint a run.",
     ];
+    if game_state.is_color() {
+        instructions.extend([
+            "",
+            "Pick a paint below, or press 1-8.",
+            "Groups of different colors don't",
+            "need a gap between them.",
+        ]);
+    }
     for (i, instruction) in instructions.iter().enumerate() {
         gfx.text(instruction)
             .at(instruction_text_position + vec2(0., (font_size * i) as f32))
@@ -122,6 +231,14 @@ pub fn play_game_screen(
             .color(Color::new(palette.group_highlight));
     }
 
+    let hint_label = format!("Hint ({} left)", game_state.hints_remaining);
+    if draw_text_button(hint_btn, &hint_label, palette, &player_input, gfx) {
+        game_state.request_hint();
+    }
+    if draw_text_button(apply_hint_btn, "Apply hint", palette, &player_input, gfx) {
+        game_state.apply_hint();
+    }
+
     if game_state.is_complete() {
         ScreenAction::ChangeScreen {
             to: Screens::WinScreen,
@@ -146,14 +263,13 @@ pub fn win_screen(
     game_state: &mut PlayState,
     ppm: &Ppm,
     frame_context: &mut FrameContext,
+    controls: &mut Controls,
     palette: &ColorPalette,
     loaded_ppms: &LoadedPpms,
 ) -> ScreenAction {
     let gfx = &mut (frame_context.gfx);
     let input = &mut (frame_context.input);
 
-    let player_input = PlayerInput::from(input, gfx);
-
     let unit_size = world_unit_size();
     let win_image_position = unit_size * vec2(11., 2.);
     let win_image_size = unit_size * vec2(14., 14.);
@@ -163,6 +279,17 @@ pub fn win_screen(
 
     let result_text_position = unit_size * vec2(2., 2.);
 
+    // The quit button is the only thing to do here, so every row leads to it.
+    let mut focus = FocusGrid::default();
+    focus.push_row(vec![Rect::new(quit_position, quit_btn_size)]);
+    focus.push_row(vec![Rect::new(quit_position, quit_btn_size)]);
+    let player_input = controls.next_input(&InputFrame {
+        input,
+        mouse_position: mouse_in_world(input, gfx),
+        delta: frame_context.timer.delta,
+        focus: &focus,
+    });
+
     let num_incorrect = game_state.number_incorrect();
     if num_incorrect == 0 {
         gfx.text(&format!("Perfect!"))
@@ -179,6 +306,12 @@ pub fn win_screen(
             .color(Color::new(palette.group_highlight))
             .at(result_text_position + vec2(0., 78.));
     }
+    if game_state.hints_used > 0 {
+        gfx.text(&format!("Hints used: {}", game_state.hints_used))
+            .size(16.)
+            .color(Color::new(palette.group_highlight))
+            .at(result_text_position + vec2(0., 98.));
+    }
 
     draw_ppm_at(ppm, win_image_position, win_image_size, gfx);
 
@@ -198,6 +331,103 @@ pub fn win_screen(
     }
 }
 
+// Picks which day's puzzle to play, today's by default. Days can be stepped
+// back through to replay them, but not forward past today.
+pub fn daily_screen(
+    date: Date,
+    progress: &DailyProgress,
+    frame_context: &mut FrameContext,
+    controls: &mut Controls,
+    palette: &ColorPalette,
+    loaded_ppms: &LoadedPpms,
+) -> ScreenAction {
+    let gfx = &mut (frame_context.gfx);
+    gfx.clear(Color::new(palette.background));
+    let input = &mut (frame_context.input);
+
+    let unit_size = world_unit_size();
+    let today = Date::today();
+    let title_position = unit_size * vec2(16., 3.);
+    let date_position = unit_size * vec2(16., 6.);
+    let text_position = unit_size * vec2(11., 8.);
+    let quit_position = unit_size * vec2(28., 1.);
+    let quit_btn_size = unit_size * vec2(3., 3.);
+    let previous_btn = Rect::new(unit_size * vec2(8., 5.5), unit_size * vec2(2., 1.));
+    let next_btn =
+        (date < today).then(|| Rect::new(unit_size * vec2(22., 5.5), unit_size * vec2(2., 1.)));
+    let play_btn = Rect::new(unit_size * vec2(13., 12.), unit_size * vec2(6., 1.5));
+
+    let mut focus = FocusGrid::default();
+    focus.push_row(vec![Rect::new(quit_position, quit_btn_size)]);
+    focus.push_row(std::iter::once(previous_btn).chain(next_btn).collect());
+    focus.push_row(vec![play_btn]);
+    let player_input = controls.next_input(&InputFrame {
+        input,
+        mouse_position: mouse_in_world(input, gfx),
+        delta: frame_context.timer.delta,
+        focus: &focus,
+    });
+
+    let text_color = Color::new(palette.group_highlight);
+    draw_centered_text(
+        gfx,
+        "Daily puzzle",
+        title_position,
+        2. * unit_size.y,
+        text_color,
+    );
+    let date_label = match date == today {
+        true => format!("{} (today)", date),
+        false => date.to_string(),
+    };
+    draw_centered_text(gfx, &date_label, date_position, unit_size.y, text_color);
+
+    let result = match progress.result(date) {
+        None => "Not solved yet".to_owned(),
+        Some(result) => format!(
+            "Solved with {} hints and {} mistakes",
+            result.hints_used, result.mistakes
+        ),
+    };
+    let streak = format!("Streak: {} days", progress.streak(today));
+    for (i, line) in [result, streak].iter().enumerate() {
+        gfx.text(line)
+            .size(24.)
+            .color(text_color)
+            .at(text_position + vec2(0., 32. * i as f32));
+    }
+
+    let mut action = ScreenAction::NoAction;
+    if draw_text_button(previous_btn, "<", palette, &player_input, gfx) {
+        action = ScreenAction::PreviousDay;
+    }
+    if let Some(next_btn) = next_btn
+        && draw_text_button(next_btn, ">", palette, &player_input, gfx)
+    {
+        action = ScreenAction::NextDay;
+    }
+    let play_label = match progress.result(date) {
+        Some(_) => "Replay",
+        None => "Play",
+    };
+    if draw_text_button(play_btn, play_label, palette, &player_input, gfx) {
+        action = ScreenAction::StartDaily { date };
+    }
+    if let Some(ScreenAction::QuitGame) = draw_quit_button(
+        quit_position,
+        quit_btn_size,
+        &loaded_ppms.quit,
+        palette,
+        &player_input,
+        gfx,
+    ) {
+        action = ScreenAction::ChangeScreen {
+            to: Screens::ChooseLevelScreen { page: 0 },
+        };
+    }
+    action
+}
+
 fn spiral_indices(rows: usize, cols: usize) -> Vec<(usize, usize)> {
     let mut indices = Vec::with_capacity(rows * cols);
 
@@ -291,9 +521,9 @@ pub fn wipe_screen(
 }
 
 pub fn level_select_screen(
-    levels: &[Level],
-    page: usize,
+    listing: LevelListing,
     frame_context: &mut FrameContext,
+    controls: &mut Controls,
     current_level: &mut PlayState,
     current_win_image: &mut Ppm,
     current_path: &mut PathBuf,
@@ -303,8 +533,15 @@ pub fn level_select_screen(
     let levels_per_page = 15;
     let levels_per_row = 5;
     let rows = levels_per_page / levels_per_row;
+    let LevelListing {
+        levels,
+        order,
+        page,
+    } = listing;
+    let levels = order.apply(levels);
     let levels_to_show: Vec<&Level> = levels
         .iter()
+        .copied()
         .skip(levels_per_page * page)
         .take(levels_per_page)
         .collect();
@@ -313,10 +550,6 @@ pub fn level_select_screen(
     gfx.clear(Color::new(palette.background));
 
     let input = &mut (frame_context.input);
-    let (mx, my) = input.mouse_position();
-    let world_xy = gfx.camera().screen_to_world(Vec2::new(mx, my));
-    let left_mouse_pressed = input.mouse_pressed(MouseButton::Left);
-    let player_input = PlayerInput::from(input, gfx);
 
     let mut action = ScreenAction::NoAction;
 
@@ -327,6 +560,59 @@ pub fn level_select_screen(
     let level_bg_position = vec2(8. * x_unit, 6. * y_unit);
     let quit_position = vec2(28. * x_unit, 1. * y_unit);
     let quit_btn_size = vec2(3. * x_unit, 3. * y_unit);
+    let sort_btn = Rect::new(vec2(1. * x_unit, 1. * y_unit), vec2(7. * x_unit, y_unit));
+    let filter_btn = Rect::new(vec2(1. * x_unit, 2.5 * y_unit), vec2(7. * x_unit, y_unit));
+    let random_btn = Rect::new(vec2(1. * x_unit, 4. * y_unit), vec2(7. * x_unit, y_unit));
+    let daily_btn = Rect::new(vec2(1. * x_unit, 5.5 * y_unit), vec2(7. * x_unit, y_unit));
+
+    // TODO: move to ui method
+    // TODO: use GridLayout
+    let padding = vec2(10., 10.);
+    let level_tile_height = (level_bg_size.y - padding.y * 2.) / rows as f32 - padding.y * 2.;
+    let level_tile_size = vec2(level_tile_height, level_tile_height);
+    let centering_x_offset =
+        (level_bg_size.x - (level_tile_height + padding.x) * levels_per_row as f32) / 2.;
+    let centering_y_offset = (level_bg_size.y - (level_tile_height + padding.y) * rows as f32) / 2.;
+    let anchor = level_bg_position + padding + vec2(centering_x_offset, centering_y_offset);
+
+    // draw a long tall < and > for the page buttons.
+    let btn_width = 30.;
+    let btn_size = vec2(btn_width, level_bg_size.y);
+    let previous_btn_position = level_bg_position - vec2(btn_width, 0.) - vec2(padding.x, 0.);
+    let next_btn_position = level_bg_position + vec2(level_bg_size.x, 0.) + vec2(padding.x, 0.);
+    let previous_btn = (page > 0).then(|| Rect::new(previous_btn_position, btn_size));
+    let next_btn = (levels.iter().skip(levels_per_page * (page + 1)).len() > 0)
+        .then(|| Rect::new(next_btn_position, btn_size));
+
+    // Each row of tiles is book ended by the page buttons so that the keyboard
+    // cursor can walk off the side of the page to flip it.
+    let mut focus = FocusGrid::default();
+    focus.push_row(vec![
+        sort_btn,
+        filter_btn,
+        random_btn,
+        daily_btn,
+        Rect::new(quit_position, quit_btn_size),
+    ]);
+    for (r, levels_in_row) in levels_to_show.chunks(levels_per_row).enumerate() {
+        let tiles = (0..levels_in_row.len()).map(|c| {
+            let pos = anchor + vec2(c as f32, r as f32) * (level_tile_size + padding);
+            Rect::new(pos, level_tile_size)
+        });
+        focus.push_row(
+            previous_btn
+                .into_iter()
+                .chain(tiles)
+                .chain(next_btn)
+                .collect(),
+        );
+    }
+    let player_input = controls.next_input(&InputFrame {
+        input,
+        mouse_position: mouse_in_world(input, gfx),
+        delta: frame_context.timer.delta,
+        focus: &focus,
+    });
 
     draw_centered_text(
         gfx,
@@ -352,21 +638,50 @@ pub fn level_select_screen(
         action = quit_action;
     }
 
-    // TODO: move to ui method
-    // TODO: use GridLayout
-    let padding = vec2(10., 10.);
-    let level_tile_height = (level_bg_size.y - padding.y * 2.) / rows as f32 - padding.y * 2.;
-    let level_tile_size = vec2(level_tile_height, level_tile_height);
-    let centering_x_offset =
-        (level_bg_size.x - (level_tile_height + padding.x) * levels_per_row as f32) / 2.;
-    let centering_y_offset = (level_bg_size.y - (level_tile_height + padding.y) * rows as f32) / 2.;
+    let sort_label = if order.by_difficulty {
+        "Sort: difficulty"
+    } else {
+        "Sort: name"
+    };
+    if draw_text_button(sort_btn, sort_label, palette, &player_input, gfx) {
+        action = ScreenAction::ToggleLevelSort;
+    }
+    let filter_label = match order.only {
+        None => "Show: all".to_owned(),
+        Some(band) => format!("Show: {}", band),
+    };
+    if draw_text_button(filter_btn, &filter_label, palette, &player_input, gfx) {
+        action = ScreenAction::NextDifficultyFilter;
+    }
+    if draw_text_button(random_btn, "Random puzzle", palette, &player_input, gfx) {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        let puzzle = generate(&PuzzleRequest {
+            seed,
+            ..PuzzleRequest::default()
+        });
+        action = ScreenAction::ChangeScreen {
+            to: Screens::GameScreen,
+        };
+        *current_level = (&puzzle.info).into();
+        *current_win_image = puzzle.image;
+        // Not one of the levels, so there's no progress to record for it
+        *current_path = PathBuf::new();
+    }
+    if draw_text_button(daily_btn, "Daily puzzle", palette, &player_input, gfx) {
+        action = ScreenAction::ChangeScreen {
+            to: Screens::DailyScreen {
+                date: Date::today(),
+            },
+        };
+    }
 
-    let anchor = level_bg_position + padding + vec2(centering_x_offset, centering_y_offset);
     for (r, levels_in_row) in levels_to_show.chunks(levels_per_row).enumerate() {
         for (c, level) in levels_in_row.into_iter().enumerate() {
             let pos = anchor + vec2(c as f32, r as f32) * (level_tile_size + padding);
             let rect = Rect::new(pos, level_tile_size);
-            let highlight_color = if rect.contains(world_xy) {
+            let highlight_color = if player_input.overlaps(&rect) {
                 Color::new(palette.cell_filled_in)
             } else {
                 Color::new(palette.group_highlight)
@@ -380,11 +695,11 @@ pub fn level_select_screen(
             } else {
                 draw_ppm_at(&loaded_ppms.unknown_level, pos, level_tile_size, gfx);
             }
-            if rect.contains(world_xy) && left_mouse_pressed {
+            if player_input.overlaps(&rect) && player_input.pressed {
                 action = ScreenAction::ChangeScreen {
                     to: Screens::GameScreen,
                 };
-                let to_load: PlayState = (&level.info).into();
+                let to_load: PlayState = (*level).into();
                 *current_level = to_load;
                 *current_win_image = level.image.clone();
                 *current_path = level.path.clone();
@@ -392,15 +707,8 @@ pub fn level_select_screen(
         }
     }
 
-    // draw a long tall < and > for the page buttons.
-    let btn_width = 30.;
-    let btn_size = vec2(btn_width, level_bg_size.y);
-    let previous_btn_position = level_bg_position - vec2(btn_width, 0.) - vec2(padding.x, 0.);
-    let next_btn_position = level_bg_position + vec2(level_bg_size.x, 0.) + vec2(padding.x, 0.);
-
-    if page > 0 {
-        let rect = Rect::new(previous_btn_position, btn_size);
-        let (bg, fg) = if rect.contains(world_xy) {
+    if let Some(rect) = previous_btn {
+        let (bg, fg) = if player_input.overlaps(&rect) {
             (
                 Color::new(palette.group_highlight),
                 Color::new(palette.background),
@@ -411,7 +719,7 @@ pub fn level_select_screen(
                 Color::new(palette.group_highlight),
             )
         };
-        if rect.contains(world_xy) && left_mouse_pressed {
+        if player_input.overlaps(&rect) && player_input.pressed {
             action = ScreenAction::PreviousPage;
         }
         gfx.rect()
@@ -428,9 +736,8 @@ pub fn level_select_screen(
             .close();
     }
 
-    if levels.iter().skip(levels_per_page * (page + 1)).len() > 0 {
-        let rect = Rect::new(next_btn_position, btn_size);
-        let (bg, fg) = if rect.contains(world_xy) {
+    if let Some(rect) = next_btn {
+        let (bg, fg) = if player_input.overlaps(&rect) {
             (
                 Color::new(palette.group_highlight),
                 Color::new(palette.background),
@@ -441,7 +748,7 @@ pub fn level_select_screen(
                 Color::new(palette.group_highlight),
             )
         };
-        if rect.contains(world_xy) && left_mouse_pressed {
+        if player_input.overlaps(&rect) && player_input.pressed {
             action = ScreenAction::NextPage;
         }
         gfx.rect().color(bg).size(btn_size).at(next_btn_position);
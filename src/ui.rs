@@ -1,12 +1,15 @@
+use crate::hints::{Hint, HintLine};
 use crate::netpbm::Ppm;
 use crate::playstate::CellState;
+use crate::playstate::Group;
 use crate::playstate::PlayState;
+use crate::playstate::StrokeKind;
 use crate::screens::ScreenAction;
 use std::fs::read_to_string;
 use std::path::PathBuf;
 
 use egor::{
-    input::{Input, MouseButton},
+    input::Input,
     math::{Rect, Vec2, vec2},
     render::{Color, Graphics},
 };
@@ -23,6 +26,7 @@ pub struct ColorPalette {
     pub cell_incorrect: [f32; 4],
     pub group_highlight: [f32; 4],
     pub group_font: [f32; 4],
+    pub group_crossed: [f32; 4],
 }
 
 fn rgba(r: u8, g: u8, b: u8, a: f32) -> [f32; 4] {
@@ -42,6 +46,18 @@ impl ColorPalette {
             cell_incorrect: rgba(255, 0, 0, 1.0),
             group_highlight: rgba(251, 212, 207, 1.0),
             group_font: rgba(0, 0, 0, 1.0),
+            group_crossed: rgba(46, 220, 255, 0.6),
+        }
+    }
+
+    // Black or white, whichever reads better on top of the given color
+    pub fn text_color_on(&self, color: [f32; 4]) -> [f32; 4] {
+        let [r, g, b, _] = color;
+        let luminance = 0.299 * r + 0.587 * g + 0.114 * b;
+        if luminance > 0.5 {
+            rgba(0, 0, 0, 1.0)
+        } else {
+            rgba(255, 255, 255, 1.0)
         }
     }
 
@@ -54,55 +70,40 @@ impl ColorPalette {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Action {
     FillCell,
     MarkCell,
 }
 
+impl From<&Action> for StrokeKind {
+    fn from(action: &Action) -> StrokeKind {
+        match action {
+            Action::FillCell => StrokeKind::Fill,
+            Action::MarkCell => StrokeKind::Mark,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct PlayerInput {
     // position in world units
     pub position: Vec2,
     pub action: Option<Action>,
+    // true only on the frame the fill button went down, for things like
+    // buttons that should trigger once rather than every frame it's held.
+    pub pressed: bool,
 }
 
 impl PlayerInput {
-    pub fn from(input: &Input, gfx: &mut Graphics) -> PlayerInput {
-        let (mx, my) = input.mouse_position();
-        let world_xy = gfx.camera().screen_to_world(Vec2::new(mx, my));
-        let left_mouse_pressed =
-            input.mouse_pressed(MouseButton::Left) || input.mouse_held(MouseButton::Left);
-        let right_mouse_pressed =
-            input.mouse_pressed(MouseButton::Right) || input.mouse_held(MouseButton::Right);
-
-        PlayerInput {
-            position: world_xy,
-            action: {
-                match (left_mouse_pressed, right_mouse_pressed) {
-                    (false, false) => None,
-                    (true, false) => Some(Action::FillCell),
-                    (_, true) => Some(Action::MarkCell),
-                }
-            },
-        }
-    }
-
-    fn can_fill_at(&self, cell: &Rect) -> bool {
+    pub fn can_fill_at(&self, cell: &Rect) -> bool {
         match self.action {
             Some(Action::FillCell) => cell.contains(self.position),
             _ => false,
         }
     }
 
-    fn can_mark_at(&self, cell: &Rect) -> bool {
-        match self.action {
-            Some(Action::MarkCell) => cell.contains(self.position),
-            _ => false,
-        }
-    }
-
-    fn overlaps(&self, cell: &Rect) -> bool {
+    pub fn overlaps(&self, cell: &Rect) -> bool {
         cell.contains(self.position)
     }
 }
@@ -244,6 +245,32 @@ impl PlayArea {
         (max_row_groups, max_column_groups, layout)
     }
 
+    // What cells painted with the given palette index are drawn in. Monochrome
+    // puzzles don't have a palette so they get the usual fill color.
+    pub fn paint_color(&self, play_state: &PlayState, color: usize) -> [f32; 4] {
+        match color {
+            0 => self.palette.cell_marked_game,
+            color => play_state
+                .palette
+                .get(color - 1)
+                .copied()
+                .unwrap_or(self.palette.cell_filled_in),
+        }
+    }
+
+    // The on screen rectangle of every puzzle cell, row by row, for the
+    // keyboard cursor to hop between.
+    pub fn cell_rects(&self, play_state: &PlayState) -> Vec<Vec<Rect>> {
+        let (origin_y, origin_x, layout) = self.full_layout(play_state);
+        (0..play_state.num_rows)
+            .map(|r| {
+                (0..play_state.num_columns)
+                    .map(|c| layout.cell_rect(origin_y + r, origin_x + c))
+                    .collect()
+            })
+            .collect()
+    }
+
     pub fn draw_backgrounds(
         &self,
         play_state: &PlayState,
@@ -317,6 +344,7 @@ impl PlayArea {
     pub fn draw_grid(&self, play_state: &mut PlayState, input: &PlayerInput, gfx: &mut Graphics) {
         let (origin_y, origin_x, layout) = self.full_layout(&play_state);
         let state_by_rows = play_state.rows();
+        let mut hovered_cell = None;
         for (r, row) in state_by_rows.iter().enumerate() {
             let (even_odd_bg_color, _) = self.palette.even_odd_color(r);
             for (c, cell) in row.iter().enumerate() {
@@ -325,7 +353,9 @@ impl PlayArea {
 
                 let color = match cell {
                     CellState::Empty => Color::new(even_odd_bg_color),
-                    CellState::Filled => Color::new(self.palette.cell_filled_in),
+                    CellState::Filled => {
+                        Color::new(self.paint_color(play_state, play_state.goal_color(r, c)))
+                    }
                     CellState::Incorrect => Color::new(self.palette.cell_incorrect),
                     CellState::RuledOut => Color::new(self.palette.cell_marked_game),
                     CellState::UserRuledOut => Color::new(self.palette.cell_marked_user),
@@ -353,14 +383,109 @@ impl PlayArea {
                     }
                 };
 
-                if input.can_fill_at(&cell_rect) {
-                    play_state.attempt_fill(r, c);
-                }
-                if input.can_mark_at(&cell_rect) {
-                    play_state.mark_cell(r, c);
+                if input.overlaps(&cell_rect) {
+                    hovered_cell = Some((r, c));
                 }
             }
         }
+
+        match (&input.action, hovered_cell) {
+            (None, _) => play_state.end_stroke(),
+            (Some(action), Some((r, c))) => match play_state.stroke {
+                Some(_) => play_state.extend_stroke(r, c),
+                None => play_state.begin_stroke(r, c, action.into()),
+            },
+            // Wandering off the grid mid-drag keeps the stroke alive so the
+            // player can come back without the axis lock resetting on them.
+            (Some(_), None) => {}
+        }
+
+        if let Some(stroke) = &play_state.stroke {
+            let font_size = layout.cell_size().y;
+            gfx.text(&format!("{}", stroke.length()))
+                .at(input.position + vec2(font_size / 2., -font_size))
+                .size(font_size)
+                .color(Color::new(self.palette.group_font));
+        }
+    }
+
+    pub fn draw_hint(&self, play_state: &PlayState, gfx: &mut Graphics) {
+        let Some(hint) = &play_state.hint else {
+            return;
+        };
+        let (origin_y, origin_x, layout) = self.full_layout(play_state);
+        let (first, last) = match hint.line() {
+            HintLine::Row(r) => ((r, 0), (r, play_state.num_columns - 1)),
+            HintLine::Column(c) => ((0, c), (play_state.num_rows - 1, c)),
+        };
+        let first = layout.cell_rect(origin_y + first.0, origin_x + first.1);
+        let last = layout.cell_rect(origin_y + last.0, origin_x + last.1);
+        let line_rect = Rect::new(first.min(), last.max() - first.min());
+        let line_color = match hint {
+            Hint::Deduction { .. } => self.palette.cell_filled_in,
+            Hint::Mistake { .. } => self.palette.cell_incorrect,
+        };
+        draw_outline(line_rect, self.grid_gutter, Color::new(line_color), gfx);
+
+        let Hint::Deduction { cells, .. } = hint else {
+            return;
+        };
+        for &(r, c, color) in cells {
+            let rect = layout.cell_rect(origin_y + r, origin_x + c);
+            let inset = rect.size * 0.3;
+            let color = self.paint_color(play_state, color);
+            draw_outline(
+                rect,
+                self.grid_gutter,
+                Color::new(self.palette.cell_highlight),
+                gfx,
+            );
+            gfx.rect()
+                .at(rect.min() + inset)
+                .size(rect.size - inset * 2.)
+                .color(Color::new(color));
+        }
+    }
+
+    // A clue the player has satisfied gets a line through it, like crossing
+    // it off on paper.
+    fn draw_strike_through(&self, rect: Rect, gfx: &mut Graphics) {
+        let thickness = (rect.size.y * 0.1).max(1.);
+        gfx.rect()
+            .at(rect.min() + vec2(rect.size.x * 0.15, (rect.size.y - thickness) / 2.))
+            .size(vec2(rect.size.x * 0.7, thickness))
+            .color(Color::new(self.palette.cell_marked_game));
+    }
+
+    fn draw_clue(&self, play_state: &PlayState, group: &Group, rect: Rect, gfx: &mut Graphics) {
+        // for some reason fonts position their _center_ at the position we tell
+        // them to be. So just add half in to get the real placement location
+        let position = rect.min() + rect.size / 2.;
+        let text = &format!("{}", group.num_cells);
+        let mut font_color = match group.filled {
+            true => self.palette.group_crossed,
+            false => self.palette.group_font,
+        };
+
+        // Color clues sit on a swatch of their color, text alone is too hard
+        // to read for the lighter ones.
+        if play_state.is_color() && group.num_cells > 0 {
+            let mut swatch = self.paint_color(play_state, group.color);
+            font_color = self.palette.text_color_on(swatch);
+            if group.filled {
+                swatch[3] *= 0.4;
+            }
+            let inset = rect.size * 0.1;
+            gfx.rect()
+                .at(rect.min() + inset)
+                .size(rect.size - inset * 2.)
+                .color(Color::new(swatch));
+        }
+
+        draw_centered_text(gfx, text, position, rect.size.y, Color::new(font_color));
+        if group.filled {
+            self.draw_strike_through(rect, gfx);
+        }
     }
 
     pub fn draw_row_groups(&self, play_state: &PlayState, gfx: &mut Graphics) {
@@ -372,16 +497,7 @@ impl PlayArea {
             for (i, group) in groups.iter().enumerate() {
                 let column = start_col + i;
                 let rect = layout.cell_rect(origin_y + r, column);
-                // for some reason fonts position their _center_ at the position we tell
-                // them to be. So just add half in to get the real placement location
-                let position = rect.min() + rect.size / 2.;
-                let text = &format!("{}", group.num_cells);
-                let font_color = match group.filled {
-                    true => Color::new(self.palette.cell_filled_in),
-                    false => Color::new(self.palette.group_font),
-                };
-
-                draw_centered_text(gfx, text, position, rect.size.y, font_color);
+                self.draw_clue(play_state, group, rect, gfx);
             }
         }
     }
@@ -395,16 +511,7 @@ impl PlayArea {
             for (i, group) in groups.iter().enumerate() {
                 let row = start_row + i;
                 let rect = layout.cell_rect(row, origin_x + c);
-                // for some reason fonts position their _center_ at the position we tell
-                // them to be. So just add half in to get the real placement location
-                let position = rect.min() + rect.size / 2.;
-                let text = &format!("{}", group.num_cells);
-                let font_color = match group.filled {
-                    true => Color::new(self.palette.cell_filled_in),
-                    false => Color::new(self.palette.group_font),
-                };
-
-                draw_centered_text(gfx, text, position, rect.size.y, font_color);
+                self.draw_clue(play_state, group, rect, gfx);
             }
         }
     }
@@ -428,6 +535,11 @@ pub fn draw_ppm_at(ppm: &Ppm, top_left: Vec2, size: Vec2, gfx: &mut Graphics) {
     }
 }
 
+pub fn mouse_in_world(input: &Input, gfx: &mut Graphics) -> Vec2 {
+    let (mx, my) = input.mouse_position();
+    gfx.camera().screen_to_world(Vec2::new(mx, my))
+}
+
 pub fn draw_centered_text(
     gfx: &mut egor::render::Graphics,
     text: &str,
@@ -500,6 +612,74 @@ pub fn draw_quit_button(
     return action;
 }
 
+pub fn draw_outline(rect: Rect, thickness: f32, color: Color, gfx: &mut Graphics) {
+    let min = rect.min() - thickness;
+    let size = rect.size + thickness * 2.;
+    gfx.rect()
+        .at(min)
+        .size(vec2(size.x, thickness))
+        .color(color);
+    gfx.rect()
+        .at(min + vec2(0., size.y - thickness))
+        .size(vec2(size.x, thickness))
+        .color(color);
+    gfx.rect()
+        .at(min)
+        .size(vec2(thickness, size.y))
+        .color(color);
+    gfx.rect()
+        .at(min + vec2(size.x - thickness, 0.))
+        .size(vec2(thickness, size.y))
+        .color(color);
+}
+
+// Returns true on the frame the button is clicked
+pub fn draw_text_button(
+    rect: Rect,
+    label: &str,
+    palette: &ColorPalette,
+    input: &PlayerInput,
+    gfx: &mut Graphics,
+) -> bool {
+    let (bg, fg) = if input.overlaps(&rect) {
+        (palette.group_highlight, palette.background)
+    } else {
+        (palette.background, palette.group_highlight)
+    };
+    gfx.rect()
+        .at(rect.min() - 4.)
+        .size(rect.size + 8.)
+        .color(Color::new(palette.group_highlight));
+    gfx.rect()
+        .at(rect.min())
+        .size(rect.size)
+        .color(Color::new(bg));
+    draw_centered_text(gfx, label, rect.center(), rect.size.y * 0.6, Color::new(fg));
+    input.overlaps(&rect) && input.pressed
+}
+
+// A square of paint to pick the active color with. Returns true on the frame
+// it's clicked.
+pub fn draw_swatch_button(
+    rect: Rect,
+    color: [f32; 4],
+    selected: bool,
+    palette: &ColorPalette,
+    input: &PlayerInput,
+    gfx: &mut Graphics,
+) -> bool {
+    if selected {
+        draw_outline(rect, 4., Color::new(palette.cell_highlight), gfx);
+    } else if input.overlaps(&rect) {
+        draw_outline(rect, 2., Color::new(palette.group_highlight), gfx);
+    }
+    gfx.rect()
+        .at(rect.min())
+        .size(rect.size)
+        .color(Color::new(color));
+    input.overlaps(&rect) && input.pressed
+}
+
 // Returns a tuple that you can use to compute
 // x,y locations in world units. Assuming a screen
 // of 32 by 18 for placement.
@@ -0,0 +1,567 @@
+use crate::editor::difficulty::{DifficultyBand, trace_solve};
+use crate::editor::solver::{SolvedState, TheMultiVerseOfLines};
+use crate::levels::{Level, LevelLoadError, PuzzleKind, load_levels_from_dir};
+use crate::playstate::PlayState;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// Everything checked about one level
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelReport {
+    pub path: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    // 1 for monochrome levels
+    pub colors: usize,
+    pub state: SolvedState,
+    // Only rated when there's a single solution to rate
+    pub difficulty: Option<u32>,
+    // The reward picture, which should be the same size as the puzzle
+    pub image_width: usize,
+    pub image_height: usize,
+    // The first level found with exactly the same mask
+    pub duplicate_of: Option<PathBuf>,
+    pub millis: f64,
+}
+
+// The one rule for whether a solve fails a level, for validating and for
+// solving files alike. Needing a guess still leaves only the one picture, so
+// that gets warned about instead.
+pub fn state_fails(state: SolvedState) -> bool {
+    matches!(
+        state,
+        SolvedState::Unsolvable | SolvedState::MultipleSolutions
+    )
+}
+
+fn describe_state(state: SolvedState) -> &'static str {
+    match state {
+        SolvedState::UniqueSolution => "one solution",
+        SolvedState::Unsolvable => "no solution",
+        SolvedState::UniqueRequiresGuessing => "only one solution, but it needs guessing",
+        SolvedState::MultipleSolutions => "more than one solution",
+    }
+}
+
+impl LevelReport {
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if state_fails(self.state) {
+            problems.push(describe_state(self.state).to_owned());
+        }
+        if (self.image_width, self.image_height) != (self.width, self.height) {
+            problems.push(format!(
+                "image is {}x{} but the puzzle is {}x{}",
+                self.image_width, self.image_height, self.width, self.height
+            ));
+        }
+        if let Some(original) = &self.duplicate_of {
+            problems.push(format!("same mask as {}", original.display()));
+        }
+        problems
+    }
+
+    pub fn passed(&self) -> bool {
+        self.problems().is_empty()
+    }
+
+    // Worth knowing about, but not enough to fail the level
+    pub fn warnings(&self) -> Vec<String> {
+        match self.state {
+            SolvedState::UniqueRequiresGuessing => vec![describe_state(self.state).to_owned()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ValidationReport {
+    pub levels: Vec<LevelReport>,
+    // Directories that couldn't be loaded and why. Loading stops at the first
+    // broken level, so none of that directory's levels are in the report.
+    pub load_errors: Vec<(PathBuf, LevelLoadError)>,
+}
+
+impl ValidationReport {
+    pub fn failures(&self) -> usize {
+        self.levels.iter().filter(|level| !level.passed()).count() + self.load_errors.len()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures() == 0
+    }
+
+    pub fn warned(&self) -> usize {
+        self.levels
+            .iter()
+            .filter(|level| !level.warnings().is_empty())
+            .count()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Table => self.to_table(),
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Junit => self.to_junit(),
+        }
+    }
+
+    fn to_table(&self) -> String {
+        let path_width = self
+            .levels
+            .iter()
+            .map(|level| level.path.display().to_string().len())
+            .chain(std::iter::once("level".len()))
+            .max()
+            .unwrap_or_default();
+        let mut table = format!(
+            "{:<path_width$}  {:<7}  {:<6}  {:<22}  {:<14}  result\n",
+            "level", "size", "colors", "state", "difficulty"
+        );
+        for level in &self.levels {
+            let problems = level.problems();
+            let warnings = level.warnings();
+            let result = match (problems.is_empty(), warnings.is_empty()) {
+                (true, true) => "ok".to_owned(),
+                (true, false) => format!("ok, warning: {}", warnings.join(", ")),
+                (false, _) => format!("FAIL: {}", problems.join(", ")),
+            };
+            table.push_str(&format!(
+                "{:<path_width$}  {:<7}  {:<6}  {:<22}  {:<14}  {}\n",
+                level.path.display(),
+                format!("{}x{}", level.width, level.height),
+                level.colors,
+                format!("{:?}", level.state),
+                describe_difficulty(level.difficulty),
+                result
+            ));
+        }
+        for (path, error) in &self.load_errors {
+            table.push_str(&format!("{}: FAIL: {}\n", path.display(), error));
+        }
+        table.push_str(&format!(
+            "{} levels, {} failed",
+            self.levels.len(),
+            self.failures()
+        ));
+        match self.warned() {
+            0 => table.push('\n'),
+            warned => table.push_str(&format!(", {} with warnings\n", warned)),
+        }
+        table
+    }
+
+    fn to_json(&self) -> String {
+        let levels: Vec<String> = self
+            .levels
+            .iter()
+            .map(|level| {
+                let problems: Vec<String> = level
+                    .problems()
+                    .iter()
+                    .map(|problem| json_string(problem))
+                    .collect();
+                let warnings: Vec<String> = level
+                    .warnings()
+                    .iter()
+                    .map(|warning| json_string(warning))
+                    .collect();
+                format!(
+                    "{{\"path\":{},\"width\":{},\"height\":{},\"colors\":{},\"state\":\"{:?}\",\"difficulty\":{},\"band\":{},\"image_width\":{},\"image_height\":{},\"duplicate_of\":{},\"millis\":{:.3},\"passed\":{},\"problems\":[{}],\"warnings\":[{}]}}",
+                    json_string(&level.path.display().to_string()),
+                    level.width,
+                    level.height,
+                    level.colors,
+                    level.state,
+                    level
+                        .difficulty
+                        .map_or("null".to_owned(), |rating| rating.to_string()),
+                    level.difficulty.map_or("null".to_owned(), |rating| {
+                        json_string(&DifficultyBand::of(rating).to_string())
+                    }),
+                    level.image_width,
+                    level.image_height,
+                    level.duplicate_of.as_ref().map_or("null".to_owned(), |path| {
+                        json_string(&path.display().to_string())
+                    }),
+                    level.millis,
+                    level.passed(),
+                    problems.join(","),
+                    warnings.join(",")
+                )
+            })
+            .collect();
+        let load_errors: Vec<String> = self
+            .load_errors
+            .iter()
+            .map(|(path, error)| {
+                format!(
+                    "{{\"path\":{},\"error\":{}}}",
+                    json_string(&path.display().to_string()),
+                    json_string(&error.to_string())
+                )
+            })
+            .collect();
+        format!(
+            "{{\"passed\":{},\"failures\":{},\"levels\":[\n{}\n],\"load_errors\":[{}]}}\n",
+            self.passed(),
+            self.failures(),
+            levels.join(",\n"),
+            load_errors.join(",\n")
+        )
+    }
+
+    // One test case per level, named after its file and grouped by directory,
+    // which is how most dashboards expect JUnit results to look
+    fn to_junit(&self) -> String {
+        let seconds: f64 = self.levels.iter().map(|level| level.millis).sum::<f64>() / 1000.;
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites>\n  <testsuite name=\"levels\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+            self.levels.len() + self.load_errors.len(),
+            self.failures() - self.load_errors.len(),
+            self.load_errors.len(),
+            seconds
+        ));
+        for level in &self.levels {
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                xml_escape(&directory_of(&level.path)),
+                xml_escape(&file_name_of(&level.path)),
+                level.millis / 1000.
+            ));
+            let problems = level.problems();
+            let warnings = level.warnings();
+            if problems.is_empty() {
+                match warnings.is_empty() {
+                    true => xml.push_str("/>\n"),
+                    // JUnit has nothing for warnings, so they go in the output
+                    false => xml.push_str(&format!(
+                        ">\n      <system-out>warning: {}</system-out>\n    </testcase>\n",
+                        xml_escape(&warnings.join(", "))
+                    )),
+                }
+                continue;
+            }
+            xml.push_str(&format!(
+                ">\n      <failure message=\"{}\">{:?}, {}x{}, {} colors, difficulty {}</failure>\n    </testcase>\n",
+                xml_escape(&problems.join(", ")),
+                level.state,
+                level.width,
+                level.height,
+                level.colors,
+                xml_escape(&describe_difficulty(level.difficulty))
+            ));
+        }
+        for (path, error) in &self.load_errors {
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\">\n      <error message=\"{}\"/>\n    </testcase>\n",
+                xml_escape(&path.display().to_string()),
+                "load",
+                xml_escape(&error.to_string())
+            ));
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Table,
+    Json,
+    Junit,
+}
+
+impl ReportFormat {
+    pub const ALL: [ReportFormat; 3] =
+        [ReportFormat::Table, ReportFormat::Json, ReportFormat::Junit];
+}
+
+impl std::fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            ReportFormat::Table => "table",
+            ReportFormat::Json => "json",
+            ReportFormat::Junit => "junit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownReportFormat {
+    pub found: String,
+}
+
+impl std::error::Error for UnknownReportFormat {}
+
+impl std::fmt::Display for UnknownReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "unknown report format {}, expected table, json or junit",
+            self.found
+        )
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = UnknownReportFormat;
+    fn from_str(s: &str) -> Result<ReportFormat, UnknownReportFormat> {
+        ReportFormat::ALL
+            .into_iter()
+            .find(|format| format.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| UnknownReportFormat {
+                found: s.to_owned(),
+            })
+    }
+}
+
+// Loads every level in each directory and checks them all, spread over as
+// many threads as there are cores
+pub fn validate_dirs(dirs: &[PathBuf]) -> ValidationReport {
+    let mut levels = Vec::new();
+    let mut load_errors = Vec::new();
+    for dir in dirs {
+        match load_levels_from_dir(dir) {
+            Ok(found) => levels.extend(found),
+            Err(error) => load_errors.push((dir.clone(), error)),
+        }
+    }
+    ValidationReport {
+        levels: validate_levels(&levels),
+        load_errors,
+    }
+}
+
+// Reports come back in the same order as the levels
+pub fn validate_levels(levels: &[Level]) -> Vec<LevelReport> {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |count| count.get())
+        .min(levels.len())
+        .max(1);
+    let next = AtomicUsize::new(0);
+    let mut checked: Vec<(usize, LevelReport)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut checked = Vec::new();
+                    // Levels are handed out one at a time since some take
+                    // far longer to solve than others
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(level) = levels.get(idx) else {
+                            break;
+                        };
+                        checked.push((idx, check_level(level)));
+                    }
+                    checked
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("validation worker panicked"))
+            .collect()
+    });
+    checked.sort_by_key(|&(idx, _)| idx);
+
+    let mut first_with_mask: HashMap<(usize, usize, Vec<u16>), PathBuf> = HashMap::new();
+    checked
+        .into_iter()
+        .map(|(idx, mut report)| {
+            let level = &levels[idx];
+            let mask = (level.info.width, level.info.height, mask_of(level));
+            report.duplicate_of = first_with_mask.get(&mask).cloned();
+            first_with_mask
+                .entry(mask)
+                .or_insert_with(|| level.path.clone());
+            report
+        })
+        .collect()
+}
+
+fn check_level(level: &Level) -> LevelReport {
+    let start = Instant::now();
+    let play_state: PlayState = level.into();
+    let mut multiverse = TheMultiVerseOfLines::new(&play_state);
+    multiverse.collapse();
+    let state = multiverse.state();
+    let difficulty = match state {
+        SolvedState::UniqueSolution | SolvedState::UniqueRequiresGuessing => {
+            Some(trace_solve(&play_state).rating())
+        }
+        _ => None,
+    };
+    LevelReport {
+        path: level.path.clone(),
+        width: level.info.width,
+        height: level.info.height,
+        colors: level.kind.palette().map_or(1, |palette| palette.len()),
+        state,
+        difficulty,
+        image_width: level.image.width,
+        image_height: level.image.height,
+        duplicate_of: None,
+        millis: start.elapsed().as_secs_f64() * 1000.,
+    }
+}
+
+// What each cell is painted with, 0 for empty, so monochrome and color
+// levels can be compared the same way
+fn mask_of(level: &Level) -> Vec<u16> {
+    match &level.kind {
+        PuzzleKind::Monochrome => level
+            .info
+            .cells
+            .iter()
+            .map(|&filled| filled as u16)
+            .collect(),
+        PuzzleKind::Color { mask, .. } => mask.cells.clone(),
+    }
+}
+
+fn describe_difficulty(difficulty: Option<u32>) -> String {
+    match difficulty {
+        Some(rating) => format!("{} ({})", rating, DifficultyBand::of(rating)),
+        None => "unrated".to_owned(),
+    }
+}
+
+fn directory_of(path: &Path) -> String {
+    path.parent()
+        .map_or(String::new(), |dir| dir.display().to_string())
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned())
+}
+
+pub fn json_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn xml_escape(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+    use crate::levels::LevelStats;
+    use crate::netpbm::{Pbm, Ppm};
+
+    fn level(name: &str, cells: Vec<bool>, image_width: usize) -> Level {
+        let info = Pbm {
+            width: 2,
+            height: 2,
+            cells,
+        };
+        Level {
+            image: Ppm {
+                width: image_width,
+                height: 2,
+                max_value: 255,
+                cells: vec![[0, 0, 0]; image_width * 2],
+            },
+            info,
+            kind: PuzzleKind::Monochrome,
+            completed: false,
+            stats: LevelStats::default(),
+            difficulty: None,
+            path: PathBuf::from(name),
+        }
+    }
+
+    #[test]
+    fn shipped_levels_have_one_solution_and_match_their_images() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("levels");
+        let report = validate_dirs(&[dir]);
+        assert!(!report.levels.is_empty());
+        assert!(report.load_errors.is_empty());
+        assert!(report.passed(), "{}", report.render(ReportFormat::Table));
+        for level in &report.levels {
+            assert!(level.difficulty.is_some());
+            assert_eq!(
+                (level.width, level.height),
+                (level.image_width, level.image_height)
+            );
+            assert_eq!(None, level.duplicate_of);
+        }
+    }
+
+    #[test]
+    fn problems_are_found_and_reported_in_order() {
+        let levels = [
+            level("levels/full.level", vec![true; 4], 2),
+            // Two diagonals fit the same clues
+            level("levels/diagonal.level", vec![true, false, false, true], 2),
+            level("levels/wide.level", vec![true, true, false, false], 3),
+            level("other/full.level", vec![true; 4], 2),
+        ];
+        let report = ValidationReport {
+            levels: validate_levels(&levels),
+            load_errors: vec![(
+                PathBuf::from("missing"),
+                LevelLoadError::InvalidDirectory(PathBuf::from("missing")),
+            )],
+        };
+        let paths: Vec<_> = report.levels.iter().map(|level| &level.path).collect();
+        assert_eq!(
+            levels.iter().map(|level| &level.path).collect::<Vec<_>>(),
+            paths
+        );
+        assert!(report.levels[0].passed());
+        assert_eq!(SolvedState::MultipleSolutions, report.levels[1].state);
+        assert_eq!(
+            vec!["image is 3x2 but the puzzle is 2x2".to_owned()],
+            report.levels[2].problems()
+        );
+        assert_eq!(
+            Some(PathBuf::from("levels/full.level")),
+            report.levels[3].duplicate_of
+        );
+        assert_eq!(4, report.failures());
+
+        let table = report.render(ReportFormat::Table);
+        assert!(table.contains("FAIL: more than one solution"));
+        assert!(table.ends_with("4 levels, 4 failed\n"));
+        let json = report.render(ReportFormat::Json);
+        assert!(json.starts_with("{\"passed\":false,\"failures\":4,"));
+        assert!(json.contains("\"duplicate_of\":\"levels/full.level\""));
+        let junit = report.render(ReportFormat::Junit);
+        assert!(junit.contains("tests=\"5\" failures=\"3\" errors=\"1\""));
+        assert!(junit.contains("<testcase classname=\"levels\" name=\"full.level\""));
+        assert!(junit.contains("&quot;missing&quot; is not a directory"));
+    }
+
+    #[test]
+    fn formats_are_named_the_way_they_are_written() {
+        for format in ReportFormat::ALL {
+            assert_eq!(Ok(format), format.to_string().parse());
+        }
+        assert!("xml".parse::<ReportFormat>().is_err());
+    }
+}